@@ -1,34 +1,42 @@
-use crate::dex::{get_account_data, DexType, MintVault};
+use crate::dex::{check_stale_state, is_cache_synced};
 use crate::executor::Executor;
-use crate::graph::HopPath;
+use crate::graph::{AmountSearchConfig, HopPath};
 use crate::grpc_processor::BalanceChangeInfo;
 use crate::grpc_subscribe::GrpcTransactionMsg;
 use crate::metadata::get_arb_mint_ata_amount;
 use crate::{HopPathSearchResult, HopPathTypes, SearchResult};
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
 use base58::ToBase58;
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
 use parking_lot::RwLock;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use solana_sdk::pubkey::Pubkey;
 use std::ops::{Div, Mul};
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::broadcast::error::RecvError;
-use tokio::sync::broadcast::Sender;
 use tokio::task::JoinSet;
 use tokio::time::Instant;
-use tracing::{error, info, warn};
+use tracing::{error, info};
 
+/// 单个arb mint的套利参数
+#[derive(Debug, Clone)]
+pub struct ArbMintConfig {
+    pub mint: Arc<Pubkey>,
+    pub amount_in: u64,
+    pub min_profit: u64,
+    // 最大amount_in = ATA余额 * bps_numerator / bps_denominator
+    pub bps_numerator: u64,
+    pub bps_denominator: u64,
+}
+
+#[derive(Clone)]
 pub struct Arb {
     arb_size: usize,
-    arb_amount_in: u64,
-    arb_min_profit: u64,
-    arb_mint: Arc<Pubkey>,
-    arb_mint_bps_numerator: u64,
-    arb_mint_bps_denominator: u64,
+    arb_mints: Arc<Vec<ArbMintConfig>>,
+    // 触发路由前将交易的post balance写入缓存
+    apply_tx_balances: bool,
+    amount_search: AmountSearchConfig,
     executor: Arc<dyn Executor>,
     hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
 }
@@ -36,21 +44,17 @@ pub struct Arb {
 impl Arb {
     pub fn new(
         arb_size: usize,
-        arb_amount_in: u64,
-        arb_min_profit: u64,
-        arb_mint: Pubkey,
-        arb_mint_bps_numerator: u64,
-        arb_mint_bps_denominator: u64,
+        arb_mints: Vec<ArbMintConfig>,
+        apply_tx_balances: bool,
+        amount_search: AmountSearchConfig,
         executor: Arc<dyn Executor>,
         hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
     ) -> Self {
         Self {
             arb_size,
-            arb_amount_in,
-            arb_min_profit,
-            arb_mint: Arc::new(arb_mint),
-            arb_mint_bps_numerator,
-            arb_mint_bps_denominator,
+            arb_mints: Arc::new(arb_mints),
+            apply_tx_balances,
+            amount_search,
             executor,
             hop_paths,
         }
@@ -61,85 +65,14 @@ impl Arb {
         join_set: &mut JoinSet<()>,
         cached_message_receiver: flume::Receiver<GrpcTransactionMsg>,
     ) {
-        let arb_size = self.arb_size as u64;
-        for index in 0..arb_size {
-            let arb_amount_in = self.arb_amount_in;
-            let executor = self.executor.clone();
-            let arb_min_profit = self.arb_min_profit.clone();
-            let arb_mint = self.arb_mint.clone();
-            let arb_mint_bps_numerator = self.arb_mint_bps_numerator.clone();
-            let arb_mint_bps_denominator = self.arb_mint_bps_denominator.clone();
-            let mut receiver = cached_message_receiver.clone();
-            let best_hop_path_searcher = self.hop_paths.clone();
-            static COUNT: AtomicUsize = AtomicUsize::new(0);
+        for index in 0..self.arb_size as u64 {
+            let arb = self.clone();
+            let receiver = cached_message_receiver.clone();
             join_set.spawn(async move {
                 loop {
                     match receiver.recv_async().await {
                         Ok(transaction_msg) => {
-                            let tx = transaction_msg.transaction.unwrap();
-                            let meta = transaction_msg.meta.unwrap();
-                            let balance_change_infos =
-                                BalanceChangeInfo::collect_balance_change_infos(
-                                    transaction_msg.signature.as_slice(),
-                                    tx.message,
-                                    meta,
-                                );
-                            if let Some(changed_balances) = balance_change_infos {
-                                // 触发路由计算
-                                let trigger_instant = Instant::now();
-                                if let Some(best_path) = Self::trigger_quote(
-                                    best_hop_path_searcher.clone(),
-                                    arb_mint.clone(),
-                                    arb_amount_in,
-                                    arb_min_profit,
-                                    arb_mint_bps_numerator,
-                                    arb_mint_bps_denominator,
-                                    changed_balances,
-                                ) {
-                                    let trigger_quote_cost = trigger_instant.elapsed();
-                                    let quote_info: String = best_path.information();
-                                    let tx = transaction_msg
-                                        .signature
-                                        .as_slice()
-                                        .to_base58()
-                                        .chars()
-                                        .take(4)
-                                        .collect::<String>();
-                                    // 有获利路径后生成指令，发送指令
-                                    let msg = executor
-                                        .execute(best_path, tx.clone(), transaction_msg.slot)
-                                        .await
-                                        .unwrap_or_else(|e| format!("发送交易失败，原因：{}", e));
-                                    let all_cost = transaction_msg.instant.elapsed().as_micros() as f64 / 1000.0;
-                                    let quote_cost = trigger_quote_cost.as_micros();
-                                    let timestamp = transaction_msg.created_at;
-                                    let datetime: DateTime<
-                                        Utc> = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp_opt(timestamp.seconds, timestamp.nanos as u32).unwrap(), Utc);
-                                    info!(
-                                        "\nArb_{index} ==> 耗时 : {:>4.2}ms, \
-                                        路由 : {:>4.2}μs, \
-                                        {} \n路径 : {}, tx : {},  Slot : {}, Time: {}, Created_at : {}",
-                                        all_cost,
-                                        quote_cost,
-                                        msg,
-                                        quote_info,
-                                        tx,
-                                        transaction_msg.slot,
-                                        transaction_msg
-                                            .received_timestamp
-                                            .format("%Y-%m-%d %H:%M:%S.%3f"),
-                                        datetime.format("%Y-%m-%d %H:%M:%S.%3f")
-                                    );
-                                }
-                                // else {
-                                //     if let Some(tx) = log_info.as_ref() {
-                                //         warn!(
-                                //             "Arb_{index}持有Tx未寻找到可套利路径， tx : {:?}",
-                                //             tx
-                                //         );
-                                //     }
-                                // }
-                            }
+                            arb.process_transaction(index, transaction_msg).await;
                         }
                         Err(_) => {
                             error!("Arb_{index} 接收消息失败，原因：所有的Processor关闭");
@@ -151,29 +84,227 @@ impl Arb {
         }
     }
 
+    /// 处理一笔交易，交易改变了池子的金库余额时触发路由，返回路由耗时
+    pub async fn process_transaction(
+        &self,
+        index: u64,
+        mut transaction_msg: GrpcTransactionMsg,
+    ) -> Option<Duration> {
+        // GRPC重连后缓存重新加载完成前不触发路由
+        if !is_cache_synced() {
+            return None;
+        }
+        let tx = transaction_msg.transaction.take().unwrap();
+        let meta = transaction_msg.meta.take().unwrap();
+        let changed_balances = BalanceChangeInfo::collect_balance_change_infos(
+            transaction_msg.signature.as_slice(),
+            tx.message,
+            meta,
+        )?;
+        if self.apply_tx_balances {
+            for balance in changed_balances.iter() {
+                balance.apply_post_balance(transaction_msg.slot, transaction_msg.index);
+            }
+        }
+        // 触发路由计算
+        let trigger_instant = Instant::now();
+        let best_paths = Self::trigger_quote(
+            self.hop_paths.clone(),
+            self.arb_mints.as_slice(),
+            &self.amount_search,
+            changed_balances,
+            transaction_msg.slot,
+        );
+        let trigger_quote_cost = trigger_instant.elapsed();
+        // 不同arb mint的利润单位不同，各自的最佳路径分别发送
+        join_all(
+            best_paths.into_iter().map(|best_path| {
+                self.execute(index, best_path, &transaction_msg, trigger_quote_cost)
+            }),
+        )
+        .await;
+        Some(trigger_quote_cost)
+    }
+
+    /// 有获利路径后生成指令，发送指令
+    async fn execute(
+        &self,
+        index: u64,
+        best_path: HopPathSearchResult,
+        transaction_msg: &GrpcTransactionMsg,
+        trigger_quote_cost: Duration,
+    ) {
+        let quote_info: String = best_path.information();
+        let tx = transaction_msg
+            .signature
+            .as_slice()
+            .to_base58()
+            .chars()
+            .take(4)
+            .collect::<String>();
+        let msg = self
+            .executor
+            .execute(best_path, tx.clone(), transaction_msg.slot)
+            .await
+            .unwrap_or_else(|e| format!("发送交易失败，原因：{}", e));
+        let all_cost = transaction_msg.instant.elapsed().as_micros() as f64 / 1000.0;
+        let quote_cost = trigger_quote_cost.as_micros();
+        let timestamp = transaction_msg.created_at;
+        let datetime: DateTime<Utc> =
+            DateTime::from_timestamp(timestamp.seconds, timestamp.nanos as u32).unwrap();
+        info!(
+            "\nArb_{index} ==> 耗时 : {:>4.2}ms, \
+            路由 : {:>4.2}μs, \
+            {} \n路径 : {}, tx : {},  Slot : {}, Time: {}, Created_at : {}",
+            all_cost,
+            quote_cost,
+            msg,
+            quote_info,
+            tx,
+            transaction_msg.slot,
+            transaction_msg
+                .received_timestamp
+                .format("%Y-%m-%d %H:%M:%S.%3f"),
+            datetime.format("%Y-%m-%d %H:%M:%S.%3f")
+        );
+    }
+
+    /// slot : 触发交易所在的slot，返回每个arb mint利润最大的路径
     fn trigger_quote(
         hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
-        arb_mint: Arc<Pubkey>,
-        arb_amount_in: u64,
-        arb_min_profit: u64,
-        arb_mint_bps_numerator: u64,
-        arb_mint_bps_denominator: u64,
+        arb_mints: &[ArbMintConfig],
+        amount_search: &AmountSearchConfig,
         balances: Vec<BalanceChangeInfo>,
-    ) -> Option<HopPathSearchResult> {
-        let arb_max_amount_in = get_arb_mint_ata_amount()?
-            .mul(arb_mint_bps_numerator)
-            .div(arb_mint_bps_denominator);
-        hop_paths
+        slot: u64,
+    ) -> Vec<HopPathSearchResult> {
+        // 钱包中没有余额的arb mint不参与搜索
+        let arb_mints = arb_mints
+            .iter()
+            .filter_map(|arb_mint| {
+                let max_amount_in = get_arb_mint_ata_amount(arb_mint.mint.as_ref())?
+                    .mul(arb_mint.bps_numerator)
+                    .div(arb_mint.bps_denominator);
+                Some((arb_mint, max_amount_in))
+            })
+            .collect::<Vec<_>>();
+        if arb_mints.is_empty() {
+            return vec![];
+        }
+        // 同一笔交易可能经过多个pool，去重后全部触发
+        let mut seen_pools = AHashSet::with_capacity(balances.len());
+        let pool_ids = balances
+            .iter()
+            .filter(|balance| seen_pools.insert(balance.pool_id))
+            .map(|balance| balance.pool_id)
+            // 交易写入了池子和金库，缓存还没有收到更新时按旧数据计算的路径大概率失败
+            .filter(|pool_id| {
+                let accounts = balances
+                    .iter()
+                    .filter(|balance| balance.pool_id == *pool_id)
+                    .map(|balance| balance.vault_account)
+                    .chain(std::iter::once(*pool_id))
+                    .collect::<Vec<_>>();
+                check_stale_state(pool_id, accounts.as_slice(), slot).is_ok()
+            })
+            .collect::<Vec<_>>();
+        let hop_paths = hop_paths.as_slice();
+        let arb_mints = arb_mints.as_slice();
+        let results = pool_ids
             .par_iter()
-            .filter_map(|best_hop_path_searcher| {
-                best_hop_path_searcher.read().find_best_hop_path(
-                    balances.first().unwrap().pool_id,
-                    arb_mint.clone(),
-                    arb_amount_in,
-                    arb_max_amount_in,
-                    arb_min_profit,
-                )
+            .flat_map_iter(|pool_id| {
+                arb_mints.iter().flat_map(move |arb_mint| {
+                    hop_paths.iter().map(move |best_hop_path_searcher| {
+                        (pool_id, arb_mint, best_hop_path_searcher)
+                    })
+                })
             })
-            .max_by_key(|a| a.profit())
+            .filter_map(
+                |(pool_id, (arb_mint, max_amount_in), best_hop_path_searcher)| {
+                    best_hop_path_searcher.read().find_best_hop_path(
+                        *pool_id,
+                        arb_mint.mint.clone(),
+                        arb_mint.amount_in,
+                        *max_amount_in,
+                        arb_mint.min_profit,
+                        amount_search,
+                    )
+                },
+            )
+            .collect::<Vec<_>>();
+        best_per_arb_mint(results)
+    }
+}
+
+/// 按arb mint分组，每组利润最大的结果，不同arb mint的利润单位不同，不互相比较
+fn best_per_arb_mint<T: SearchResult>(results: Vec<T>) -> Vec<T> {
+    let mut best = AHashMap::<Pubkey, T>::new();
+    for result in results {
+        let (_, arb_mint) = result.amount_in();
+        match best.get(&arb_mint) {
+            Some(current) if current.profit() >= result.profit() => {}
+            _ => {
+                best.insert(arb_mint, result);
+            }
+        }
+    }
+    best.into_values().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::arb::best_per_arb_mint;
+    use crate::dex::InstructionMaterial;
+    use crate::graph::RouteStep;
+    use crate::SearchResult;
+    use solana_sdk::pubkey::Pubkey;
+
+    struct TestResult {
+        arb_mint: Pubkey,
+        profit: i64,
+    }
+
+    impl SearchResult for TestResult {
+        fn profit(&self) -> i64 {
+            self.profit
+        }
+
+        fn amount_in(&self) -> (u64, Pubkey) {
+            (0, self.arb_mint)
+        }
+
+        fn convert_to_instruction_materials(&self) -> anyhow::Result<Vec<InstructionMaterial>> {
+            Ok(vec![])
+        }
+
+        fn route_steps(&self) -> Vec<RouteStep> {
+            vec![]
+        }
+
+        fn information(&self) -> String {
+            String::new()
+        }
+    }
+
+    #[test]
+    fn test_best_per_arb_mint() {
+        // WSOL 9位小数，min_profit 10_000，利润为min_profit的1.5倍
+        let wsol = Pubkey::new_unique();
+        // 6位小数的小币，min_profit 1，利润为min_profit的2倍，价值远小于WSOL的利润
+        let dust = Pubkey::new_unique();
+        let result = |arb_mint, profit| TestResult { arb_mint, profit };
+        let mut best = best_per_arb_mint(vec![
+            result(dust, 2),
+            result(wsol, 12_000),
+            result(wsol, 15_000),
+            result(dust, 1),
+        ]);
+        best.sort_by_key(|result| result.profit);
+        assert_eq!(
+            best.iter()
+                .map(|result| (result.arb_mint, result.profit))
+                .collect::<Vec<_>>(),
+            vec![(dust, 2), (wsol, 15_000)]
+        );
+        assert!(best_per_arb_mint(Vec::<TestResult>::new()).is_empty());
     }
 }
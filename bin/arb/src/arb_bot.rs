@@ -1,19 +1,27 @@
-use crate::arb::Arb;
+use crate::arb::{Arb, ArbMintConfig};
+use crate::data_source::{
+    DataSource, DataSourceKind, DataSourceType, ReconnectBackoff, Recorder, ReplaySource,
+    WebsocketSubscribe,
+};
 use crate::dex::init_snapshot;
-use crate::dex::{get_global_cache, init_global_cache, GlobalCache};
+use crate::dex::{get_global_cache, init_global_cache, store_snapshot, GlobalCache};
 use crate::dex::{init_account_relations, init_data_slice_config};
+use crate::dex::{init_quote_error_stats, quote_error_stats, rejected_write_count};
+use crate::dex::{init_stale_state_config, StaleStateConfig};
+use crate::dex::{init_tick_array_depth, TickArrayDepth};
 use crate::dex_data::DexJson;
 use crate::executor::JitoExecutor;
 use crate::executor::{Executor, JitoTipsType};
 use crate::graph::HopPathTypes;
-use crate::graph::HopPathTypes::TwoHop;
+use crate::graph::HopPathTypes::{MultiHop, ThreeHop, TwoHop};
 use crate::grpc_processor::MessageProcessor;
 use crate::grpc_subscribe::{GrpcMessage, GrpcSubscribe, GrpcTransactionMsg};
 use crate::keypair::KeypairVault;
 use crate::metadata::init_metadata;
-use crate::{init_graph, TwoHopPath};
+use crate::pool_manager::PoolManager;
+use crate::{init_graph, AmountSearchConfig, MultiHopPath, ThreeHopPath, TwoHopPath};
 use anyhow::anyhow;
-use clap::Parser;
+use clap::Args;
 use parking_lot::RwLock;
 use rpassword::read_password;
 use solana_rpc_client::nonblocking::rpc_client::RpcClient;
@@ -30,14 +38,15 @@ use tokio::task::JoinSet;
 use tokio::time::Instant;
 use tracing::{error, info};
 
-#[derive(Parser, Debug)]
+#[derive(Args, Debug)]
 pub struct Command {
     #[arg(long, required = true)]
     dex_json_path: String,
     #[arg(long, required = true)]
     keypair_path: String,
-    #[arg(long, default_value = "https://solana-yellowstone-grpc.publicnode.com")]
-    grpc_url: String,
+    /// 多个GRPC端点同时订阅，推送的数据去重后只保留最先到达的
+    #[arg(long, num_args = 1.., default_values = ["https://solana-yellowstone-grpc.publicnode.com"])]
+    grpc_url: Vec<String>,
     #[arg(long, default_value = "https://solana-rpc.publicnode.com")]
     rpc_url: String,
     #[arg(long,num_args = 1..)]
@@ -50,43 +59,137 @@ pub struct Command {
     follow_mints: Vec<Pubkey>,
     #[arg(long)]
     pub arb_bot_name: Option<String>,
-    #[arg(long, required = true)]
-    arb_amount_in: u64,
+    /// 与arb_mint一一对应，只有一个值时所有arb_mint共用
+    #[arg(long, required = true, num_args = 1..)]
+    arb_amount_in: Vec<u64>,
     #[arg(long, default_value = "1")]
     arb_size: usize,
-    #[arg(long, default_value = "So11111111111111111111111111111111111111112")]
-    arb_mint: Pubkey,
-    #[arg(long, default_value = "70")]
-    arb_mint_bps_numerator: u64,
+    /// 同时套利的多个Mint
+    #[arg(long, num_args = 1.., default_values = ["So11111111111111111111111111111111111111112"])]
+    arb_mint: Vec<Pubkey>,
+    /// 与arb_mint一一对应，只有一个值时所有arb_mint共用
+    #[arg(long, num_args = 1.., default_values = ["70"])]
+    arb_mint_bps_numerator: Vec<u64>,
     #[arg(long, default_value = "100")]
     arb_mint_bps_denominator: u64,
     #[arg(long, default_value = "1000")]
     arb_channel_capacity: usize,
-    #[arg(long, default_value = "100000")]
-    arb_min_profit: u64,
+    /// 与arb_mint一一对应，只有一个值时所有arb_mint共用
+    #[arg(long, num_args = 1.., default_values = ["100000"])]
+    arb_min_profit: Vec<u64>,
     #[arg(long, default_value = "1")]
     processor_size: usize,
+    /// 开启3 hop(三角)套利路径搜索
+    #[arg(long)]
+    three_hop: bool,
+    /// 开启N hop负权环搜索，值为环路的最大长度
+    #[arg(long)]
+    multi_hop_max_len: Option<usize>,
+    /// 最佳amount_in搜索的最大迭代次数，0 : 只使用固定的arb_amount_in
+    #[arg(long, default_value = "24")]
+    arb_size_search_iterations: usize,
+    /// 最佳amount_in搜索的下限
+    #[arg(long, default_value = "1000000")]
+    arb_size_search_min_amount_in: u64,
+    /// 最佳amount_in搜索的精度
+    #[arg(long, default_value = "1000000")]
+    arb_size_search_precision: u64,
+    /// Raydium CLMM quote最多遍历的tick array数量
+    #[arg(long, default_value = "5")]
+    clmm_tick_array_depth: u8,
+    /// Meteora DLMM quote最多遍历的bin array数量
+    #[arg(long, default_value = "5")]
+    dlmm_bin_array_depth: u8,
+    /// Orca Whirlpool quote最多遍历的tick array数量(3~6)
+    #[arg(long, default_value = "6")]
+    whirlpool_tick_array_depth: u8,
+    /// 定时重新加载dex_json_path，增删池子(秒)
+    #[arg(long)]
+    dex_json_reload_secs: Option<u64>,
+    /// 收到交易后立即将恒定乘积池子金库的post balance写入缓存，不等待Account推送
+    #[arg(long)]
+    apply_tx_balances: bool,
+    /// 定时输出各池子quote失败的次数和原因、缓存拒绝的乱序写入次数(秒)，不设置时不统计
+    #[arg(long)]
+    quote_error_report_secs: Option<u64>,
+    /// 触发交易涉及的池子、金库在缓存中落后于交易超过该slot数量时不触发路由，不设置时不检查
+    #[arg(long)]
+    max_slot_lag: Option<u64>,
+    /// 缓存落后时只记录，仍然触发路由
+    #[arg(long)]
+    flag_stale_state: bool,
+    /// GRPC断线后第一次重连、重新加载snapshot失败后第一次重试的等待时间(毫秒)，连续失败时翻倍
+    #[arg(long, default_value = "500")]
+    grpc_reconnect_min_delay_ms: u64,
+    /// GRPC断线重连、重新加载snapshot重试的最长等待时间(毫秒)
+    #[arg(long, default_value = "30000")]
+    grpc_reconnect_max_delay_ms: u64,
+    /// 多个GRPC端点时定时输出各端点按程序统计的到达延迟(秒)，不设置时不输出
+    #[arg(long)]
+    grpc_latency_report_secs: Option<u64>,
+    /// 行情数据源
+    #[arg(long, value_enum, default_value_t = DataSourceKind::Grpc)]
+    data_source: DataSourceKind,
+    /// data_source为websocket时订阅的Solana RPC websocket
+    #[arg(long, default_value = "wss://solana-rpc.publicnode.com")]
+    ws_url: String,
+    /// data_source为replay时回放的录制文件
+    #[arg(long)]
+    replay_path: Option<PathBuf>,
+    /// 按录制时的间隔回放，否则尽快回放
+    #[arg(long)]
+    replay_realtime: bool,
+    /// 回放时通过slot索引跳过该slot之前的数据
+    #[arg(long)]
+    replay_start_slot: Option<u64>,
+    /// 将数据源推送的消息录制到该目录，每小时一个文件，snapshot保存到该目录的snapshots子目录，不设置时不录制
+    #[arg(long)]
+    record_dir: Option<PathBuf>,
+    /// 录制文件使用gzip压缩
+    #[arg(long)]
+    record_compress: bool,
 }
 
-pub async fn start_with_custom() -> anyhow::Result<()> {
-    let command = Command::parse();
+pub async fn start_with_custom(command: Command) -> anyhow::Result<()> {
     info!("{:#?}", command);
-    let grpc_url = command.grpc_url.clone();
+    let grpc_urls = command.grpc_url.clone();
     let rpc_url = command.rpc_url.clone();
-    let arb_mint = command.arb_mint.clone();
+    let arb_mints = arb_mint_configs(&command)?;
     let follow_mints = command.follow_mints.clone();
     let dex_json_path = command.dex_json_path.clone();
     let keypair_path = command.keypair_path.clone();
     let processor_size = command.processor_size;
     let arb_size = command.arb_size;
-    let arb_amount_in = command.arb_amount_in;
-    let arb_mint_bps_numerator = command.arb_mint_bps_numerator;
-    let arb_mint_bps_denominator = command.arb_mint_bps_denominator;
-    let arb_min_profit = command.arb_min_profit;
     // Account本地缓存更新后广播通道容量
     let arb_channel_capacity = command.arb_channel_capacity;
     let rpc_client = Arc::new(RpcClient::new(rpc_url));
-    let hop_path_types = Arc::new(vec![RwLock::new(TwoHop(TwoHopPath))]);
+    let mut hop_path_types = vec![RwLock::new(TwoHop(TwoHopPath::default()))];
+    if command.three_hop {
+        hop_path_types.push(RwLock::new(ThreeHop(ThreeHopPath::default())));
+    }
+    if let Some(max_hops) = command.multi_hop_max_len {
+        hop_path_types.push(RwLock::new(MultiHop(MultiHopPath::new(max_hops))));
+    }
+    let hop_path_types = Arc::new(hop_path_types);
+    let amount_search = AmountSearchConfig {
+        min_amount_in: command.arb_size_search_min_amount_in,
+        max_iterations: command.arb_size_search_iterations,
+        precision: command.arb_size_search_precision,
+    };
+    init_tick_array_depth(TickArrayDepth {
+        raydium_clmm: command.clmm_tick_array_depth,
+        meteora_dlmm: command.dlmm_bin_array_depth,
+        orca_whirl: command.whirlpool_tick_array_depth,
+    })?;
+    if command.quote_error_report_secs.is_some() {
+        init_quote_error_stats()?;
+    }
+    if let Some(max_slot_lag) = command.max_slot_lag {
+        init_stale_state_config(StaleStateConfig {
+            max_slot_lag,
+            flag_only: command.flag_stale_state,
+        })?;
+    }
     // 0.初始化钱包，ata账户，blockhash
     // 1.初始化各个Account的切片规则
     // 2.初始化snapshot，返回有效的DexJson(所有数据都合法的)
@@ -95,12 +198,14 @@ pub async fn start_with_custom() -> anyhow::Result<()> {
     let dex_data = init_program(
         keypair_path,
         dex_json_path,
-        &arb_mint,
+        command.arb_mint.as_slice(),
         follow_mints.as_slice(),
         hop_path_types.clone(),
-        rpc_client,
+        rpc_client.clone(),
     )
     .await?;
+    // 重新加载的DexJson，用于更新GRPC订阅
+    let (dex_data_sender, dex_data_receiver) = flume::unbounded::<Vec<DexJson>>();
     // grpc消息消费通道
     let (grpc_message_sender, grpc_message_receiver) = flume::unbounded::<GrpcMessage>();
     // Account本地缓存更新后广播通道
@@ -121,20 +226,113 @@ pub async fn start_with_custom() -> anyhow::Result<()> {
     // 接收更新缓存的Account信息，判断是否需要触发route
     Arb::new(
         arb_size,
-        arb_amount_in,
-        arb_min_profit,
-        arb_mint,
-        arb_mint_bps_numerator,
-        arb_mint_bps_denominator,
+        arb_mints,
+        command.apply_tx_balances,
+        amount_search,
         JitoExecutor::initialize(&command).await?,
-        hop_path_types,
+        hop_path_types.clone(),
     )
     .start(&mut join_set, cached_message_receiver)
     .await;
+    // 定时重新加载DexJson，增删池子
+    if let Some(reload_secs) = command.dex_json_reload_secs {
+        PoolManager::new(
+            command.dex_json_path.clone(),
+            follow_mints,
+            hop_path_types,
+            rpc_client,
+            dex_data_sender,
+            dex_data.clone(),
+        )
+        .start(&mut join_set, Duration::from_secs(reload_secs))
+        .await;
+    }
+    // 定时输出quote失败次数最多的池子和缓存拒绝的乱序写入次数
+    if let Some(report_secs) = command.quote_error_report_secs {
+        join_set.spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(report_secs)).await;
+                info!("缓存拒绝乱序写入[{}]次", rejected_write_count());
+                for (pool_id, quote_error, count) in quote_error_stats().into_iter().take(20) {
+                    info!(
+                        "池子[{}]quote失败[{}]次，原因 : {}",
+                        pool_id, count, quote_error
+                    );
+                }
+            }
+        });
+    }
+    // 断线后重连，重新加载snapshot
+    let backoff = ReconnectBackoff {
+        min_delay: Duration::from_millis(command.grpc_reconnect_min_delay_ms),
+        max_delay: Duration::from_millis(command.grpc_reconnect_max_delay_ms),
+    };
+    let data_source: DataSourceType = match command.data_source {
+        DataSourceKind::Grpc => {
+            let grpc_subscribe = GrpcSubscribe::new(grpc_urls, command.rpc_url.clone(), backoff);
+            // 定时输出各端点的到达延迟
+            if let (Some(report_secs), Some(deduper)) =
+                (command.grpc_latency_report_secs, grpc_subscribe.deduper())
+            {
+                join_set.spawn(async move {
+                    loop {
+                        tokio::time::sleep(Duration::from_secs(report_secs)).await;
+                        for (endpoint, program, latency) in deduper.latency_stats() {
+                            info!(
+                                "GRPC[{}]程序[{}] : 到达[{}]次，最先到达[{}]次，平均落后[{:?}]",
+                                endpoint,
+                                program,
+                                latency.arrivals,
+                                latency.first_arrivals,
+                                latency.average_lag()
+                            );
+                        }
+                    }
+                });
+            }
+            grpc_subscribe.into()
+        }
+        DataSourceKind::Websocket => {
+            WebsocketSubscribe::new(command.ws_url.clone(), command.rpc_url.clone(), backoff).into()
+        }
+        DataSourceKind::Replay => ReplaySource::new(
+            command
+                .replay_path
+                .clone()
+                .ok_or(anyhow!("data_source为replay时需要replay_path"))?,
+            command.replay_realtime,
+            command.replay_start_slot,
+        )
+        .into(),
+    };
+    // 录制时数据源的消息先经过Recorder，再转发给Processor
+    let data_source_sender = match command.record_dir.clone() {
+        Some(record_dir) => {
+            // 回放时从该snapshot开始，与录制文件分开保存，避免回放目录时被当作录制文件
+            store_snapshot(
+                get_global_cache(),
+                dex_data.as_slice(),
+                &record_dir.join("snapshots"),
+            )?;
+            let recorder = Recorder::new(record_dir, command.record_compress)?;
+            let (record_sender, record_receiver) = flume::unbounded::<GrpcMessage>();
+            join_set.spawn(async move {
+                while let Ok(message) = record_receiver.recv_async().await {
+                    if let Err(e) = grpc_message_sender.send_async(message.clone()).await {
+                        error!("转发录制消息失败，原因：{}", e);
+                        break;
+                    }
+                    recorder.record(message);
+                }
+            });
+            record_sender
+        }
+        None => grpc_message_sender,
+    };
     join_set.spawn(async move {
-        // 订阅GRPC
-        GrpcSubscribe
-            .subscribe(grpc_url, dex_data, grpc_message_sender)
+        // 订阅行情数据
+        data_source
+            .subscribe(dex_data, dex_data_receiver, data_source_sender)
             .await;
     });
     while let Some(event) = join_set.join_next().await {
@@ -147,10 +345,52 @@ pub async fn start_with_custom() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 按arb_mint展开各自的套利参数
+fn arb_mint_configs(command: &Command) -> anyhow::Result<Vec<ArbMintConfig>> {
+    let arb_mint_count = command.arb_mint.len();
+    let per_arb_mint = |values: &[u64], name: &str| per_arb_mint(values, name, arb_mint_count);
+    let amount_ins = per_arb_mint(command.arb_amount_in.as_slice(), "arb_amount_in")?;
+    let min_profits = per_arb_mint(command.arb_min_profit.as_slice(), "arb_min_profit")?;
+    let bps_numerators = per_arb_mint(
+        command.arb_mint_bps_numerator.as_slice(),
+        "arb_mint_bps_numerator",
+    )?;
+    Ok(command
+        .arb_mint
+        .iter()
+        .enumerate()
+        .map(|(index, mint)| ArbMintConfig {
+            mint: Arc::new(*mint),
+            amount_in: amount_ins[index],
+            min_profit: min_profits[index],
+            bps_numerator: bps_numerators[index],
+            bps_denominator: command.arb_mint_bps_denominator,
+        })
+        .collect())
+}
+
+/// 只有一个值时所有arb_mint共用，否则与arb_mint一一对应
+pub(crate) fn per_arb_mint(
+    values: &[u64],
+    name: &str,
+    arb_mint_count: usize,
+) -> anyhow::Result<Vec<u64>> {
+    match values.len() {
+        1 => Ok(vec![values[0]; arb_mint_count]),
+        len if len == arb_mint_count => Ok(values.to_vec()),
+        len => Err(anyhow!(
+            "{}数量[{}]与arb_mint数量[{}]不一致",
+            name,
+            len,
+            arb_mint_count
+        )),
+    }
+}
+
 pub async fn init_program(
     keypair_path: String,
     dex_json_path: String,
-    arb_mint: &Pubkey,
+    arb_mints: &[Pubkey],
     follow_mints: &[Pubkey],
     hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
     rpc_client: Arc<RpcClient>,
@@ -167,7 +407,7 @@ pub async fn init_program(
     init_global_cache(global_cache);
     // 初始化钱包关联的ATA账户余额
     // 初始化blockhash
-    init_metadata(keypair, arb_mint, dex_data.as_slice(), rpc_client.clone()).await?;
+    init_metadata(keypair, arb_mints, dex_data.as_slice(), rpc_client.clone()).await?;
     // 初始化account之间的关系，用于解析GRPC推送数据
     init_account_relations(dex_data.as_slice())?;
     // 构建图
@@ -0,0 +1,493 @@
+use crate::arb::{Arb, ArbMintConfig};
+use crate::arb_bot::per_arb_mint;
+use crate::data_source::{DataSource, ReplaySource};
+use crate::dex::{
+    init_account_relations, init_data_slice_config, init_global_cache, init_stale_state_config,
+    init_tick_array_depth, load_stored_snapshot, StaleStateConfig, TickArrayDepth,
+};
+use crate::dex_data::{load_dex_json, DexJson};
+use crate::executor::PaperExecutor;
+use crate::graph::HopPathTypes::{MultiHop, ThreeHop, TwoHop};
+use crate::grpc_processor::MessageProcessor;
+use crate::grpc_subscribe::GrpcMessage;
+use crate::metadata::init_paper_metadata;
+use crate::{
+    build_hop_paths, init_graph, AmountSearchConfig, HopPathTypes, MultiHopPath, SearchResult,
+    ThreeHopPath, TwoHopPath,
+};
+use anyhow::anyhow;
+use clap::Args;
+use parking_lot::RwLock;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// 回放录制的消息，按多组参数模拟套利，对比找到的机会、利润和路由耗时
+///
+/// arb replay --dex-json-path .. --snapshot-path .. --replay-path .. --paper-balance ..
+#[derive(Args, Debug)]
+pub struct ReplayCommand {
+    #[arg(long, required = true)]
+    dex_json_path: String,
+    /// Recorder录制时保存的snapshot，位于record_dir/snapshots
+    #[arg(long, required = true)]
+    snapshot_path: PathBuf,
+    /// 录制文件，为目录时按时间顺序回放目录中所有的录制文件
+    #[arg(long, required = true)]
+    replay_path: PathBuf,
+    /// 按录制时的间隔回放，否则尽快回放
+    #[arg(long)]
+    replay_realtime: bool,
+    /// 不设置时从snapshot的slot开始回放
+    #[arg(long)]
+    replay_start_slot: Option<u64>,
+    #[arg(long, default_values = ["So11111111111111111111111111111111111111112"])]
+    follow_mints: Vec<Pubkey>,
+    #[arg(long, num_args = 1.., default_values = ["So11111111111111111111111111111111111111112"])]
+    arb_mint: Vec<Pubkey>,
+    /// 模拟钱包中arb mint的余额，与arb_mint一一对应，只有一个值时所有arb_mint共用
+    #[arg(long, required = true, num_args = 1..)]
+    paper_balance: Vec<u64>,
+    /// 与arb_mint一一对应，只有一个值时所有arb_mint共用
+    #[arg(long, num_args = 1.., default_values = ["70"])]
+    arb_mint_bps_numerator: Vec<u64>,
+    #[arg(long, default_value = "100")]
+    arb_mint_bps_denominator: u64,
+    /// 对比的amount_in，每个值所有arb_mint共用
+    #[arg(long, required = true, num_args = 1..)]
+    arb_amount_in: Vec<u64>,
+    /// 对比的min_profit，每个值所有arb_mint共用
+    #[arg(long, num_args = 1.., default_values = ["100000"])]
+    arb_min_profit: Vec<u64>,
+    /// 对比的路径搜索策略，2 hop始终开启 : two / three / multi:<最大长度>，组合时逗号分隔
+    #[arg(long, num_args = 1.., default_values = ["two"])]
+    hop_strategy: Vec<HopStrategy>,
+    /// 每笔bundle的tip(lamports)
+    #[arg(long, default_value = "1000000")]
+    paper_tip: u64,
+    /// 对比的最佳amount_in搜索最大迭代次数，0 : 只使用固定的arb_amount_in
+    #[arg(long, num_args = 1.., default_values = ["0"])]
+    arb_size_search_iterations: Vec<usize>,
+    /// 最佳amount_in搜索的下限
+    #[arg(long, default_value = "1000000")]
+    arb_size_search_min_amount_in: u64,
+    /// 最佳amount_in搜索的精度
+    #[arg(long, default_value = "1000000")]
+    arb_size_search_precision: u64,
+    /// Raydium CLMM quote最多遍历的tick array数量
+    #[arg(long, default_value = "5")]
+    clmm_tick_array_depth: u8,
+    /// Meteora DLMM quote最多遍历的bin array数量
+    #[arg(long, default_value = "5")]
+    dlmm_bin_array_depth: u8,
+    /// Orca Whirlpool quote最多遍历的tick array数量(3~6)
+    #[arg(long, default_value = "6")]
+    whirlpool_tick_array_depth: u8,
+    /// 收到交易后立即将恒定乘积池子金库的post balance写入缓存，不等待Account推送
+    #[arg(long)]
+    apply_tx_balances: bool,
+    /// 触发交易涉及的池子、金库在缓存中落后于交易超过该slot数量时不触发路由，不设置时不检查
+    #[arg(long)]
+    max_slot_lag: Option<u64>,
+    /// 缓存落后时只记录，仍然触发路由
+    #[arg(long)]
+    flag_stale_state: bool,
+    /// 报告保存为JSON，不设置时只输出日志
+    #[arg(long)]
+    report_path: Option<PathBuf>,
+}
+
+/// 路径搜索策略，2 hop始终开启
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HopStrategy {
+    three_hop: bool,
+    multi_hop_max_len: Option<usize>,
+}
+
+impl HopStrategy {
+    fn hop_paths(&self) -> Vec<RwLock<HopPathTypes>> {
+        let mut hop_paths = vec![RwLock::new(TwoHop(TwoHopPath::default()))];
+        if self.three_hop {
+            hop_paths.push(RwLock::new(ThreeHop(ThreeHopPath::default())));
+        }
+        if let Some(max_hops) = self.multi_hop_max_len {
+            hop_paths.push(RwLock::new(MultiHop(MultiHopPath::new(max_hops))));
+        }
+        hop_paths
+    }
+}
+
+impl FromStr for HopStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut strategy = Self::default();
+        for hop in s.split(',').map(str::trim) {
+            match hop.split_once(':') {
+                None if hop == "two" => {}
+                None if hop == "three" => strategy.three_hop = true,
+                Some(("multi", max_len)) => strategy.multi_hop_max_len = Some(max_len.parse()?),
+                _ => return Err(anyhow!("无法识别的路径搜索策略 : {}", hop)),
+            }
+        }
+        Ok(strategy)
+    }
+}
+
+impl Display for HopStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "two")?;
+        if self.three_hop {
+            write!(f, ",three")?;
+        }
+        if let Some(max_len) = self.multi_hop_max_len {
+            write!(f, ",multi:{}", max_len)?;
+        }
+        Ok(())
+    }
+}
+
+/// 一组回放参数的模拟结果
+#[derive(Debug, Serialize)]
+pub struct BacktestReport {
+    pub amount_in: u64,
+    pub min_profit: u64,
+    pub hop_strategy: String,
+    // 0 : 固定amount_in
+    pub size_search_iterations: usize,
+    // 改变了池子金库余额、触发路由的交易数量
+    pub triggers: usize,
+    pub opportunities: usize,
+    // lamports
+    pub tip_cost: u64,
+    pub avg_routing_micros: u64,
+    pub p99_routing_micros: u64,
+    pub max_routing_micros: u64,
+    // 利润的单位为各自的arb mint，不同arb mint的利润不能相加
+    pub arb_mints: Vec<ArbMintReport>,
+    pub trades: Vec<TradeReport>,
+}
+
+/// 单个arb mint的模拟结果
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ArbMintReport {
+    pub arb_mint: String,
+    pub opportunities: usize,
+    pub estimated_profit: i64,
+    // lamports
+    pub tip_cost: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TradeReport {
+    pub slot: u64,
+    pub tx: String,
+    pub arb_mint: String,
+    pub amount_in: u64,
+    pub profit: i64,
+    // lamports
+    pub tip: u64,
+    pub path: String,
+}
+
+/// 按arb mint汇总的模拟结果，按arb mint排序
+fn arb_mint_reports(trades: &[TradeReport]) -> Vec<ArbMintReport> {
+    let mut reports = BTreeMap::<&str, ArbMintReport>::new();
+    for trade in trades {
+        let report = reports
+            .entry(trade.arb_mint.as_str())
+            .or_insert_with(|| ArbMintReport {
+                arb_mint: trade.arb_mint.clone(),
+                ..Default::default()
+            });
+        report.opportunities += 1;
+        report.estimated_profit += trade.profit;
+        report.tip_cost += trade.tip;
+    }
+    reports.into_values().collect()
+}
+
+/// 一组回放参数，各自的图和PaperExecutor
+struct BacktestRun {
+    amount_in: u64,
+    min_profit: u64,
+    hop_strategy: HopStrategy,
+    amount_search: AmountSearchConfig,
+    arb: Arb,
+    executor: Arc<PaperExecutor>,
+    // 每次触发路由的耗时
+    routing_costs: Vec<Duration>,
+}
+
+impl BacktestRun {
+    fn report(&self) -> BacktestReport {
+        let trades = self.executor.trades();
+        let mut routing_costs = self
+            .routing_costs
+            .iter()
+            .map(|cost| cost.as_micros() as u64)
+            .collect::<Vec<_>>();
+        routing_costs.sort_unstable();
+        let percentile = |p: usize| match routing_costs.len() {
+            0 => 0,
+            len => routing_costs[((len - 1) * p / 100).min(len - 1)],
+        };
+        let trades = trades
+            .iter()
+            .map(|trade| {
+                let (amount_in, arb_mint) = trade.result.amount_in();
+                TradeReport {
+                    slot: trade.slot,
+                    tx: trade.tx.clone(),
+                    arb_mint: arb_mint.to_string(),
+                    amount_in,
+                    profit: trade.result.profit(),
+                    tip: trade.tip,
+                    path: trade.result.information(),
+                }
+            })
+            .collect::<Vec<_>>();
+        BacktestReport {
+            amount_in: self.amount_in,
+            min_profit: self.min_profit,
+            hop_strategy: self.hop_strategy.to_string(),
+            size_search_iterations: self.amount_search.max_iterations,
+            triggers: routing_costs.len(),
+            opportunities: trades.len(),
+            tip_cost: trades.iter().map(|trade| trade.tip).sum(),
+            avg_routing_micros: routing_costs
+                .iter()
+                .sum::<u64>()
+                .checked_div(routing_costs.len() as u64)
+                .unwrap_or_default(),
+            p99_routing_micros: percentile(99),
+            max_routing_micros: routing_costs.last().copied().unwrap_or_default(),
+            arb_mints: arb_mint_reports(&trades),
+            trades,
+        }
+    }
+}
+
+pub async fn start_replay(command: ReplayCommand) -> anyhow::Result<()> {
+    info!("{:#?}", command);
+    init_tick_array_depth(TickArrayDepth {
+        raydium_clmm: command.clmm_tick_array_depth,
+        meteora_dlmm: command.dlmm_bin_array_depth,
+        orca_whirl: command.whirlpool_tick_array_depth,
+    })?;
+    if let Some(max_slot_lag) = command.max_slot_lag {
+        init_stale_state_config(StaleStateConfig {
+            max_slot_lag,
+            flag_only: command.flag_stale_state,
+        })?;
+    }
+    // 与实盘相同的初始化，snapshot从文件加载
+    init_data_slice_config()?;
+    let mut dex_data = load_dex_json(command.dex_json_path.clone(), &command.follow_mints)?;
+    let snapshot = load_stored_snapshot(&command.snapshot_path)?;
+    dex_data.retain(|json| snapshot.pools.contains(&json.pool));
+    if dex_data.is_empty() {
+        return Err(anyhow!("snapshot中没有dex_json_path中的池子"));
+    }
+    let start_slot = command.replay_start_slot.unwrap_or(snapshot.slot);
+    init_global_cache(snapshot.cache);
+    let arb_mint_count = command.arb_mint.len();
+    let paper_balances = per_arb_mint(&command.paper_balance, "paper_balance", arb_mint_count)?;
+    init_paper_metadata(
+        command
+            .arb_mint
+            .iter()
+            .cloned()
+            .zip(paper_balances)
+            .collect::<Vec<_>>()
+            .as_slice(),
+    )?;
+    init_account_relations(dex_data.as_slice())?;
+    init_graph(dex_data.as_slice(), &command.follow_mints, Arc::new(vec![]))?;
+
+    let mut runs = backtest_runs(&command, dex_data.as_slice())?;
+    info!("回放配置数量 : {}, 起始slot : {}", runs.len(), start_slot);
+    let (message_sender, message_receiver) = flume::bounded::<GrpcMessage>(1024);
+    let (_dex_data_sender, dex_data_receiver) = flume::unbounded::<Vec<DexJson>>();
+    let replay_source = ReplaySource::new(
+        command.replay_path.clone(),
+        command.replay_realtime,
+        Some(start_slot),
+    );
+    let replay = tokio::spawn(async move {
+        replay_source
+            .subscribe(dex_data, dex_data_receiver, message_sender)
+            .await;
+    });
+    // 按录制顺序逐条处理，交易在所有配置上触发路由后再处理下一条
+    let instant = Instant::now();
+    let mut message_count = 0;
+    while let Ok(message) = message_receiver.recv_async().await {
+        message_count += 1;
+        if let Some(transaction_msg) = MessageProcessor::process(message) {
+            for run in runs.iter_mut() {
+                if let Some(routing_cost) = run
+                    .arb
+                    .process_transaction(0, transaction_msg.clone())
+                    .await
+                {
+                    run.routing_costs.push(routing_cost);
+                }
+            }
+        }
+    }
+    replay.await?;
+    info!(
+        "回放结束，消息数量 : {}, 耗时 : {:?}",
+        message_count,
+        instant.elapsed()
+    );
+
+    let reports = runs.iter().map(BacktestRun::report).collect::<Vec<_>>();
+    for report in reports.iter() {
+        info!(
+            "amount_in : {}, min_profit : {}, hop : {}, size_search : {} ==> 触发 : {}, 机会 : {}, tip : {}, 路由 avg/p99/max : {}/{}/{}μs",
+            report.amount_in,
+            report.min_profit,
+            report.hop_strategy,
+            report.size_search_iterations,
+            report.triggers,
+            report.opportunities,
+            report.tip_cost,
+            report.avg_routing_micros,
+            report.p99_routing_micros,
+            report.max_routing_micros
+        );
+        for arb_mint in report.arb_mints.iter() {
+            info!(
+                "    arb_mint : {} ==> 机会 : {}, 利润 : {}, tip : {}",
+                arb_mint.arb_mint,
+                arb_mint.opportunities,
+                arb_mint.estimated_profit,
+                arb_mint.tip_cost
+            );
+        }
+    }
+    if let Some(report_path) = command.report_path {
+        serde_json::to_writer_pretty(File::create(&report_path)?, &reports)?;
+        info!("回放报告 : {}", report_path.display());
+    }
+    Ok(())
+}
+
+/// amount_in、min_profit、hop_strategy、amount_in搜索迭代次数的所有组合
+fn backtest_runs(
+    command: &ReplayCommand,
+    dex_data: &[DexJson],
+) -> anyhow::Result<Vec<BacktestRun>> {
+    let arb_mint_count = command.arb_mint.len();
+    let bps_numerators = per_arb_mint(
+        &command.arb_mint_bps_numerator,
+        "arb_mint_bps_numerator",
+        arb_mint_count,
+    )?;
+    let mut runs = vec![];
+    for hop_strategy in command.hop_strategy.iter() {
+        for amount_in in command.arb_amount_in.iter() {
+            for min_profit in command.arb_min_profit.iter() {
+                for max_iterations in command.arb_size_search_iterations.iter() {
+                    let hop_paths = Arc::new(hop_strategy.hop_paths());
+                    build_hop_paths(dex_data, &command.follow_mints, hop_paths.as_slice());
+                    let arb_mints = command
+                        .arb_mint
+                        .iter()
+                        .zip(bps_numerators.iter())
+                        .map(|(mint, bps_numerator)| ArbMintConfig {
+                            mint: Arc::new(*mint),
+                            amount_in: *amount_in,
+                            min_profit: *min_profit,
+                            bps_numerator: *bps_numerator,
+                            bps_denominator: command.arb_mint_bps_denominator,
+                        })
+                        .collect();
+                    let amount_search = AmountSearchConfig {
+                        min_amount_in: command.arb_size_search_min_amount_in,
+                        max_iterations: *max_iterations,
+                        precision: command.arb_size_search_precision,
+                    };
+                    let executor = Arc::new(PaperExecutor::new(command.paper_tip));
+                    runs.push(BacktestRun {
+                        amount_in: *amount_in,
+                        min_profit: *min_profit,
+                        hop_strategy: hop_strategy.clone(),
+                        amount_search,
+                        arb: Arb::new(
+                            1,
+                            arb_mints,
+                            command.apply_tx_balances,
+                            amount_search,
+                            executor.clone(),
+                            hop_paths,
+                        ),
+                        executor,
+                        routing_costs: vec![],
+                    });
+                }
+            }
+        }
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::backtest::{arb_mint_reports, ArbMintReport, HopStrategy, TradeReport};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_hop_strategy_from_str() {
+        assert_eq!(
+            HopStrategy::from_str("two").unwrap(),
+            HopStrategy::default()
+        );
+        let strategy = HopStrategy::from_str("three,multi:4").unwrap();
+        assert!(strategy.three_hop);
+        assert_eq!(strategy.multi_hop_max_len, Some(4));
+        assert_eq!(strategy.to_string(), "two,three,multi:4");
+        assert!(HopStrategy::from_str("four").is_err());
+        assert!(HopStrategy::from_str("multi:x").is_err());
+    }
+
+    #[test]
+    fn test_arb_mint_reports() {
+        let trade = |arb_mint: &str, profit, tip| TradeReport {
+            slot: 1,
+            tx: String::new(),
+            arb_mint: arb_mint.to_string(),
+            amount_in: 100,
+            profit,
+            tip,
+            path: String::new(),
+        };
+        let trades = vec![trade("b", 10, 1), trade("a", 1000, 2), trade("b", -3, 3)];
+        assert_eq!(
+            arb_mint_reports(&trades),
+            vec![
+                ArbMintReport {
+                    arb_mint: "a".to_string(),
+                    opportunities: 1,
+                    estimated_profit: 1000,
+                    tip_cost: 2,
+                },
+                ArbMintReport {
+                    arb_mint: "b".to_string(),
+                    opportunities: 2,
+                    estimated_profit: 7,
+                    tip_cost: 4,
+                },
+            ]
+        );
+    }
+}
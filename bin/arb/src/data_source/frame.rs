@@ -0,0 +1,171 @@
+use crate::grpc_subscribe::{GrpcAccountMsg, GrpcMessage, GrpcTransactionMsg};
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+use std::io::{ErrorKind, Read, Write};
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{
+    SubscribeUpdate, SubscribeUpdateAccount, SubscribeUpdateAccountInfo,
+    SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo,
+};
+use yellowstone_grpc_proto::prost::Message;
+
+/// 帧头长度 : u32(LE) update长度 + i64(LE) 接收时间(微秒)
+pub const FRAME_HEADER_LEN: usize = 12;
+
+/// 录制文件中的一帧，update为prost编码的SubscribeUpdate
+///
+/// 交易的created_at为GRPC服务端的时间，与接收时间分开保存
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    pub received_at: DateTime<Local>,
+    pub update: SubscribeUpdate,
+}
+
+impl RecordedFrame {
+    /// Account : 推送的slot，Transaction : 交易所在的slot
+    pub fn slot(&self) -> Option<u64> {
+        match self.update.update_oneof.as_ref()? {
+            UpdateOneof::Account(account) => Some(account.slot),
+            UpdateOneof::Transaction(transaction) => Some(transaction.slot),
+            _ => None,
+        }
+    }
+
+    /// 返回写入的字节数
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<usize> {
+        let data = self.update.encode_to_vec();
+        writer.write_all(&(data.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.received_at.timestamp_micros().to_le_bytes())?;
+        writer.write_all(data.as_slice())?;
+        Ok(FRAME_HEADER_LEN + data.len())
+    }
+
+    /// 读取下一帧，文件结束时返回None
+    pub fn read(reader: &mut impl Read) -> anyhow::Result<Option<Self>> {
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        match reader.read_exact(&mut header) {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(anyhow!(e)),
+        }
+        let len = u32::from_le_bytes(header[..4].try_into()?) as usize;
+        let received_at =
+            DateTime::from_timestamp_micros(i64::from_le_bytes(header[4..].try_into()?))
+                .ok_or(anyhow!("录制帧的接收时间无效"))?
+                .with_timezone(&Local);
+        let mut data = vec![0u8; len];
+        reader.read_exact(&mut data)?;
+        Ok(Some(Self {
+            received_at,
+            update: SubscribeUpdate::decode(data.as_slice())?,
+        }))
+    }
+
+    /// 转换为GrpcMessage，接收时间为录制时的时间
+    pub fn into_grpc_message(self) -> Option<GrpcMessage> {
+        let created_at = self.update.created_at;
+        match self.update.update_oneof? {
+            UpdateOneof::Account(account) => {
+                // 回放只有一个来源
+                let mut account_msg = GrpcAccountMsg::from((account.account?, account.slot, 0));
+                account_msg.received_timestamp = self.received_at;
+                Some(GrpcMessage::Account(account_msg))
+            }
+            UpdateOneof::Transaction(transaction) => {
+                let mut transaction_msg = GrpcTransactionMsg::from((
+                    transaction.transaction?,
+                    transaction.slot,
+                    created_at.unwrap_or_default(),
+                ));
+                transaction_msg.received_timestamp = self.received_at;
+                Some(GrpcMessage::Transaction(transaction_msg))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<GrpcMessage> for RecordedFrame {
+    fn from(message: GrpcMessage) -> Self {
+        match message {
+            GrpcMessage::Account(account) => Self {
+                received_at: account.received_timestamp,
+                update: SubscribeUpdate {
+                    filters: vec![],
+                    created_at: None,
+                    update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                        account: Some(SubscribeUpdateAccountInfo {
+                            pubkey: account.account_key,
+                            owner: account.owner_key,
+                            data: account.data,
+                            write_version: account.write_version,
+                            txn_signature: Some(account.tx),
+                            ..Default::default()
+                        }),
+                        slot: account.slot,
+                        is_startup: false,
+                    })),
+                },
+            },
+            GrpcMessage::Transaction(transaction) => Self {
+                received_at: transaction.received_timestamp,
+                update: SubscribeUpdate {
+                    filters: vec![],
+                    created_at: Some(transaction.created_at),
+                    update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                        transaction: Some(SubscribeUpdateTransactionInfo {
+                            signature: transaction.signature,
+                            transaction: transaction.transaction,
+                            meta: transaction.meta,
+                            index: transaction.index,
+                            ..Default::default()
+                        }),
+                        slot: transaction.slot,
+                    })),
+                },
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_source::RecordedFrame;
+    use crate::grpc_subscribe::{GrpcAccountMsg, GrpcMessage};
+    use chrono::{DateTime, Local};
+
+    #[test]
+    fn test_recorded_frame_round_trip() {
+        let received_at = DateTime::from_timestamp_micros(1_700_000_000_123_456)
+            .unwrap()
+            .with_timezone(&Local);
+        let frame = RecordedFrame::from(GrpcMessage::Account(GrpcAccountMsg {
+            tx: vec![0; 64],
+            account_key: vec![1; 32],
+            owner_key: vec![2; 32],
+            data: vec![1, 2, 3],
+            write_version: 7,
+            slot: 100,
+            endpoint: 0,
+            received_timestamp: received_at,
+        }));
+        let mut buffer = vec![];
+        assert_eq!(frame.write(&mut buffer).unwrap(), buffer.len());
+        frame.write(&mut buffer).unwrap();
+        let mut reader = buffer.as_slice();
+        assert_eq!(
+            RecordedFrame::read(&mut reader).unwrap(),
+            Some(frame.clone())
+        );
+        let frame = RecordedFrame::read(&mut reader).unwrap().unwrap();
+        assert_eq!(frame.slot(), Some(100));
+        assert!(RecordedFrame::read(&mut reader).unwrap().is_none());
+        match frame.into_grpc_message() {
+            Some(GrpcMessage::Account(account)) => {
+                assert_eq!(account.write_version, 7);
+                assert_eq!(account.received_timestamp, received_at);
+            }
+            _ => panic!("应为Account"),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::dex::{get_global_cache, resync_snapshot, set_cache_synced, GlobalCache};
+use crate::dex_data::DexJson;
+use crate::grpc_subscribe::{GrpcMessage, GrpcSubscribe};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use enum_dispatch::enum_dispatch;
+use flume::Sender;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+mod frame;
+mod recorder;
+mod replay;
+mod websocket;
+
+pub use frame::*;
+pub use recorder::*;
+pub use replay::*;
+pub use websocket::*;
+
+/// 行情数据源，将推送的Account和交易转换为GrpcMessage发送给MessageProcessor
+#[async_trait]
+#[enum_dispatch(DataSourceType)]
+pub trait DataSource {
+    /// dex_data_receiver : 运行时池子发生变化后，接收全量的DexJson，不支持更新订阅的数据源忽略
+    async fn subscribe(
+        &self,
+        dex_data: Vec<DexJson>,
+        dex_data_receiver: flume::Receiver<Vec<DexJson>>,
+        message_sender: Sender<GrpcMessage>,
+    );
+}
+
+#[enum_dispatch]
+pub enum DataSourceType {
+    Grpc(GrpcSubscribe),
+    Websocket(WebsocketSubscribe),
+    Replay(ReplaySource),
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DataSourceKind {
+    // Yellowstone GRPC
+    Grpc,
+    // Solana RPC websocket
+    Websocket,
+    // 录制的文件
+    Replay,
+}
+
+/// 断线重连的等待时间，连续失败时翻倍
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectBackoff {
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl ReconnectBackoff {
+    pub fn next_delay(&self, delay: Duration) -> Duration {
+        (delay * 2).min(self.max_delay)
+    }
+}
+
+/// 数据源所有连接的状态，全部断开时推送丢失，重连后重新加载snapshot，加载完成前暂停路由
+pub(crate) struct ConnectionState {
+    connected: AtomicUsize,
+    // 全部断开的次数，重新加载snapshot期间再次全部断开时不恢复路由
+    disconnections: AtomicU64,
+    need_resync: AtomicBool,
+    // commitment为processed
+    resync_rpc_client: Arc<RpcClient>,
+    // 重新加载snapshot失败后重试的等待时间
+    resync_backoff: ReconnectBackoff,
+}
+
+impl ConnectionState {
+    pub fn new(rpc_url: String, resync_backoff: ReconnectBackoff) -> Arc<Self> {
+        Arc::new(Self {
+            connected: AtomicUsize::new(0),
+            disconnections: AtomicU64::new(0),
+            need_resync: AtomicBool::new(false),
+            resync_rpc_client: Arc::new(RpcClient::new_with_commitment(
+                rpc_url,
+                CommitmentConfig::processed(),
+            )),
+            resync_backoff,
+        })
+    }
+
+    /// dex_data : 需要重新加载snapshot时获取当前的DexJson
+    pub fn on_connected(self: &Arc<Self>, dex_data: impl FnOnce() -> Vec<DexJson>) {
+        self.connected.fetch_add(1, Ordering::SeqCst);
+        if self.need_resync.swap(false, Ordering::SeqCst) {
+            self.spawn_resync(dex_data());
+        }
+    }
+
+    pub fn on_disconnected(&self) {
+        // 所有连接都已断开
+        if self.connected.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.disconnections.fetch_add(1, Ordering::SeqCst);
+            self.need_resync.store(true, Ordering::SeqCst);
+            set_cache_synced(false);
+        }
+    }
+
+    /// 重新加载snapshot，失败时重试，期间没有再次全部断开时恢复路由
+    fn spawn_resync(self: &Arc<Self>, dex_data: Vec<DexJson>) {
+        let state = self.clone();
+        let disconnections = state.disconnections.load(Ordering::SeqCst);
+        tokio::spawn(async move {
+            let instant = Instant::now();
+            let count = resync_with_retry(
+                dex_data.as_slice(),
+                state.resync_rpc_client.clone(),
+                get_global_cache(),
+                state.resync_backoff,
+            )
+            .await;
+            if state.disconnections.load(Ordering::SeqCst) == disconnections {
+                set_cache_synced(true);
+            }
+            info!(
+                "重连后重新加载snapshot完成，更新账户数量 : {}, 耗时 : {:?}",
+                count,
+                instant.elapsed()
+            );
+        });
+    }
+}
+
+/// 重新加载snapshot直到成功，RPC请求失败时等待后重试，连续失败时等待时间翻倍
+async fn resync_with_retry(
+    dex_data: &[DexJson],
+    rpc_client: Arc<RpcClient>,
+    cache: &GlobalCache,
+    backoff: ReconnectBackoff,
+) -> usize {
+    let mut delay = backoff.min_delay;
+    loop {
+        match resync_snapshot(dex_data, rpc_client.clone(), cache).await {
+            Ok(count) => return count,
+            Err(e) => {
+                error!("重连后重新加载snapshot失败，{:?}后重试，原因：{}", delay, e);
+                tokio::time::sleep(delay).await;
+                delay = backoff.next_delay(delay);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_source::{resync_with_retry, ReconnectBackoff};
+    use crate::dex::{DexType, GlobalCache};
+    use crate::dex_data::DexJson;
+    use async_trait::async_trait;
+    use solana_rpc_client::mock_sender::MockSender;
+    use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+    use solana_rpc_client::rpc_client::RpcClientConfig;
+    use solana_rpc_client::rpc_sender::{RpcSender, RpcTransportStats};
+    use solana_rpc_client_api::client_error::{ErrorKind, Result};
+    use solana_rpc_client_api::request::RpcRequest;
+    use solana_sdk::pubkey::Pubkey;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// 第一次请求失败，之后正常返回
+    struct FailOnceSender {
+        requests: Arc<AtomicUsize>,
+        sender: MockSender,
+    }
+
+    #[async_trait]
+    impl RpcSender for FailOnceSender {
+        async fn send(
+            &self,
+            request: RpcRequest,
+            params: serde_json::Value,
+        ) -> Result<serde_json::Value> {
+            if self.requests.fetch_add(1, Ordering::SeqCst) == 0 {
+                return Err(ErrorKind::Custom("connection reset".to_string()).into());
+            }
+            self.sender.send(request, params).await
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            self.sender.get_transport_stats()
+        }
+
+        fn url(&self) -> String {
+            self.sender.url()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resync_retry_after_rpc_error() -> anyhow::Result<()> {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let rpc_client = Arc::new(RpcClient::new_sender(
+            FailOnceSender {
+                requests: requests.clone(),
+                sender: MockSender::new("succeeds"),
+            },
+            RpcClientConfig::default(),
+        ));
+        let dex_data = vec![DexJson {
+            pool: Pubkey::new_unique(),
+            owner: *DexType::MeteoraDAMMV2.get_ref_program_id(),
+            mint_a: Pubkey::new_unique(),
+            mint_b: Pubkey::new_unique(),
+            vault_a: Pubkey::new_unique(),
+            vault_b: Pubkey::new_unique(),
+            address_lookup_table_address: None,
+        }];
+        let backoff = ReconnectBackoff {
+            min_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        };
+        let count = resync_with_retry(
+            dex_data.as_slice(),
+            rpc_client,
+            &GlobalCache::init(),
+            backoff,
+        )
+        .await;
+        // 模拟的RPC返回空账户，没有写入
+        assert_eq!(count, 0);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+}
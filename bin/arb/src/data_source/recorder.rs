@@ -0,0 +1,307 @@
+use crate::data_source::RecordedFrame;
+use crate::grpc_subscribe::GrpcMessage;
+use anyhow::anyhow;
+use chrono::{DateTime, Local};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// 录制文件名的前缀
+pub const SEGMENT_PREFIX: &str = "grpc-";
+/// 录制文件的扩展名
+pub const SEGMENT_EXTENSION: &str = "bin";
+/// 压缩的录制文件的扩展名
+pub const COMPRESSED_SEGMENT_EXTENSION: &str = "bin.gz";
+/// slot索引文件的扩展名
+pub const INDEX_EXTENSION: &str = "idx";
+/// 写入缓冲区的最长间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 将推送的消息追加写入录制文件，写入在单独的线程中进行，不阻塞消息处理
+///
+/// 按接收时间每小时一个文件 : grpc-YYYYMMDD-HH.bin(.gz)，
+/// 每个文件对应一个slot索引 : grpc-YYYYMMDD-HH.idx，记录slot第一次出现的帧在(解压后)文件中的偏移
+pub struct Recorder {
+    sender: flume::Sender<GrpcMessage>,
+}
+
+impl Recorder {
+    pub fn new(dir: PathBuf, compress: bool) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir)?;
+        let (sender, receiver) = flume::unbounded::<GrpcMessage>();
+        std::thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || {
+                let mut writer = SegmentWriter {
+                    dir,
+                    compress,
+                    segment: None,
+                };
+                if let Err(e) = writer.run(receiver) {
+                    error!("录制消息失败，原因：{}", e);
+                }
+            })?;
+        Ok(Self { sender })
+    }
+
+    pub fn record(&self, message: GrpcMessage) {
+        if let Err(e) = self.sender.send(message) {
+            error!("录制消息失败，原因：{}", e);
+        }
+    }
+}
+
+struct SegmentWriter {
+    dir: PathBuf,
+    compress: bool,
+    segment: Option<Segment>,
+}
+
+/// 当前小时的录制文件
+struct Segment {
+    // 文件名，不含扩展名
+    name: String,
+    writer: Box<dyn Write + Send>,
+    index: BufWriter<File>,
+    // 已写入的(解压后)字节数
+    offset: u64,
+    // 已写入索引的最大slot
+    indexed_slot: u64,
+}
+
+impl SegmentWriter {
+    fn run(&mut self, receiver: flume::Receiver<GrpcMessage>) -> anyhow::Result<()> {
+        let mut flushed = Instant::now();
+        loop {
+            match receiver.recv_timeout(FLUSH_INTERVAL) {
+                Ok(message) => self.write(RecordedFrame::from(message))?,
+                Err(flume::RecvTimeoutError::Timeout) => {}
+                Err(flume::RecvTimeoutError::Disconnected) => break,
+            }
+            if flushed.elapsed() >= FLUSH_INTERVAL {
+                self.flush()?;
+                flushed = Instant::now();
+            }
+        }
+        self.flush()
+    }
+
+    fn write(&mut self, frame: RecordedFrame) -> anyhow::Result<()> {
+        let name = segment_name(&frame.received_at);
+        if self
+            .segment
+            .as_ref()
+            .map_or(true, |segment| segment.name != name)
+        {
+            self.flush()?;
+            self.segment = Some(self.open(name)?);
+        }
+        let segment = self.segment.as_mut().unwrap();
+        if let Some(slot) = frame.slot() {
+            if slot > segment.indexed_slot {
+                segment.index.write_all(&slot.to_le_bytes())?;
+                segment.index.write_all(&segment.offset.to_le_bytes())?;
+                segment.indexed_slot = slot;
+            }
+        }
+        segment.offset += frame.write(&mut segment.writer)? as u64;
+        Ok(())
+    }
+
+    /// 同一个小时的文件已存在时(重启)追加写入
+    fn open(&self, name: String) -> anyhow::Result<Segment> {
+        let extension = if self.compress {
+            COMPRESSED_SEGMENT_EXTENSION
+        } else {
+            SEGMENT_EXTENSION
+        };
+        let path = self.dir.join(format!("{}.{}", name, extension));
+        let file = File::options().create(true).append(true).open(&path)?;
+        // 压缩文件追加时为新的gzip member
+        let offset = match file.metadata()?.len() {
+            0 => 0,
+            _ if self.compress => uncompressed_len(&path)?,
+            len => len,
+        };
+        let indexed_slot = read_slot_index(&path)?.last().map_or(0, |(slot, _)| *slot);
+        let writer: Box<dyn Write + Send> = if self.compress {
+            Box::new(GzEncoder::new(BufWriter::new(file), Compression::fast()))
+        } else {
+            Box::new(BufWriter::new(file))
+        };
+        let index = BufWriter::new(
+            File::options()
+                .create(true)
+                .append(true)
+                .open(index_path(&path))?,
+        );
+        info!("录制文件 : {}", path.display());
+        Ok(Segment {
+            name,
+            writer,
+            index,
+            offset,
+            indexed_slot,
+        })
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        if let Some(segment) = self.segment.as_mut() {
+            segment.writer.flush()?;
+            segment.index.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn segment_name(received_at: &DateTime<Local>) -> String {
+    format!("{}{}", SEGMENT_PREFIX, received_at.format("%Y%m%d-%H"))
+}
+
+fn is_compressed(path: &Path) -> bool {
+    path.to_string_lossy()
+        .ends_with(COMPRESSED_SEGMENT_EXTENSION)
+}
+
+/// 录制文件对应的索引文件
+pub fn index_path(path: &Path) -> PathBuf {
+    let name = path.to_string_lossy();
+    let name = name
+        .strip_suffix(COMPRESSED_SEGMENT_EXTENSION)
+        .or_else(|| name.strip_suffix(SEGMENT_EXTENSION))
+        .unwrap_or(&name);
+    PathBuf::from(format!("{}{}", name, INDEX_EXTENSION))
+}
+
+fn uncompressed_len(path: &Path) -> anyhow::Result<u64> {
+    Ok(std::io::copy(
+        &mut MultiGzDecoder::new(BufReader::new(File::open(path)?)),
+        &mut std::io::sink(),
+    )?)
+}
+
+/// 按slot升序的(slot, 偏移)，索引文件不存在时为空
+pub fn read_slot_index(path: &Path) -> anyhow::Result<Vec<(u64, u64)>> {
+    let mut data = vec![];
+    match File::open(index_path(path)) {
+        Ok(mut file) => file.read_to_end(&mut data)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(anyhow!(e)),
+    };
+    Ok(data
+        .chunks_exact(16)
+        .map(|entry| {
+            (
+                u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                u64::from_le_bytes(entry[8..].try_into().unwrap()),
+            )
+        })
+        .collect())
+}
+
+/// 目录中按时间排序的录制文件，只包含Recorder写入的文件，path为文件时只回放该文件
+pub fn list_segments(path: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut segments = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(SEGMENT_PREFIX)
+                && (name.ends_with(SEGMENT_EXTENSION)
+                    || name.ends_with(COMPRESSED_SEGMENT_EXTENSION))
+        })
+        .collect::<Vec<_>>();
+    segments.sort();
+    Ok(segments)
+}
+
+/// 打开录制文件，从start_slot第一次出现的帧开始读取
+///
+/// 文件中所有的slot都小于start_slot时返回None
+pub fn open_segment(path: &Path, start_slot: Option<u64>) -> anyhow::Result<Option<Box<dyn Read>>> {
+    let file = BufReader::new(File::open(path)?);
+    let mut reader: Box<dyn Read> = if is_compressed(path) {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(file)
+    };
+    if let Some(start_slot) = start_slot {
+        let index = read_slot_index(path)?;
+        let position = index.partition_point(|(slot, _)| *slot < start_slot);
+        match index.get(position) {
+            Some((_, offset)) => {
+                // 压缩文件不能seek，跳过之前的数据
+                std::io::copy(&mut (&mut reader).take(*offset), &mut std::io::sink())?;
+            }
+            None if index.is_empty() => {}
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(reader))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data_source::recorder::SegmentWriter;
+    use crate::data_source::{list_segments, open_segment, read_slot_index, RecordedFrame};
+    use crate::grpc_subscribe::{GrpcAccountMsg, GrpcMessage};
+    use chrono::{Local, TimeZone};
+
+    fn account_frame(slot: u64, hour: u32) -> RecordedFrame {
+        RecordedFrame::from(GrpcMessage::Account(GrpcAccountMsg {
+            tx: vec![0; 64],
+            account_key: vec![1; 32],
+            owner_key: vec![2; 32],
+            data: vec![0; 100],
+            write_version: slot,
+            slot,
+            endpoint: 0,
+            received_timestamp: Local.with_ymd_and_hms(2025, 1, 1, hour, 0, 0).unwrap(),
+        }))
+    }
+
+    #[test]
+    fn test_recorder_segment_index() {
+        for compress in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut writer = SegmentWriter {
+                dir: dir.path().to_path_buf(),
+                compress,
+                segment: None,
+            };
+            for (slot, hour) in [(10, 1), (12, 1), (11, 1), (13, 2), (14, 2)] {
+                writer.write(account_frame(slot, hour)).unwrap();
+            }
+            writer.flush().unwrap();
+            drop(writer);
+            // 目录中的snapshot不是录制文件
+            std::fs::write(dir.path().join("snapshot-10.bin"), [0; 8]).unwrap();
+
+            let segments = list_segments(dir.path()).unwrap();
+            assert_eq!(segments.len(), 2);
+            // 乱序的slot 11不写入索引
+            let index = read_slot_index(&segments[0]).unwrap();
+            assert_eq!(
+                index.iter().map(|(slot, _)| *slot).collect::<Vec<_>>(),
+                vec![10, 12]
+            );
+
+            let mut reader = open_segment(&segments[0], Some(11)).unwrap().unwrap();
+            let mut slots = vec![];
+            while let Some(frame) = RecordedFrame::read(&mut reader).unwrap() {
+                slots.push(frame.slot().unwrap());
+            }
+            assert_eq!(slots, vec![12, 11]);
+            assert!(open_segment(&segments[0], Some(13)).unwrap().is_none());
+        }
+    }
+}
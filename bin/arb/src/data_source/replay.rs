@@ -0,0 +1,111 @@
+use crate::data_source::{list_segments, open_segment, DataSource, RecordedFrame};
+use crate::dex::{is_subscribed_account, replace_subscribed_accounts, SubscribePlan};
+use crate::dex_data::DexJson;
+use crate::grpc_subscribe::GrpcMessage;
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use flume::Sender;
+use solana_sdk::pubkey::Pubkey;
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::{error, info};
+
+/// 回放Recorder录制的文件，path为目录时按时间顺序回放目录中所有的录制文件
+///
+/// 不需要任何RPC/GRPC服务，池子变化不会更新订阅
+pub struct ReplaySource {
+    path: PathBuf,
+    // 按录制时的接收间隔推送，否则尽快推送
+    realtime: bool,
+    // 通过slot索引跳过之前的数据
+    start_slot: Option<u64>,
+}
+
+impl ReplaySource {
+    pub fn new(path: PathBuf, realtime: bool, start_slot: Option<u64>) -> Self {
+        Self {
+            path,
+            realtime,
+            start_slot,
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for ReplaySource {
+    async fn subscribe(
+        &self,
+        dex_data: Vec<DexJson>,
+        _dex_data_receiver: flume::Receiver<Vec<DexJson>>,
+        message_sender: Sender<GrpcMessage>,
+    ) {
+        match SubscribePlan::build(dex_data) {
+            Ok(plan) => replace_subscribed_accounts(plan.subscribed_accounts),
+            Err(e) => {
+                error!("构建回放订阅账户失败，原因：{}", e);
+                return;
+            }
+        }
+        let path = self.path.clone();
+        let realtime = self.realtime;
+        let start_slot = self.start_slot;
+        let instant = Instant::now();
+        match tokio::task::spawn_blocking(move || {
+            replay(path, realtime, start_slot, message_sender)
+        })
+        .await
+        .map_err(|e| anyhow!(e))
+        .and_then(|result| result)
+        {
+            Ok(count) => info!(
+                "回放[{}]完成，消息数量 : {}, 耗时 : {:?}",
+                self.path.display(),
+                count,
+                instant.elapsed()
+            ),
+            Err(e) => error!("回放[{}]失败，原因：{}", self.path.display(), e),
+        }
+    }
+}
+
+fn replay(
+    path: PathBuf,
+    realtime: bool,
+    start_slot: Option<u64>,
+    message_sender: Sender<GrpcMessage>,
+) -> anyhow::Result<usize> {
+    let mut count = 0;
+    // 第一条消息的接收时间和回放开始的时间
+    let mut start: Option<(DateTime<Local>, Instant)> = None;
+    for segment in list_segments(&path)? {
+        let mut reader = match open_segment(&segment, start_slot)? {
+            Some(reader) => reader,
+            None => continue,
+        };
+        info!("回放录制文件 : {}", segment.display());
+        while let Some(frame) = RecordedFrame::read(&mut reader)? {
+            if realtime {
+                let (first_received_at, started) =
+                    *start.get_or_insert((frame.received_at, Instant::now()));
+                if let Ok(offset) = (frame.received_at - first_received_at).to_std() {
+                    std::thread::sleep(
+                        (started + offset).saturating_duration_since(Instant::now()),
+                    );
+                }
+            }
+            if let Some(message) = frame.into_grpc_message() {
+                if let GrpcMessage::Account(account) = &message {
+                    // 与GRPC推送相同，只保留订阅的账户
+                    match Pubkey::try_from(account.account_key.as_slice()) {
+                        Ok(account_key) if is_subscribed_account(&account_key) => {}
+                        _ => continue,
+                    }
+                }
+                message_sender.send(message)?;
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
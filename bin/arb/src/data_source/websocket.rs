@@ -0,0 +1,411 @@
+use crate::data_source::{ConnectionState, DataSource, ReconnectBackoff};
+use crate::dex::{
+    is_subscribed_account, replace_subscribed_accounts, update_subscribe_plan, SubscribePlan,
+};
+use crate::dex_data::DexJson;
+use crate::grpc_dedupe::GrpcDeduper;
+use crate::grpc_subscribe::{GrpcAccountMsg, GrpcMessage, GrpcTransactionMsg};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::Local;
+use flume::Sender;
+use futures_util::stream::{select_all, BoxStream};
+use futures_util::StreamExt;
+use solana_account_decoder_client_types::{UiAccount, UiAccountEncoding};
+use solana_pubsub_client::nonblocking::pubsub_client::PubsubClient;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_rpc_client_api::config::{
+    RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig, RpcTransactionLogsConfig,
+    RpcTransactionLogsFilter,
+};
+use solana_rpc_client_api::filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status_client_types::{
+    UiLoadedAddresses, UiTransactionEncoding, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+use yellowstone_grpc_proto::geyser::subscribe_request_filter_accounts_filter::Filter;
+use yellowstone_grpc_proto::geyser::subscribe_request_filter_accounts_filter_memcmp::Data;
+use yellowstone_grpc_proto::geyser::SubscribeRequestFilterAccountsFilter;
+use yellowstone_grpc_proto::prelude::{
+    Message, TokenBalance, Transaction, TransactionStatusMeta, UiTokenAmount,
+};
+
+/// 交易确认前获取交易的重试次数
+const GET_TRANSACTION_RETRIES: usize = 5;
+
+/// Solana RPC websocket数据源
+///
+/// 按GRPC的订阅请求订阅 : account -> accountSubscribe，owner -> programSubscribe，
+/// 交易 -> logsSubscribe，logs只有签名，需要再通过RPC获取交易
+///
+/// websocket没有write_version，写入缓存时为0
+pub struct WebsocketSubscribe {
+    ws_url: String,
+    // 获取交易，commitment为confirmed
+    rpc_client: Arc<RpcClient>,
+    backoff: ReconnectBackoff,
+    // 同一个交易会从多个logsSubscribe推送
+    deduper: GrpcDeduper,
+    connection: Arc<ConnectionState>,
+}
+
+enum WebsocketUpdate {
+    Account {
+        account_key: Pubkey,
+        slot: u64,
+        account: UiAccount,
+    },
+    Transaction {
+        signature: String,
+        slot: u64,
+    },
+}
+
+impl WebsocketSubscribe {
+    pub fn new(ws_url: String, rpc_url: String, backoff: ReconnectBackoff) -> Self {
+        Self {
+            deduper: GrpcDeduper::new(vec![ws_url.clone()]),
+            ws_url,
+            rpc_client: Arc::new(RpcClient::new_with_commitment(
+                rpc_url.clone(),
+                CommitmentConfig::confirmed(),
+            )),
+            backoff,
+            connection: ConnectionState::new(rpc_url, backoff),
+        }
+    }
+
+    /// plan发生变化时返回Ok，需要按新的plan重新订阅
+    async fn subscribe_plan(
+        &self,
+        plan: &SubscribePlan,
+        plan_receiver: &mut watch::Receiver<Arc<SubscribePlan>>,
+        message_sender: &Sender<GrpcMessage>,
+    ) -> anyhow::Result<()> {
+        let pubsub_client = PubsubClient::new(self.ws_url.as_str()).await?;
+        let mut streams: Vec<BoxStream<'_, WebsocketUpdate>> = vec![];
+        for filter in plan.request.accounts.values() {
+            for account in filter.account.iter() {
+                let account_key = Pubkey::from_str(account)?;
+                let (stream, _) = pubsub_client
+                    .account_subscribe(&account_key, Some(account_info_config()))
+                    .await?;
+                streams.push(
+                    stream
+                        .map(move |response| WebsocketUpdate::Account {
+                            account_key,
+                            slot: response.context.slot,
+                            account: response.value,
+                        })
+                        .boxed(),
+                );
+            }
+            let filters = filter
+                .filters
+                .iter()
+                .map(to_rpc_filter)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            for owner in filter.owner.iter() {
+                let config = RpcProgramAccountsConfig {
+                    filters: Some(filters.clone()),
+                    account_config: account_info_config(),
+                    ..Default::default()
+                };
+                let (stream, _) = pubsub_client
+                    .program_subscribe(&Pubkey::from_str(owner)?, Some(config))
+                    .await?;
+                streams.push(
+                    stream
+                        .filter_map(|response| async move {
+                            Some(WebsocketUpdate::Account {
+                                account_key: Pubkey::from_str(&response.value.pubkey).ok()?,
+                                slot: response.context.slot,
+                                account: response.value.account,
+                            })
+                        })
+                        .boxed(),
+                );
+            }
+        }
+        for filter in plan.request.transactions.values() {
+            for account in filter.account_include.iter() {
+                let (stream, _) = pubsub_client
+                    .logs_subscribe(
+                        RpcTransactionLogsFilter::Mentions(vec![account.clone()]),
+                        RpcTransactionLogsConfig {
+                            commitment: Some(CommitmentConfig::processed()),
+                        },
+                    )
+                    .await?;
+                streams.push(
+                    stream
+                        .filter_map(|response| async move {
+                            // 只处理成功的交易
+                            response
+                                .value
+                                .err
+                                .is_none()
+                                .then(|| WebsocketUpdate::Transaction {
+                                    signature: response.value.signature,
+                                    slot: response.context.slot,
+                                })
+                        })
+                        .boxed(),
+                );
+            }
+        }
+        replace_subscribed_accounts(plan.subscribed_accounts.clone());
+        info!("websocket订阅成功, 订阅数量 : {}", streams.len());
+        self.connection
+            .on_connected(|| plan_receiver.borrow().dex_json.clone());
+        let mut updates = select_all(streams);
+        let result = 'outer: loop {
+            tokio::select! {
+                update = updates.next() => match update {
+                    Some(update) => self.forward(update, message_sender),
+                    None => break Err(anyhow!("websocket连接断开")),
+                },
+                result = plan_receiver.changed() => match result {
+                    Ok(_) => break Ok(()),
+                    // 不再更新plan，继续接收推送
+                    Err(_) => loop {
+                        match updates.next().await {
+                            Some(update) => self.forward(update, message_sender),
+                            None => break 'outer Err(anyhow!("websocket连接断开")),
+                        }
+                    },
+                },
+            }
+        };
+        self.connection.on_disconnected();
+        result
+    }
+
+    fn forward(&self, update: WebsocketUpdate, message_sender: &Sender<GrpcMessage>) {
+        match update {
+            WebsocketUpdate::Account {
+                account_key,
+                slot,
+                account,
+            } => {
+                if !is_subscribed_account(&account_key) {
+                    return;
+                }
+                match account.decode::<Account>() {
+                    Some(account) => {
+                        if let Err(e) = message_sender.send(GrpcMessage::Account(GrpcAccountMsg {
+                            tx: vec![0; 64],
+                            account_key: account_key.to_bytes().to_vec(),
+                            owner_key: account.owner.to_bytes().to_vec(),
+                            data: account.data,
+                            write_version: 0,
+                            slot,
+                            endpoint: 0,
+                            received_timestamp: Local::now(),
+                        })) {
+                            error!("推送websocket Account消息失败, 原因 : {}", e);
+                        }
+                    }
+                    None => error!("解析websocket Account[{}]失败", account_key),
+                }
+            }
+            WebsocketUpdate::Transaction { signature, slot } => {
+                if !self.deduper.is_first_transaction(
+                    0,
+                    Pubkey::default(),
+                    signature.as_bytes(),
+                    slot,
+                ) {
+                    return;
+                }
+                let rpc_client = self.rpc_client.clone();
+                let message_sender = message_sender.clone();
+                tokio::spawn(async move {
+                    match get_transaction(rpc_client, signature.as_str(), slot).await {
+                        Ok(transaction_msg) => {
+                            if let Err(e) =
+                                message_sender.send(GrpcMessage::Transaction(transaction_msg))
+                            {
+                                error!("推送websocket Transaction消息失败, 原因 : {}", e);
+                            }
+                        }
+                        Err(e) => warn!("获取交易[{}]失败，原因 : {}", signature, e),
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataSource for WebsocketSubscribe {
+    /// 断线或plan变化后重新订阅，所有订阅都断开时的处理与GRPC相同
+    async fn subscribe(
+        &self,
+        dex_data: Vec<DexJson>,
+        dex_data_receiver: flume::Receiver<Vec<DexJson>>,
+        message_sender: Sender<GrpcMessage>,
+    ) {
+        let plan = match SubscribePlan::build(dex_data) {
+            Ok(plan) => plan,
+            Err(e) => {
+                error!("构建websocket订阅请求失败，原因：{}", e);
+                return;
+            }
+        };
+        let (plan_sender, mut plan_receiver) = watch::channel(Arc::new(plan));
+        tokio::spawn(update_subscribe_plan(dex_data_receiver, plan_sender));
+        let mut delay = self.backoff.min_delay;
+        loop {
+            let plan = plan_receiver.borrow_and_update().clone();
+            match self
+                .subscribe_plan(&plan, &mut plan_receiver, &message_sender)
+                .await
+            {
+                Ok(_) => {
+                    info!("websocket订阅更新, 池子数量 : {}", plan.dex_json.len());
+                    delay = self.backoff.min_delay;
+                    continue;
+                }
+                Err(e) => error!("websocket订阅失败，原因：{}", e),
+            }
+            warn!("websocket连接断开，{:?}后重连", delay);
+            tokio::time::sleep(delay).await;
+            delay = self.backoff.next_delay(delay);
+        }
+    }
+}
+
+fn account_info_config() -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        commitment: Some(CommitmentConfig::processed()),
+        ..Default::default()
+    }
+}
+
+fn to_rpc_filter(filter: &SubscribeRequestFilterAccountsFilter) -> anyhow::Result<RpcFilterType> {
+    match &filter.filter {
+        Some(Filter::Datasize(size)) => Ok(RpcFilterType::DataSize(*size)),
+        Some(Filter::Memcmp(memcmp)) => match &memcmp.data {
+            Some(Data::Bytes(bytes)) => Ok(RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+                memcmp.offset as usize,
+                bytes.clone(),
+            ))),
+            _ => Err(anyhow!("不支持的memcmp过滤条件")),
+        },
+        _ => Err(anyhow!("不支持的过滤条件")),
+    }
+}
+
+/// logs推送时交易可能还没有确认，失败时重试
+async fn get_transaction(
+    rpc_client: Arc<RpcClient>,
+    signature: &str,
+    slot: u64,
+) -> anyhow::Result<GrpcTransactionMsg> {
+    let instant = Instant::now();
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    let signature = Signature::from_str(signature)?;
+    let mut retries = 0;
+    let encoded = loop {
+        match rpc_client
+            .get_transaction_with_config(&signature, config)
+            .await
+        {
+            Ok(encoded) => break encoded,
+            Err(e) if retries >= GET_TRANSACTION_RETRIES => return Err(anyhow!(e)),
+            Err(_) => {
+                retries += 1;
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        }
+    };
+    let transaction = encoded
+        .transaction
+        .transaction
+        .decode()
+        .ok_or(anyhow!("解析交易失败"))?;
+    let meta = encoded.transaction.meta.ok_or(anyhow!("交易没有meta"))?;
+    Ok(GrpcTransactionMsg {
+        signature: signature.as_ref().to_vec(),
+        transaction: Some(Transaction {
+            signatures: transaction
+                .signatures
+                .iter()
+                .map(|signature| signature.as_ref().to_vec())
+                .collect(),
+            message: Some(Message {
+                account_keys: transaction
+                    .message
+                    .static_account_keys()
+                    .iter()
+                    .map(|key| key.to_bytes().to_vec())
+                    .collect(),
+                versioned: transaction.message.address_table_lookups().is_some(),
+                ..Default::default()
+            }),
+        }),
+        meta: Some(to_transaction_status_meta(meta)?),
+        // logs没有交易在slot中的位置
+        index: 0,
+        received_timestamp: Local::now(),
+        slot,
+        instant,
+        created_at: SystemTime::now().into(),
+    })
+}
+
+/// 只转换Arb使用的token balance和alt加载的地址
+fn to_transaction_status_meta(
+    meta: UiTransactionStatusMeta,
+) -> anyhow::Result<TransactionStatusMeta> {
+    let loaded_addresses =
+        Option::<UiLoadedAddresses>::from(meta.loaded_addresses).unwrap_or_default();
+    let to_bytes = |addresses: Vec<String>| {
+        addresses
+            .iter()
+            .map(|address| Pubkey::from_str(address).map(|key| key.to_bytes().to_vec()))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    let to_token_balances = |balances: Option<Vec<UiTransactionTokenBalance>>| {
+        balances
+            .unwrap_or_default()
+            .into_iter()
+            .map(|balance| TokenBalance {
+                account_index: balance.account_index as u32,
+                mint: balance.mint,
+                ui_token_amount: Some(UiTokenAmount {
+                    ui_amount: balance.ui_token_amount.ui_amount.unwrap_or_default(),
+                    decimals: balance.ui_token_amount.decimals as u32,
+                    amount: balance.ui_token_amount.amount,
+                    ui_amount_string: balance.ui_token_amount.ui_amount_string,
+                }),
+                owner: Option::<String>::from(balance.owner).unwrap_or_default(),
+                program_id: Option::<String>::from(balance.program_id).unwrap_or_default(),
+            })
+            .collect()
+    };
+    Ok(TransactionStatusMeta {
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        pre_token_balances: to_token_balances(meta.pre_token_balances.into()),
+        post_token_balances: to_token_balances(meta.post_token_balances.into()),
+        loaded_writable_addresses: to_bytes(loaded_addresses.writable)?,
+        loaded_readonly_addresses: to_bytes(loaded_addresses.readonly)?,
+        ..Default::default()
+    })
+}
@@ -7,15 +7,16 @@ use crate::dex::raydium_clmm::RaydiumCLMMRelationRecord;
 use crate::dex::raydium_cpmm::RaydiumCPMMAccountRelationRecord;
 use crate::dex::{AccountType, DexType};
 use crate::dex_data::DexJson;
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::anyhow;
 use enum_dispatch::enum_dispatch;
+use parking_lot::RwLock;
 use solana_sdk::pubkey::Pubkey;
-use std::sync::Arc;
 use tokio::sync::OnceCell;
 
-static ACCOUNT_RELATION_CACHE: OnceCell<Arc<AHashMap<Pubkey, AccountInfo>>> = OnceCell::const_new();
-static SUPPLEMENTARY_ACCOUNT_RELATION_CACHE: OnceCell<Arc<AHashMap<DexType, AccountType>>> =
+static ACCOUNT_RELATION_CACHE: OnceCell<RwLock<AHashMap<Pubkey, AccountInfo>>> =
+    OnceCell::const_new();
+static SUPPLEMENTARY_ACCOUNT_RELATION_CACHE: OnceCell<RwLock<AHashMap<DexType, AccountType>>> =
     OnceCell::const_new();
 
 #[enum_dispatch]
@@ -39,6 +40,47 @@ pub enum AccountRelationRecordType {
 }
 
 pub(crate) fn init_account_relations(dex_data: &[DexJson]) -> anyhow::Result<()> {
+    let (account_mapping, supplementary_account_mapping) = build_account_relations(dex_data)?;
+    ACCOUNT_RELATION_CACHE
+        .set(RwLock::new(account_mapping))
+        .map_or(Err(anyhow!("初始化AccountRelation失败")), |_| Ok(()))?;
+    SUPPLEMENTARY_ACCOUNT_RELATION_CACHE
+        .set(RwLock::new(supplementary_account_mapping))
+        .map_or(Err(anyhow!("初始化AccountRelation失败")), |_| Ok(()))
+}
+
+/// 运行时新增池子，追加account之间的关系
+pub(crate) fn extend_account_relations(dex_data: &[DexJson]) -> anyhow::Result<()> {
+    let (account_mapping, supplementary_account_mapping) = build_account_relations(dex_data)?;
+    ACCOUNT_RELATION_CACHE
+        .get()
+        .ok_or(anyhow!("AccountRelation未初始化"))?
+        .write()
+        .extend(account_mapping);
+    SUPPLEMENTARY_ACCOUNT_RELATION_CACHE
+        .get()
+        .ok_or(anyhow!("AccountRelation未初始化"))?
+        .write()
+        .extend(supplementary_account_mapping);
+    Ok(())
+}
+
+/// 运行时移除池子，删除与池子相关的account关系
+pub(crate) fn remove_account_relations(pool_ids: &AHashSet<Pubkey>) -> anyhow::Result<()> {
+    ACCOUNT_RELATION_CACHE
+        .get()
+        .ok_or(anyhow!("AccountRelation未初始化"))?
+        .write()
+        .retain(|_, info| !pool_ids.contains(&info.pool_id));
+    Ok(())
+}
+
+fn build_account_relations(
+    dex_data: &[DexJson],
+) -> anyhow::Result<(
+    AHashMap<Pubkey, AccountInfo>,
+    AHashMap<DexType, AccountType>,
+)> {
     let mut account_mapping = AHashMap::with_capacity(1000);
     let mut supplementary_account_mapping = AHashMap::with_capacity(1000);
     for record_type in vec![
@@ -79,17 +121,12 @@ pub(crate) fn init_account_relations(dex_data: &[DexJson]) -> anyhow::Result<()>
             Ok(())
         })?;
     }
-    ACCOUNT_RELATION_CACHE
-        .set(Arc::new(account_mapping))
-        .map_or(Err(anyhow!("初始化AccountRelation失败")), |_| Ok(()))?;
-    SUPPLEMENTARY_ACCOUNT_RELATION_CACHE
-        .set(Arc::new(supplementary_account_mapping))
-        .map_or(Err(anyhow!("初始化AccountRelation失败")), |_| Ok(()))
+    Ok((account_mapping, supplementary_account_mapping))
 }
 
 #[inline]
 pub fn is_follow_vault(vault_account: &Pubkey) -> Option<(Pubkey, DexType)> {
-    match ACCOUNT_RELATION_CACHE.get()?.get(vault_account) {
+    match ACCOUNT_RELATION_CACHE.get()?.read().get(vault_account) {
         None => None,
         Some(a) => Some((a.pool_id.clone(), a.dex_type)),
     }
@@ -99,11 +136,12 @@ pub fn get_dex_type_and_account_type(
     owner: &Pubkey,
     account_key: &Pubkey,
 ) -> Option<(DexType, AccountType)> {
-    match ACCOUNT_RELATION_CACHE.get()?.get(account_key) {
+    match ACCOUNT_RELATION_CACHE.get()?.read().get(account_key) {
         None => {
             let dex_type = DexType::try_from(owner).ok()?;
             let account_type = SUPPLEMENTARY_ACCOUNT_RELATION_CACHE
                 .get()?
+                .read()
                 .get(&dex_type)?
                 .clone();
             Some((dex_type, account_type))
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+/// 账户数据写入缓存时的版本，保证同一个账户不会被旧数据覆盖
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CacheVersion {
+    // GRPC推送的Account，权威数据，RPC加载的数据write_version为0
+    // endpoint : 推送的端点下标，write_version只在同一个端点内可比较，RPC加载的数据为None
+    Account {
+        slot: u64,
+        write_version: u64,
+        endpoint: Option<usize>,
+    },
+    // 交易的post token balance，index : 交易在slot中的位置
+    Transaction {
+        slot: u64,
+        index: u64,
+    },
+}
+
+impl CacheVersion {
+    /// 没有slot的数据(如启动时加载的clock)，任何推送的数据都可以覆盖
+    pub const UNVERSIONED: CacheVersion = CacheVersion::Account {
+        slot: 0,
+        write_version: 0,
+        endpoint: None,
+    };
+
+    #[inline]
+    pub fn slot(&self) -> u64 {
+        match self {
+            CacheVersion::Account { slot, .. } => *slot,
+            CacheVersion::Transaction { slot, .. } => *slot,
+        }
+    }
+
+    /// 交易post balance没有write_version，返回0
+    #[inline]
+    pub fn write_version(&self) -> u64 {
+        match self {
+            CacheVersion::Account { write_version, .. } => *write_version,
+            CacheVersion::Transaction { .. } => 0,
+        }
+    }
+
+    /// 是否可以覆盖 previous 写入的数据
+    ///
+    /// 不同slot : 新的slot优先；同一个slot : Account始终优先于Transaction，相同版本可以重复写入
+    ///
+    /// 同一个slot的Account : 同一个端点按write_version排序，RPC加载的数据不覆盖推送的数据，
+    /// 不同端点的write_version不可比较，只按slot排序，后到达的覆盖
+    pub fn supersedes(&self, previous: &CacheVersion) -> bool {
+        if self.slot() != previous.slot() {
+            return self.slot() > previous.slot();
+        }
+        match (self, previous) {
+            (
+                CacheVersion::Account {
+                    write_version,
+                    endpoint,
+                    ..
+                },
+                CacheVersion::Account {
+                    write_version: previous_write_version,
+                    endpoint: previous_endpoint,
+                    ..
+                },
+            ) => match (endpoint, previous_endpoint) {
+                (None, Some(_)) => false,
+                (Some(endpoint), Some(previous_endpoint)) if endpoint != previous_endpoint => true,
+                _ => write_version >= previous_write_version,
+            },
+            (CacheVersion::Account { .. }, CacheVersion::Transaction { .. }) => true,
+            (CacheVersion::Transaction { .. }, CacheVersion::Account { .. }) => false,
+            (
+                CacheVersion::Transaction { index, .. },
+                CacheVersion::Transaction {
+                    index: previous_index,
+                    ..
+                },
+            ) => index > previous_index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::cache_version::CacheVersion;
+
+    #[test]
+    fn test_cache_version_supersedes() {
+        let account = CacheVersion::Account {
+            slot: 10,
+            write_version: 100,
+            endpoint: Some(0),
+        };
+        let transaction = CacheVersion::Transaction { slot: 10, index: 5 };
+        // 同一个slot内Account优先
+        assert!(account.supersedes(&transaction));
+        assert!(!transaction.supersedes(&account));
+        // 同一个slot内按write_version排序
+        assert!(account.supersedes(&account));
+        assert!(!CacheVersion::Account {
+            slot: 10,
+            write_version: 99,
+            endpoint: Some(0),
+        }
+        .supersedes(&account));
+        // 不同端点的write_version不可比较，同一个slot后到达的覆盖
+        let other_endpoint = CacheVersion::Account {
+            slot: 10,
+            write_version: 99,
+            endpoint: Some(1),
+        };
+        assert!(other_endpoint.supersedes(&account));
+        assert!(account.supersedes(&other_endpoint));
+        assert!(!CacheVersion::Account {
+            slot: 9,
+            write_version: 1000,
+            endpoint: Some(1),
+        }
+        .supersedes(&account));
+        // 同一个slot内RPC加载的数据不覆盖推送的数据
+        let rpc = CacheVersion::Account {
+            slot: 10,
+            write_version: 0,
+            endpoint: None,
+        };
+        assert!(!rpc.supersedes(&account));
+        assert!(account.supersedes(&rpc));
+        // 新slot的交易可以覆盖旧slot的Account
+        assert!(CacheVersion::Transaction { slot: 11, index: 0 }.supersedes(&account));
+        // 旧slot的Account不能覆盖新slot的交易
+        assert!(!account.supersedes(&CacheVersion::Transaction { slot: 11, index: 0 }));
+        assert!(CacheVersion::Transaction { slot: 10, index: 6 }.supersedes(&transaction));
+        assert!(!CacheVersion::Transaction { slot: 10, index: 4 }.supersedes(&transaction));
+        // 没有版本的数据不能覆盖推送的数据
+        assert!(account.supersedes(&CacheVersion::UNVERSIONED));
+        assert!(!CacheVersion::UNVERSIONED.supersedes(&account));
+    }
+}
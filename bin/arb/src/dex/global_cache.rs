@@ -1,13 +1,16 @@
 use crate::dex::utils::read_from;
-use crate::dex::{FromCache, CLOCK_ID, MINT2022_PROGRAM_ID, MINT_PROGRAM_ID};
+use crate::dex::{CacheVersion, FromCache, MintInfo, CLOCK_ID, MINT_PROGRAM_ID};
 use ahash::{AHashMap, RandomState};
+use anyhow::anyhow;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use parking_lot::RwLock;
 use solana_sdk::address_lookup_table::AddressLookupTableAccount;
 use solana_sdk::clock::Clock;
+use solana_sdk::instruction::AccountMeta;
 use solana_sdk::pubkey::Pubkey;
 use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
-use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::OnceCell;
 
@@ -22,9 +25,16 @@ pub fn get_global_cache() -> &'static GlobalCache {
 }
 
 #[derive(Debug)]
-pub struct DynamicCache(DashMap<Pubkey, Arc<Vec<u8>>, RandomState>);
+pub struct DynamicCache(DashMap<Pubkey, CacheEntry, RandomState>);
+/// 账户数据和写入时的版本
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub data: Arc<Vec<u8>>,
+    pub version: CacheVersion,
+}
+/// 运行时新增池子时也需要写入
 #[derive(Debug)]
-pub struct StaticCache(AHashMap<Pubkey, Arc<Vec<u8>>>);
+pub struct StaticCache(DashMap<Pubkey, Arc<Vec<u8>>, RandomState>);
 #[derive(Debug)]
 pub struct AltCache(AHashMap<Pubkey, Vec<AddressLookupTableAccount>>);
 
@@ -33,29 +43,109 @@ pub struct GlobalCache {
     dynamic_account_cache: DynamicCache,
     static_account_cache: StaticCache,
     alt_cache: RwLock<AltCache>,
+    // 版本比缓存旧而被拒绝的写入次数
+    rejected_writes: AtomicU64,
+    // 订阅推送的Account中最大的slot，之前的slot里没有推送的账户没有变化
+    stream_slot: AtomicU64,
+    // 池子涉及的Mint
+    mint_infos: DashMap<Pubkey, Arc<MintInfo>, RandomState>,
 }
 
 impl GlobalCache {
     pub fn init() -> Self {
         Self {
             dynamic_account_cache: DynamicCache::new(10000),
-            static_account_cache: StaticCache::new(),
+            static_account_cache: StaticCache::new(1_000),
             alt_cache: RwLock::new(AltCache::new()),
+            rejected_writes: AtomicU64::new(0),
+            stream_slot: AtomicU64::new(0),
+            mint_infos: DashMap::with_hasher(RandomState::default()),
+        }
+    }
+
+    /// 没有版本的数据，任何带版本的数据都可以覆盖
+    pub fn upsert_dynamic(&self, account_key: Pubkey, value: Vec<u8>) -> bool {
+        self.upsert_dynamic_with_version(account_key, value, CacheVersion::UNVERSIONED)
+    }
+
+    /// 带版本写入，不比已写入的版本新时忽略，返回是否写入
+    pub fn upsert_dynamic_with_version(
+        &self,
+        account_key: Pubkey,
+        value: Vec<u8>,
+        version: CacheVersion,
+    ) -> bool {
+        let inserted = self
+            .dynamic_account_cache
+            .insert(account_key, value, version);
+        if !inserted {
+            self.rejected_writes.fetch_add(1, Ordering::Relaxed);
         }
+        inserted
+    }
+
+    pub fn rejected_write_count(&self) -> u64 {
+        self.rejected_writes.load(Ordering::Relaxed)
     }
 
-    pub fn upsert_dynamic(&self, account_key: Pubkey, value: Vec<u8>) -> Option<Arc<Vec<u8>>> {
-        self.dynamic_account_cache.insert(account_key, value)
+    /// 收到订阅推送的Account，不论是否写入缓存
+    pub fn confirm_stream_slot(&self, slot: u64) {
+        self.stream_slot.fetch_max(slot, Ordering::Release);
     }
 
-    pub fn upsert_static(&mut self, account_key: Pubkey, value: Vec<u8>) -> Option<Arc<Vec<u8>>> {
+    pub fn stream_slot(&self) -> u64 {
+        self.stream_slot.load(Ordering::Acquire)
+    }
+
+    pub fn upsert_static(&self, account_key: Pubkey, value: Vec<u8>) -> Option<Arc<Vec<u8>>> {
         self.static_account_cache.insert(account_key, value)
     }
 
+    pub fn upsert_mint_info(&self, mint: Pubkey, mint_info: MintInfo) {
+        self.mint_infos.insert(mint, Arc::new(mint_info));
+    }
+
+    pub fn get_mint_info(&self, mint: &Pubkey) -> Option<Arc<MintInfo>> {
+        self.mint_infos.get(mint).map(|v| v.value().clone())
+    }
+
     pub fn upsert_alt(&self, pool_id: Pubkey, alts: Vec<AddressLookupTableAccount>) {
         self.alt_cache.write().insert(pool_id, alts)
     }
 
+    /// 导出动态数据，用于保存snapshot
+    pub(crate) fn dynamic_entries(&self) -> Vec<(Pubkey, CacheEntry)> {
+        self.dynamic_account_cache
+            .0
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect()
+    }
+
+    pub(crate) fn static_entries(&self) -> Vec<(Pubkey, Arc<Vec<u8>>)> {
+        self.static_account_cache
+            .0
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect()
+    }
+
+    pub(crate) fn alt_entries(&self) -> Vec<(Pubkey, Vec<AddressLookupTableAccount>)> {
+        self.alt_cache
+            .read()
+            .0
+            .iter()
+            .map(|(pool_id, alts)| (*pool_id, alts.clone()))
+            .collect()
+    }
+
+    pub(crate) fn mint_info_entries(&self) -> Vec<(Pubkey, Arc<MintInfo>)> {
+        self.mint_infos
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect()
+    }
+
     fn get_account_data<T: FromCache>(&self, account_key: &Pubkey) -> Option<T> {
         let static_data = self.static_account_cache.get(account_key);
         let dynamic_data = self.dynamic_account_cache.get(account_key);
@@ -73,24 +163,50 @@ impl DynamicCache {
     }
 
     pub fn get(&self, account_key: &Pubkey) -> Option<Arc<Vec<u8>>> {
-        self.0.get(account_key).map(|v| v.value().clone())
+        self.0.get(account_key).map(|v| v.value().data.clone())
     }
 
-    pub fn insert(&self, account_key: Pubkey, data: Vec<u8>) -> Option<Arc<Vec<u8>>> {
-        self.0.insert(account_key, Arc::new(data))
+    pub fn get_slot(&self, account_key: &Pubkey) -> Option<u64> {
+        self.0.get(account_key).map(|v| v.value().version.slot())
+    }
+
+    /// 持有entry的锁比较版本后写入，多个线程并发写入时旧数据不会覆盖新数据
+    pub fn insert(&self, account_key: Pubkey, data: Vec<u8>, version: CacheVersion) -> bool {
+        match self.0.entry(account_key) {
+            Entry::Occupied(mut entry) => {
+                if !version.supersedes(&entry.get().version) {
+                    return false;
+                }
+                entry.insert(CacheEntry {
+                    data: Arc::new(data),
+                    version,
+                });
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(CacheEntry {
+                    data: Arc::new(data),
+                    version,
+                });
+            }
+        }
+        true
     }
 }
 
 impl StaticCache {
-    pub(crate) fn new() -> Self {
-        Self(AHashMap::with_capacity(1_000))
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self(DashMap::with_capacity_and_hasher_and_shard_amount(
+            capacity,
+            RandomState::default(),
+            128,
+        ))
     }
 
     pub fn get(&self, account_key: &Pubkey) -> Option<Arc<Vec<u8>>> {
-        self.0.get(account_key).map_or(None, |v| Some(v.clone()))
+        self.0.get(account_key).map(|v| v.value().clone())
     }
 
-    pub fn insert(&mut self, account_key: Pubkey, data: Vec<u8>) -> Option<Arc<Vec<u8>>> {
+    pub fn insert(&self, account_key: Pubkey, data: Vec<u8>) -> Option<Arc<Vec<u8>>> {
         self.0.insert(account_key, Arc::new(data))
     }
 }
@@ -115,26 +231,36 @@ pub fn get_account_data<T: FromCache>(account_key: &Pubkey) -> Option<T> {
     get_global_cache().get_account_data::<T>(account_key)
 }
 
+pub fn get_mint_info(mint: &Pubkey) -> Option<Arc<MintInfo>> {
+    get_global_cache().get_mint_info(mint)
+}
+
+/// 缓存中没有的Mint视为Token program
 pub fn get_token_program(mint: &Pubkey) -> Pubkey {
-    if get_global_cache().static_account_cache.0.contains_key(mint) {
-        MINT2022_PROGRAM_ID
-    } else {
-        MINT_PROGRAM_ID
-    }
+    get_global_cache()
+        .mint_infos
+        .get(mint)
+        .map_or(MINT_PROGRAM_ID, |mint_info| mint_info.token_program)
+}
+
+pub fn get_transfer_fee_config(mint: &Pubkey) -> Option<TransferFeeConfig> {
+    get_global_cache()
+        .mint_infos
+        .get(mint)
+        .and_then(|mint_info| mint_info.transfer_fee)
 }
 
-pub fn get_token2022_data(mint_key: &Pubkey) -> Option<TransferFeeConfig> {
-    let static_data = &get_global_cache().static_account_cache;
-    static_data.get(mint_key).map_or(None, |data| {
-        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(data.as_slice()).map_or(
-            None,
-            |mint_extensions| {
-                mint_extensions
-                    .get_extension::<TransferFeeConfig>()
-                    .map_or(None, |result| Some(result.clone()))
-            },
-        )
-    })
+/// transfer hook需要附带的账户，没有transfer hook时为空
+pub fn get_transfer_hook_accounts(mint: &Pubkey) -> anyhow::Result<Vec<AccountMeta>> {
+    let Some(hook) = get_global_cache()
+        .mint_infos
+        .get(mint)
+        .and_then(|mint_info| mint_info.transfer_hook.clone())
+    else {
+        return Ok(vec![]);
+    };
+    hook.accounts(mint)
+        .ok_or(anyhow!("Mint[{}]的transfer hook额外账户未能预先解析", mint))
 }
 
 pub fn get_clock() -> Option<Clock> {
@@ -150,9 +276,56 @@ pub fn get_alt(pool_id: &Pubkey) -> Option<Vec<AddressLookupTableAccount>> {
     get_global_cache().alt_cache.read().get(pool_id)
 }
 
-pub fn update_cache(account_key: Pubkey, data: Vec<u8>) -> anyhow::Result<()> {
+/// 返回是否写入，比缓存旧的数据不会写入
+pub fn update_cache(account_key: Pubkey, data: Vec<u8>, version: CacheVersion) -> bool {
+    get_global_cache().upsert_dynamic_with_version(account_key, data, version)
+}
+
+/// 账户数据最后一次写入时所在的slot，没有订阅的账户返回None
+pub fn get_cache_slot(account_key: &Pubkey) -> Option<u64> {
     get_global_cache()
         .dynamic_account_cache
-        .insert(account_key, data);
-    Ok(())
+        .get_slot(account_key)
+}
+
+pub fn confirm_stream_slot(slot: u64) {
+    get_global_cache().confirm_stream_slot(slot)
+}
+
+/// 订阅推送的Account中最大的slot，还没有收到推送时为0
+pub fn get_stream_slot() -> u64 {
+    get_global_cache().stream_slot()
+}
+
+/// 版本比缓存旧而被拒绝的写入次数
+pub fn rejected_write_count() -> u64 {
+    get_global_cache().rejected_write_count()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::global_cache::DynamicCache;
+    use crate::dex::CacheVersion;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_dynamic_cache_insert_order() {
+        let cache = DynamicCache::new(1);
+        let key = Pubkey::new_unique();
+        let version = |slot, write_version| CacheVersion::Account {
+            slot,
+            write_version,
+            endpoint: Some(0),
+        };
+        assert!(cache.insert(key, vec![1], CacheVersion::UNVERSIONED));
+        assert!(cache.insert(key, vec![2], version(10, 5)));
+        // 乱序到达的旧数据
+        assert!(!cache.insert(key, vec![3], version(10, 4)));
+        assert!(!cache.insert(key, vec![4], version(9, 100)));
+        assert!(!cache.insert(key, vec![5], CacheVersion::UNVERSIONED));
+        assert_eq!(cache.get(&key).unwrap().as_slice(), &[2]);
+        assert!(cache.insert(key, vec![6], version(11, 0)));
+        assert_eq!(cache.get(&key).unwrap().as_slice(), &[6]);
+        assert_eq!(cache.get_slot(&key), Some(11));
+    }
 }
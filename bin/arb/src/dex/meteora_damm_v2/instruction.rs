@@ -2,8 +2,8 @@ use crate::dex::meteora_damm_v2::{
     DAMM_V2_EVENT_AUTHORITY, DAMM_V2_POOL_AUTHORITY, DAMM_V2_PROGRAM_ID,
 };
 use crate::dex::{
-    get_alt, get_token_program, DexType, InstructionMaterial,
-    InstructionMaterialConverter, ATA_PROGRAM_ID,
+    get_alt, get_token_program, DexType, InstructionMaterial, InstructionMaterialConverter,
+    QuoteResult, ATA_PROGRAM_ID,
 };
 use crate::metadata::{get_keypair, MintAtaPair};
 use solana_sdk::instruction::AccountMeta;
@@ -18,6 +18,7 @@ impl InstructionMaterialConverter for MeteoraDAMMV2InstructionMaterialConverter
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        _quote_result: Option<&QuoteResult>,
     ) -> anyhow::Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let pool = crate::dex::global_cache::get_account_data::<Pool>(pool_id).unwrap();
@@ -108,6 +108,67 @@ pub fn get_next_sqrt_price_from_input(
     }
 }
 
+/// Gets the next sqrt price given an output amount of token_a or token_b
+/// Throws if price or liquidity are 0, or if the output exceeds the liquidity
+pub fn get_next_sqrt_price_from_output(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount_out: u64,
+    a_for_b: bool,
+) -> Result<u128> {
+    assert!(sqrt_price > 0);
+    assert!(liquidity > 0);
+
+    // round to make sure that the exact output is met
+    if a_for_b {
+        get_next_sqrt_price_from_amount_b_output_rounding_down(sqrt_price, liquidity, amount_out)
+    } else {
+        get_next_sqrt_price_from_amount_a_output_rounding_up(sqrt_price, liquidity, amount_out)
+    }
+}
+
+/// Gets the next sqrt price √P' given a negative delta of token_a
+///
+/// # Formula
+///
+/// * `√P' = √P * L / (L - Δx * √P)`
+fn get_next_sqrt_price_from_amount_a_output_rounding_up(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u64,
+) -> Result<u128> {
+    if amount == 0 {
+        return Ok(sqrt_price);
+    }
+    let sqrt_price = U256::from(sqrt_price);
+    let liquidity = U256::from(liquidity);
+
+    let product = U256::from(amount).safe_mul(sqrt_price)?;
+    let denominator = liquidity.safe_sub(product)?;
+    require!(denominator > U256::ZERO, MathOverflow);
+    let result = mul_div_u256(liquidity, sqrt_price, denominator, Rounding::Up)
+        .ok_or_else(|| anyhow!(MathOverflow))?;
+    return Ok(result.try_into().map_err(|_| anyhow!(TypeCastFailed))?);
+}
+
+/// Gets the next sqrt price √P' given a negative delta of token_b
+///
+/// # Formula
+///
+/// * `√P' = √P - Δy / L`
+fn get_next_sqrt_price_from_amount_b_output_rounding_down(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount: u64,
+) -> Result<u128> {
+    let quotient = U256::from(amount)
+        .safe_shl((RESOLUTION * 2) as usize)?
+        .div_ceil(U256::from(liquidity));
+
+    let result = U256::from(sqrt_price).safe_sub(quotient)?;
+    Ok(result.try_into().map_err(|_| anyhow!(TypeCastFailed))?)
+}
+
 /// Gets the next sqrt price √P' given a delta of token_a
 ///
 /// Always round up because
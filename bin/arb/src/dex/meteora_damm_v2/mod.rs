@@ -15,7 +15,7 @@ use solana_sdk::pubkey::Pubkey;
 pub use account_subscriber::MeteoraDAMMV2AccountSubscriber;
 pub use data_slice::MeteoraDAMMV2DataSlicer;
 pub use instruction::MeteoraDAMMV2InstructionMaterialConverter;
-pub use quote::MeteoraDAMMV2Quoter;
+pub use quote::{MeteoraDAMMV2PreparedQuote, MeteoraDAMMV2Quoter};
 pub use relation::MeteoraDAMMV2RelationRecord;
 pub use snapshot_loader::MeteoraDAMMV2SnapshotLoader;
 
@@ -31,6 +31,10 @@ mod constants {
 
     pub const ONE_Q64: u128 = 1u128 << 64;
 
+    pub const MIN_SQRT_PRICE: u128 = 4295048016;
+
+    pub const MAX_SQRT_PRICE: u128 = 79226673521066979257578248091;
+
     pub const BASIS_POINT_MAX: u64 = 10_000;
 
     pub mod activation {
@@ -1,80 +1,338 @@
+use crate::dex::meteora_damm_v2::constants::fee::{FEE_DENOMINATOR, MAX_FEE_NUMERATOR};
+use crate::dex::meteora_damm_v2::constants::{MAX_SQRT_PRICE, MIN_SQRT_PRICE};
+use crate::dex::meteora_damm_v2::error::{
+    MathOverflow, PoolDisabled, PriceRangeViolation, TypeCastFailed,
+};
 use crate::dex::meteora_damm_v2::state::fee::FeeMode;
-use crate::dex::meteora_damm_v2::state::pool::Pool;
+use crate::dex::meteora_damm_v2::state::pool::{Pool, PoolStatus};
 use crate::dex::meteora_damm_v2::{ActivationType, TradeDirection};
-use crate::dex::{get_account_data, get_clock, get_transfer_fee, QuoteResult, Quoter};
-use anyhow::{ensure, Context, Ok, Result};
+use crate::dex::quoter::sqrt_x64_price;
+use crate::dex::{
+    calculate_inverse_transfer_fee, calculate_transfer_fee, get_account_data, get_clock,
+    get_transfer_fee_config, ConstantProductReserves, PreparedQuote, PreparedQuoteType, QuoteError,
+    QuoteResult, Quoter,
+};
+use anyhow::{anyhow, ensure, Context, Ok, Result};
+use ruint::aliases::U256;
 use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
 use std::ops::Sub;
 
+/// 数学库返回的错误
+const SWAP_ERRORS: &[(&str, QuoteError)] = &[
+    (MathOverflow, QuoteError::MathOverflow),
+    (TypeCastFailed, QuoteError::MathOverflow),
+    (PriceRangeViolation, QuoteError::InsufficientLiquidity),
+    (PoolDisabled, QuoteError::PoolDisabled),
+    ("amount is zero", QuoteError::InvalidAmount),
+];
+
 #[derive(Debug)]
 pub struct MeteoraDAMMV2Quoter;
 
 impl Quoter for MeteoraDAMMV2Quoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.prepare(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        let pool = get_account_data::<Pool>(pool_id).ok_or(QuoteError::MissingCache)?;
+        MeteoraDAMMV2PreparedQuote::new(pool)
+            .map(PreparedQuoteType::from)
+            .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))
+    }
+
+    fn constant_product_reserves(
+        &self,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Option<ConstantProductReserves> {
         let pool = get_account_data::<Pool>(pool_id)?;
-        Some(QuoteResult {
-            amount_out: get_quote(pool, amount_in, swap_direction).ok()?,
-        })
+        get_constant_product_reserves(pool, swap_direction)
+            .ok()
+            .flatten()
+    }
+
+    fn marginal_price(&self, swap_direction: bool, pool_id: &Pubkey) -> Option<f64> {
+        let mut pool = get_account_data::<Pool>(pool_id)?;
+        let fee = 1.0 - get_trade_fee_rate(&mut pool).ok()?;
+        Some(sqrt_x64_price(pool.sqrt_price, swap_direction) * fee)
     }
 }
 
-fn get_quote(mut pool: Pool, amount_in: u64, swap_direction: bool) -> Result<u64> {
-    ensure!(amount_in > 0, "amount is zero");
-    let clock = get_clock().expect("无法获取Clock");
-    let current_timestamp = clock.unix_timestamp as u64;
-    let current_slot = clock.slot as u64;
-    let epoch = clock.epoch as u64;
-    let result = if pool.dynamic_fee.is_dynamic_fee_enable() {
-        pool.update_pre_swap(current_timestamp)?;
-        get_internal_quote(
-            &pool,
-            current_timestamp,
-            current_slot,
-            epoch,
-            amount_in,
-            swap_direction,
-            true,
-        )
+/// 全价格区间的池子等价于恒定乘积 : 虚拟储备 a = L / √P, b = L * √P
+fn get_constant_product_reserves(
+    mut pool: Pool,
+    swap_direction: bool,
+) -> Result<Option<ConstantProductReserves>> {
+    if pool.sqrt_min_price != MIN_SQRT_PRICE
+        || pool.sqrt_max_price != MAX_SQRT_PRICE
+        || pool.sqrt_price == 0
+        || get_transfer_fee_config(&pool.token_a_mint).is_some()
+        || get_transfer_fee_config(&pool.token_b_mint).is_some()
+    {
+        return Ok(None);
+    }
+    let fee = 1.0 - get_trade_fee_rate(&mut pool)?;
+
+    let reserve_a = pool.liquidity / pool.sqrt_price;
+    let reserve_b =
+        u128::try_from((U256::from(pool.liquidity) * U256::from(pool.sqrt_price)) >> 128)
+            .map_err(|_| anyhow!(TypeCastFailed))?;
+    let trade_direction = if swap_direction {
+        TradeDirection::AtoB
     } else {
-        get_internal_quote(
-            &pool,
-            current_timestamp,
-            current_slot,
-            epoch,
-            amount_in,
-            swap_direction,
-            true,
-        )
+        TradeDirection::BtoA
     };
+    let (fee_in, fee_out) =
+        if FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, true)?.fees_on_input {
+            (fee, 1.0)
+        } else {
+            (1.0, fee)
+        };
+    Ok(if swap_direction {
+        ConstantProductReserves::new(reserve_a, reserve_b, fee_in, fee_out)
+    } else {
+        ConstantProductReserves::new(reserve_b, reserve_a, fee_in, fee_out)
+    })
+}
+
+/// 当前的交易手续费率(含动态手续费)
+fn get_trade_fee_rate(pool: &mut Pool) -> Result<f64> {
+    let clock = get_clock().context("无法获取Clock")?;
+    let current_point =
+        match ActivationType::try_from(pool.activation_type).context("invalid activation type")? {
+            ActivationType::Slot => clock.slot,
+            ActivationType::Timestamp => clock.unix_timestamp as u64,
+        };
+    pool.update_pre_swap(clock.unix_timestamp as u64)?;
+    let trade_fee_numerator = pool
+        .get_total_trading_fee(current_point, pool.activation_point)?
+        .min(MAX_FEE_NUMERATOR as u128);
+    Ok(trade_fee_numerator as f64 / FEE_DENOMINATOR as f64)
+}
 
-    result
+/// 池子被禁用或未到activation_point时不能swap
+fn validate_swap_activation(pool: &Pool, current_slot: u64, current_timestamp: u64) -> Result<()> {
+    ensure!(
+        pool.pool_status == u8::from(PoolStatus::Enable),
+        PoolDisabled
+    );
+    let current_point =
+        match ActivationType::try_from(pool.activation_type).context("invalid activation type")? {
+            ActivationType::Slot => current_slot,
+            ActivationType::Timestamp => current_timestamp,
+        };
+    ensure!(current_point >= pool.activation_point, PoolDisabled);
+    Ok(())
 }
 
-fn get_internal_quote(
-    pool: &Pool,
+/// 已更新动态手续费的池子、Clock和两个Mint的transfer fee配置
+pub struct MeteoraDAMMV2PreparedQuote {
+    pool: Pool,
     current_timestamp: u64,
     current_slot: u64,
     epoch: u64,
-    amount_in: u64,
-    a_to_b: bool,
-    has_referral: bool,
-) -> Result<u64> {
-    let activation_type =
-        ActivationType::try_from(pool.activation_type).context("invalid activation type")?;
-
-    let current_point = match activation_type {
-        ActivationType::Slot => current_slot,
-        ActivationType::Timestamp => current_timestamp,
-    };
+    token_a_transfer_fee: Option<TransferFeeConfig>,
+    token_b_transfer_fee: Option<TransferFeeConfig>,
+}
 
-    let (trade_direction, token_in) = if a_to_b {
-        (TradeDirection::AtoB, pool.token_a_mint)
-    } else {
-        (TradeDirection::BtoA, pool.token_b_mint)
-    };
-    let actual_amount_in = amount_in.sub(get_transfer_fee(&token_in, epoch, amount_in));
-    let fee_mode = &FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
-    Ok(pool
-        .get_swap_result(actual_amount_in, fee_mode, trade_direction, current_point)?
-        .output_amount)
+impl MeteoraDAMMV2PreparedQuote {
+    fn new(mut pool: Pool) -> Result<Self> {
+        let clock = get_clock().ok_or(QuoteError::MissingClock)?;
+        let current_timestamp = clock.unix_timestamp as u64;
+        validate_swap_activation(&pool, clock.slot, current_timestamp)?;
+        if pool.dynamic_fee.is_dynamic_fee_enable() {
+            pool.update_pre_swap(current_timestamp)?;
+        }
+        Ok(Self {
+            token_a_transfer_fee: get_transfer_fee_config(&pool.token_a_mint),
+            token_b_transfer_fee: get_transfer_fee_config(&pool.token_b_mint),
+            pool,
+            current_timestamp,
+            current_slot: clock.slot,
+            epoch: clock.epoch,
+        })
+    }
+
+    fn get_internal_quote(
+        &self,
+        amount_in: u64,
+        a_to_b: bool,
+        has_referral: bool,
+    ) -> Result<QuoteResult> {
+        ensure!(amount_in > 0, "amount is zero");
+        let pool = &self.pool;
+        let activation_type =
+            ActivationType::try_from(pool.activation_type).context("invalid activation type")?;
+
+        let current_point = match activation_type {
+            ActivationType::Slot => self.current_slot,
+            ActivationType::Timestamp => self.current_timestamp,
+        };
+
+        let (trade_direction, fee_config_in) = if a_to_b {
+            (TradeDirection::AtoB, self.token_a_transfer_fee.as_ref())
+        } else {
+            (TradeDirection::BtoA, self.token_b_transfer_fee.as_ref())
+        };
+        let transfer_fee_in = calculate_transfer_fee(fee_config_in, self.epoch, amount_in);
+        let actual_amount_in = amount_in.sub(transfer_fee_in);
+        let fee_mode =
+            &FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
+        let swap_result =
+            pool.get_swap_result(actual_amount_in, fee_mode, trade_direction, current_point)?;
+        Ok(QuoteResult {
+            amount_in,
+            amount_out: swap_result.output_amount,
+            lp_fee: swap_result.lp_fee,
+            protocol_fee: swap_result.protocol_fee,
+            creator_fee: swap_result.partner_fee,
+            transfer_fee_in,
+            start_price: sqrt_x64_price(pool.sqrt_price, a_to_b),
+            end_price: sqrt_x64_price(swap_result.next_sqrt_price, a_to_b),
+            ..Default::default()
+        })
+    }
+
+    fn get_internal_quote_exact_out(
+        &self,
+        amount_out: u64,
+        a_to_b: bool,
+        has_referral: bool,
+    ) -> Result<QuoteResult> {
+        ensure!(amount_out > 0, "amount is zero");
+        let pool = &self.pool;
+        let activation_type =
+            ActivationType::try_from(pool.activation_type).context("invalid activation type")?;
+
+        let current_point = match activation_type {
+            ActivationType::Slot => self.current_slot,
+            ActivationType::Timestamp => self.current_timestamp,
+        };
+
+        let (trade_direction, fee_config_in) = if a_to_b {
+            (TradeDirection::AtoB, self.token_a_transfer_fee.as_ref())
+        } else {
+            (TradeDirection::BtoA, self.token_b_transfer_fee.as_ref())
+        };
+        let fee_mode =
+            &FeeMode::get_fee_mode(pool.collect_fee_mode, trade_direction, has_referral)?;
+        let swap_result = pool.get_swap_result_from_exact_output(
+            amount_out,
+            fee_mode,
+            trade_direction,
+            current_point,
+        )?;
+        let transfer_fee_in =
+            calculate_inverse_transfer_fee(fee_config_in, self.epoch, swap_result.input_amount);
+        Ok(QuoteResult {
+            amount_in: swap_result
+                .input_amount
+                .checked_add(transfer_fee_in)
+                .context(MathOverflow)?,
+            amount_out,
+            lp_fee: swap_result.lp_fee,
+            transfer_fee_in,
+            start_price: sqrt_x64_price(pool.sqrt_price, a_to_b),
+            end_price: sqrt_x64_price(swap_result.next_sqrt_price, a_to_b),
+            ..Default::default()
+        })
+    }
+}
+
+impl PreparedQuote for MeteoraDAMMV2PreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        self.get_internal_quote(amount_in, swap_direction, true)
+            .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))
+    }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.get_internal_quote_exact_out(amount_out, swap_direction, true)
+            .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::meteora_damm_v2::constants::{MAX_SQRT_PRICE, MIN_SQRT_PRICE};
+    use crate::dex::meteora_damm_v2::quote::MeteoraDAMMV2PreparedQuote;
+    use crate::dex::meteora_damm_v2::state::fee::{BaseFeeStruct, DynamicFeeStruct};
+    use crate::dex::meteora_damm_v2::state::pool::Pool;
+    use crate::dex::quoter::{assert_exact_out_round_trip, PreparedQuote};
+    use crate::dex::QuoteError;
+    use solana_sdk::pubkey::Pubkey;
+
+    /// 全价格区间，价格为1，两边各约1e12的虚拟储备，0.25%手续费
+    fn prepared_quote(collect_fee_mode: u8) -> MeteoraDAMMV2PreparedQuote {
+        MeteoraDAMMV2PreparedQuote {
+            pool: Pool {
+                base_fee: BaseFeeStruct {
+                    cliff_fee_numerator: 2_500_000,
+                    fee_scheduler_mode: 0,
+                    number_of_period: 0,
+                    period_frequency: 0,
+                    reduction_factor: 0,
+                },
+                dynamic_fee: DynamicFeeStruct {
+                    initialized: 0,
+                    variable_fee_control: 0,
+                    bin_step: 0,
+                    filter_period: 0,
+                    decay_period: 0,
+                    reduction_factor: 0,
+                    last_update_timestamp: 0,
+                    sqrt_price_reference: 0,
+                    volatility_accumulator: 0,
+                    volatility_reference: 0,
+                },
+                token_a_mint: Pubkey::new_unique(),
+                token_b_mint: Pubkey::new_unique(),
+                token_a_vault: Pubkey::new_unique(),
+                token_b_vault: Pubkey::new_unique(),
+                liquidity: 1_000_000_000_000u128 << 64,
+                sqrt_min_price: MIN_SQRT_PRICE,
+                sqrt_max_price: MAX_SQRT_PRICE,
+                sqrt_price: 1 << 64,
+                activation_point: 0,
+                activation_type: 0,
+                pool_status: 0,
+                token_a_flag: 0,
+                token_b_flag: 0,
+                collect_fee_mode,
+            },
+            current_timestamp: 1_700_000_000,
+            current_slot: 300_000_000,
+            epoch: 700,
+            token_a_transfer_fee: None,
+            token_b_transfer_fee: None,
+        }
+    }
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        // 0: 两边都收手续费(输出端)，1: 只收token b
+        for prepared in [prepared_quote(0), prepared_quote(1)] {
+            for swap_direction in [true, false] {
+                assert_exact_out_round_trip(
+                    &prepared,
+                    swap_direction,
+                    &[1, 999, 1_000_000, 1_000_000_000, 100_000_000_000],
+                );
+            }
+        }
+        assert_eq!(
+            prepared_quote(0).quote_exact_out(0, true).unwrap_err(),
+            QuoteError::InvalidAmount
+        );
+    }
 }
@@ -19,13 +19,13 @@ impl SnapshotInitializer for MeteoraDAMMV2SnapshotLoader {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| &json.owner == DexType::MeteoraDAMMV2.get_ref_program_id())
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【{}】开始初始化Snapshot...", DexType::MeteoraDAMMV2);
         let mut invalid_pool = AHashSet::with_capacity(dex_data.len());
@@ -40,7 +40,7 @@ impl SnapshotInitializer for MeteoraDAMMV2SnapshotLoader {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none()
                 || account.dynamic_slice_data.as_ref().is_none()
@@ -58,10 +58,9 @@ impl SnapshotInitializer for MeteoraDAMMV2SnapshotLoader {
             all_pool_account_data.len()
         );
         if dex_json.is_empty() {
-            vec![]
-        } else {
-            all_pool_account_data.into_iter().collect()
+            return Ok(vec![]);
         }
+        Ok(all_pool_account_data.into_iter().collect())
     }
 
     fn print_snapshot(&self, dex_json: &[DexJson]) -> anyhow::Result<()> {
@@ -6,6 +6,7 @@ use crate::dex::meteora_damm_v2::constants::fee::{FEE_DENOMINATOR, MAX_FEE_NUMER
 use crate::dex::meteora_damm_v2::error::PriceRangeViolation;
 use crate::dex::meteora_damm_v2::math::curve::{
     get_delta_amount_a_unsigned, get_delta_amount_b_unsigned, get_next_sqrt_price_from_input,
+    get_next_sqrt_price_from_output,
 };
 use crate::dex::meteora_damm_v2::math::safe_math::SafeMath;
 use crate::dex::meteora_damm_v2::math::u128x128_math::Rounding;
@@ -230,6 +231,77 @@ impl Pool {
             referral_fee: actual_referral_fee,
         })
     }
+    /// 指定输出数量，返回需要的输入数量(含手续费)
+    pub fn get_swap_result_from_exact_output(
+        &self,
+        amount_out: u64,
+        fee_mode: &FeeMode,
+        trade_direction: TradeDirection,
+        current_point: u64,
+    ) -> Result<ExactOutSwapResult> {
+        let included_fee_amount_out = if fee_mode.fees_on_input {
+            amount_out
+        } else {
+            self.get_included_fee_amount(amount_out, current_point, self.activation_point)?
+        };
+
+        let (amount_in, next_sqrt_price) = match trade_direction {
+            TradeDirection::AtoB => self.get_input_from_a_to_b(included_fee_amount_out),
+            TradeDirection::BtoA => self.get_input_from_b_to_a(included_fee_amount_out),
+        }?;
+
+        let (input_amount, lp_fee) = if fee_mode.fees_on_input {
+            let included_fee_amount_in =
+                self.get_included_fee_amount(amount_in, current_point, self.activation_point)?;
+            (
+                included_fee_amount_in,
+                included_fee_amount_in.safe_sub(amount_in)?,
+            )
+        } else {
+            (amount_in, included_fee_amount_out.safe_sub(amount_out)?)
+        };
+
+        Ok(ExactOutSwapResult {
+            input_amount,
+            next_sqrt_price,
+            lp_fee,
+        })
+    }
+
+    fn get_input_from_a_to_b(&self, amount_out: u64) -> Result<(u64, u128)> {
+        let next_sqrt_price =
+            get_next_sqrt_price_from_output(self.sqrt_price, self.liquidity, amount_out, true)?;
+
+        if next_sqrt_price < self.sqrt_min_price {
+            return Err(anyhow!(PriceRangeViolation));
+        }
+
+        let input_amount = get_delta_amount_a_unsigned(
+            next_sqrt_price,
+            self.sqrt_price,
+            self.liquidity,
+            Rounding::Up,
+        )?;
+        Ok((input_amount, next_sqrt_price))
+    }
+
+    fn get_input_from_b_to_a(&self, amount_out: u64) -> Result<(u64, u128)> {
+        let next_sqrt_price =
+            get_next_sqrt_price_from_output(self.sqrt_price, self.liquidity, amount_out, false)?;
+
+        if next_sqrt_price > self.sqrt_max_price {
+            return Err(anyhow!(PriceRangeViolation));
+        }
+
+        let input_amount = get_delta_amount_b_unsigned(
+            self.sqrt_price,
+            next_sqrt_price,
+            self.liquidity,
+            Rounding::Up,
+        )?;
+        Ok((input_amount, next_sqrt_price))
+    }
+
     fn get_swap_result_from_a_to_b(&self, amount_in: u64) -> Result<SwapAmount> {
         // finding new target price
         let next_sqrt_price =
@@ -314,6 +386,28 @@ impl Pool {
 
         Ok(FeeOnAmountResult { amount, lp_fee })
     }
+
+    /// 扣除手续费后剩余不少于amount的最小数量
+    pub fn get_included_fee_amount(
+        &self,
+        amount: u64,
+        current_point: u64,
+        activation_point: u64,
+    ) -> Result<u64> {
+        let trade_fee_numerator = self.get_total_trading_fee(current_point, activation_point)?;
+        let trade_fee_numerator = if trade_fee_numerator > MAX_FEE_NUMERATOR as u128 {
+            MAX_FEE_NUMERATOR
+        } else {
+            trade_fee_numerator.try_into().unwrap()
+        };
+        // 手续费向上取整，amount - ceil(amount * n / d) >= x 等价于 amount >= x * d / (d - n)
+        safe_mul_div_cast_u64(
+            amount,
+            FEE_DENOMINATOR,
+            FEE_DENOMINATOR.safe_sub(trade_fee_numerator)?,
+            Rounding::Up,
+        )
+    }
 }
 
 /// Encodes all results of swapping
@@ -327,6 +421,14 @@ pub struct SwapResult {
     pub referral_fee: u64,
 }
 
+/// exact out的计算结果
+#[derive(Debug, PartialEq)]
+pub struct ExactOutSwapResult {
+    pub input_amount: u64,
+    pub next_sqrt_price: u128,
+    pub lp_fee: u64,
+}
+
 pub struct SwapAmount {
     output_amount: u64,
     next_sqrt_price: u128,
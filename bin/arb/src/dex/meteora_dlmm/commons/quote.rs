@@ -1,10 +1,12 @@
 use crate::dex::meteora_dlmm::commons::pda::derive_bin_array_pda;
-use crate::dex::meteora_dlmm::commons::token_2022::calculate_transfer_fee_excluded_amount;
+use crate::dex::meteora_dlmm::commons::token_2022::{
+    calculate_transfer_fee_excluded_amount, calculate_transfer_fee_included_amount,
+};
 use crate::dex::meteora_dlmm::commons::typedefs::SwapResult;
 use crate::dex::meteora_dlmm::extensions::BinArrayBitmapExtExtension;
 use crate::dex::meteora_dlmm::extensions::BinArrayExtension;
 use crate::dex::meteora_dlmm::extensions::BinExtension;
-use crate::dex::meteora_dlmm::interface::{ActivationType, PairStatus, PairType};
+use crate::dex::meteora_dlmm::interface::{ActivationType, Bin, PairStatus, PairType};
 use crate::dex::meteora_dlmm::lb_pair::LbPairExtension;
 use crate::dex::{BinArray, BinArrayBitmapExtension, LbPair};
 use anyhow::Result;
@@ -20,15 +22,35 @@ use std::ops::Deref;
 pub struct SwapExactInQuote {
     pub amount_out: u64,
     pub fee: u64,
+    /// fee中的协议手续费部分
+    pub protocol_fee: u64,
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+    /// swap结束时的active_id
+    pub end_active_id: i32,
+    /// 发生交换的bin数量
+    pub bins_crossed: u32,
+    /// 实际用到的bin_array数量(传入bin_arrays的前n个)
+    pub bin_arrays_consumed: usize,
 }
 
 #[derive(Debug)]
 pub struct SwapExactOutQuote {
     pub amount_in: u64,
     pub fee: u64,
+    /// fee中的协议手续费部分
+    pub protocol_fee: u64,
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+    /// swap结束时的active_id
+    pub end_active_id: i32,
+    /// 发生交换的bin数量
+    pub bins_crossed: u32,
+    /// 实际用到的bin_array数量(传入bin_arrays的前n个)
+    pub bin_arrays_consumed: usize,
 }
 
-fn validate_swap_activation(
+pub fn validate_swap_activation(
     lb_pair: &LbPair,
     current_timestamp: u64,
     current_slot: u64,
@@ -76,6 +98,8 @@ pub fn quote_exact_in(
 
     let mut total_amount_out: u64 = 0;
     let mut total_fee: u64 = 0;
+    let mut total_protocol_fee: u64 = 0;
+    let mut bins_crossed: u32 = 0;
 
     let (in_mint_transfer_fee_config, out_mint_transfer_fee_config) = if swap_for_y {
         (mint_x_account.as_ref(), mint_y_account.as_ref())
@@ -84,10 +108,9 @@ pub fn quote_exact_in(
     };
 
     let transfer_fee_excluded_amount_in =
-        calculate_transfer_fee_excluded_amount(in_mint_transfer_fee_config, amount_in, epoch)?
-            .amount;
+        calculate_transfer_fee_excluded_amount(in_mint_transfer_fee_config, amount_in, epoch)?;
 
-    let mut amount_left = transfer_fee_excluded_amount_in;
+    let mut amount_left = transfer_fee_excluded_amount_in.amount;
     let mut loop_count = 0;
     // 循环swap，直到amount_in交换完
     while amount_left > 0 {
@@ -114,6 +137,7 @@ pub fn quote_exact_in(
                     amount_in_with_fees,
                     amount_out,
                     fee,
+                    protocol_fee_after_host_fee,
                     ..
                 } = active_bin.swap(amount_left, price, swap_for_y, &lb_pair, None)?;
 
@@ -125,6 +149,10 @@ pub fn quote_exact_in(
                     .checked_add(amount_out)
                     .context("MathOverflow")?;
                 total_fee = total_fee.checked_add(fee).context("MathOverflow")?;
+                total_protocol_fee = total_protocol_fee
+                    .checked_add(protocol_fee_after_host_fee)
+                    .context("MathOverflow")?;
+                bins_crossed += 1;
             }
             if amount_left > 0 {
                 lb_pair.advance_active_bin(swap_for_y)?;
@@ -136,12 +164,122 @@ pub fn quote_exact_in(
         out_mint_transfer_fee_config,
         total_amount_out,
         epoch,
-    )?
-    .amount;
+    )?;
 
     Ok(SwapExactInQuote {
-        amount_out: transfer_fee_excluded_amount_out,
+        amount_out: transfer_fee_excluded_amount_out.amount,
+        fee: total_fee,
+        protocol_fee: total_protocol_fee,
+        transfer_fee_in: transfer_fee_excluded_amount_in.transfer_fee,
+        transfer_fee_out: transfer_fee_excluded_amount_out.transfer_fee,
+        end_active_id: lb_pair.active_id,
+        bins_crossed,
+        bin_arrays_consumed: loop_count,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn quote_exact_out(
+    lb_pair: LbPair,
+    amount_out: u64,
+    swap_for_y: bool,
+    mut bin_arrays: VecDeque<BinArray>,
+    clock: Clock,
+    mint_x_account: Option<TransferFeeConfig>,
+    mint_y_account: Option<TransferFeeConfig>,
+) -> Result<SwapExactOutQuote> {
+    let current_timestamp = clock.unix_timestamp as u64;
+    let current_slot = clock.slot;
+    let epoch = clock.epoch;
+    // 池子状态判断
+    validate_swap_activation(&lb_pair, current_timestamp, current_slot)?;
+
+    let mut lb_pair = lb_pair;
+    lb_pair.update_references(current_timestamp as i64)?;
+
+    let mut total_amount_in: u64 = 0;
+    let mut total_fee: u64 = 0;
+    let mut total_protocol_fee: u64 = 0;
+    let mut bins_crossed: u32 = 0;
+    let mut bin_arrays_consumed = 0;
+
+    let (in_mint_transfer_fee_config, out_mint_transfer_fee_config) = if swap_for_y {
+        (mint_x_account.as_ref(), mint_y_account.as_ref())
+    } else {
+        (mint_y_account.as_ref(), mint_x_account.as_ref())
+    };
+
+    // 池子需要转出的数量(包含transfer fee)
+    let transfer_fee_included_amount_out =
+        calculate_transfer_fee_included_amount(out_mint_transfer_fee_config, amount_out, epoch)?;
+    let mut amount_out_left = transfer_fee_included_amount_out.amount;
+    // 循环swap，直到amount_out全部换出
+    while amount_out_left > 0 {
+        bin_arrays_consumed += 1;
+        let mut active_bin_array = bin_arrays
+            .pop_front()
+            .context("Active bin array not found")?;
+
+        loop {
+            if !active_bin_array.is_bin_id_within_range(lb_pair.active_id)? || amount_out_left == 0
+            {
+                break;
+            }
+
+            lb_pair.update_volatility_accumulator()?;
+            let active_bin = active_bin_array.get_bin_mut(lb_pair.active_id)?;
+            let price = active_bin.get_or_store_bin_price(lb_pair.active_id, lb_pair.bin_step)?;
+            if !active_bin.is_empty(!swap_for_y) {
+                let bin_max_amount_out = active_bin.get_max_amount_out(swap_for_y);
+                // 当前bin的流动性全部换出
+                let (amount_in, amount_out) = if amount_out_left >= bin_max_amount_out {
+                    (
+                        active_bin.get_max_amount_in(price, swap_for_y)?,
+                        bin_max_amount_out,
+                    )
+                } else {
+                    (
+                        Bin::get_amount_in(amount_out_left, price, swap_for_y)?,
+                        amount_out_left,
+                    )
+                };
+                let fee = lb_pair.compute_fee(amount_in)?;
+                total_amount_in = total_amount_in
+                    .checked_add(amount_in)
+                    .context("MathOverflow")?;
+                total_fee = total_fee.checked_add(fee).context("MathOverflow")?;
+                total_protocol_fee = total_protocol_fee
+                    .checked_add(lb_pair.compute_protocol_fee(fee)?)
+                    .context("MathOverflow")?;
+                amount_out_left = amount_out_left
+                    .checked_sub(amount_out)
+                    .context("MathOverflow")?;
+                bins_crossed += 1;
+            }
+            if amount_out_left > 0 {
+                lb_pair.advance_active_bin(swap_for_y)?;
+            }
+        }
+    }
+
+    let total_amount_in = total_amount_in
+        .checked_add(total_fee)
+        .context("MathOverflow")?;
+    let transfer_fee_included_amount_in = calculate_transfer_fee_included_amount(
+        in_mint_transfer_fee_config,
+        total_amount_in,
+        epoch,
+    )?;
+
+    Ok(SwapExactOutQuote {
+        amount_in: transfer_fee_included_amount_in.amount,
         fee: total_fee,
+        protocol_fee: total_protocol_fee,
+        transfer_fee_in: transfer_fee_included_amount_in.transfer_fee,
+        transfer_fee_out: transfer_fee_included_amount_out.transfer_fee,
+        end_active_id: lb_pair.active_id,
+        bins_crossed,
+        bin_arrays_consumed,
     })
 }
 
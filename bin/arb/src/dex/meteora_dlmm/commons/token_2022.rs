@@ -45,6 +45,46 @@ pub struct TransferFeeIncludedAmount {
     pub transfer_fee: u64,
 }
 
+pub fn calculate_transfer_fee_included_amount(
+    mint_transfer_fee_config: Option<&TransferFeeConfig>,
+    transfer_fee_excluded_amount: u64,
+    epoch: u64,
+) -> Result<TransferFeeIncludedAmount> {
+    if transfer_fee_excluded_amount == 0 {
+        return Ok(TransferFeeIncludedAmount {
+            amount: 0,
+            transfer_fee: 0,
+        });
+    }
+    if let Some(transfer_fee_config) = mint_transfer_fee_config {
+        let epoch_transfer_fee = transfer_fee_config.get_epoch_fee(epoch);
+        let transfer_fee: u64 =
+            if u16::from(epoch_transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
+                u64::from(epoch_transfer_fee.maximum_fee)
+            } else {
+                calculate_inverse_fee(epoch_transfer_fee, transfer_fee_excluded_amount)
+                    .context("MathOverflow")?
+            };
+        let transfer_fee_included_amount = transfer_fee_excluded_amount
+            .checked_add(transfer_fee)
+            .context("MathOverflow")?;
+
+        return Ok(TransferFeeIncludedAmount {
+            amount: transfer_fee_included_amount,
+            transfer_fee,
+        });
+    }
+    Ok(TransferFeeIncludedAmount {
+        amount: transfer_fee_excluded_amount,
+        transfer_fee: 0,
+    })
+}
+
+pub fn calculate_inverse_fee(transfer_fee: &TransferFee, post_fee_amount: u64) -> Option<u64> {
+    let pre_fee_amount = calculate_pre_fee_amount(transfer_fee, post_fee_amount)?;
+    transfer_fee.calculate_fee(pre_fee_amount)
+}
+
 pub fn calculate_pre_fee_amount(transfer_fee: &TransferFee, post_fee_amount: u64) -> Option<u64> {
     if post_fee_amount == 0 {
         return Some(0);
@@ -2,7 +2,7 @@ use crate::dex::meteora_dlmm::commons::get_bin_array_pubkeys_for_swap;
 use crate::dex::meteora_dlmm::{METEORA_DLMM_EVENT_AUTHORITY_PROGRAM_ID, METEORA_DLMM_PROGRAM_ID};
 use crate::dex::swap_instruction::{InstructionMaterial, InstructionMaterialConverter};
 use crate::dex::DexType::MeteoraDLMM;
-use crate::dex::{BinArrayBitmapExtension, LbPair, ATA_PROGRAM_ID};
+use crate::dex::{BinArrayBitmapExtension, LbPair, QuoteResult, ATA_PROGRAM_ID};
 use crate::dex::global_cache::{get_alt, get_token_program};
 use crate::metadata::{get_keypair, MintAtaPair};
 use anyhow::Result;
@@ -17,6 +17,7 @@ impl InstructionMaterialConverter for MeteoraDLMMInstructionMaterialConverter {
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        quote_result: Option<&QuoteResult>,
     ) -> Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let lb_pair = crate::dex::global_cache::get_account_data::<LbPair>(pool_id).unwrap();
@@ -88,14 +89,17 @@ impl InstructionMaterialConverter for MeteoraDLMMInstructionMaterialConverter {
         ));
         // 15.program
         accounts.push(AccountMeta::new_readonly(METEORA_DLMM_PROGRAM_ID, false));
-        // 16~~.current bin array
-        let bin_arrays = get_bin_array_pubkeys_for_swap(
-            &pool_id,
-            &lb_pair,
-            bitmap_extension.as_ref(),
-            swap_direction,
-            3,
-        )?;
+        // 16~~.current bin array，优先使用quote实际遍历的bin array
+        let bin_arrays = match quote_result.filter(|quote| !quote.tick_arrays.is_empty()) {
+            Some(quote) => quote.tick_arrays.clone(),
+            None => get_bin_array_pubkeys_for_swap(
+                &pool_id,
+                &lb_pair,
+                bitmap_extension.as_ref(),
+                swap_direction,
+                3,
+            )?,
+        };
         accounts.extend(
             bin_arrays
                 .into_iter()
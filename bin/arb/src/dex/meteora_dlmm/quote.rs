@@ -1,48 +1,186 @@
-use crate::dex::global_cache::{get_account_data, get_token2022_data};
-use crate::dex::meteora_dlmm::commons::{get_bin_array_pubkeys_for_swap, quote_exact_in};
+use crate::dex::global_cache::{get_account_data, get_transfer_fee_config};
+use crate::dex::meteora_dlmm::commons::{
+    get_bin_array_pubkeys_for_swap, quote_exact_in, quote_exact_out, validate_swap_activation,
+    FEE_PRECISION,
+};
 use crate::dex::meteora_dlmm::lb_pair::LbPairExtension;
-use crate::dex::quoter::{QuoteResult, Quoter};
+use crate::dex::meteora_dlmm::math::get_price_from_id;
+use crate::dex::quoter::{
+    tick_array_depth, x64_price, PreparedQuote, PreparedQuoteType, QuoteResult, Quoter,
+};
 use crate::dex::raydium_clmm::state::TickArrayState;
-use crate::dex::{BinArray, BinArrayBitmapExtension, LbPair};
+use crate::dex::{BinArray, BinArrayBitmapExtension, LbPair, QuoteError};
+use solana_sdk::clock::Clock;
 use solana_sdk::pubkey::Pubkey;
 use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
 use std::array;
 use std::collections::{HashMap, VecDeque};
+use std::sync::OnceLock;
 use tracing::error;
 
+/// commons中quote返回的错误，Active bin array not found : bin array用完仍未完成swap
+const SWAP_ERRORS: &[(&str, QuoteError)] = &[
+    ("Pair is disabled", QuoteError::PoolDisabled),
+    ("Active bin array not found", QuoteError::MissingTickArray),
+    ("Insufficient liquidity", QuoteError::InsufficientLiquidity),
+    ("MathOverflow", QuoteError::MathOverflow),
+];
+
 #[derive(Debug)]
 pub struct MeteoraDLMMQuoter;
 
 impl Quoter for MeteoraDLMMQuoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.prepare(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        let pool = get_account_data::<LbPair>(pool_id).ok_or(QuoteError::MissingCache)?;
+        let clock = crate::dex::global_cache::get_clock().ok_or(QuoteError::MissingClock)?;
+        validate_pool(&pool, &clock)?;
+        Ok(PreparedQuoteType::from(MeteoraDLMMPreparedQuote {
+            pool_id: *pool_id,
+            bitmap_extension: get_bitmap_extension(pool_id),
+            token_transfer_configs: get_token_transfer_config(&pool),
+            clock,
+            pool,
+            bin_arrays: Default::default(),
+        }))
+    }
+
+    fn marginal_price(&self, swap_direction: bool, pool_id: &Pubkey) -> Option<f64> {
         let pool = get_account_data::<LbPair>(pool_id)?;
-        let bitmap_extension = get_bitmap_extension(pool_id);
-        let token_transfer_configs = get_token_transfer_config(&pool);
-        match get_bin_arrays(pool_id, &pool, bitmap_extension.as_ref(), swap_direction, 3) {
-            None => None,
-            Some(bin_arrays) => {
-                match quote_exact_in(
-                    pool,
-                    amount_in,
+        // 可变手续费随波动累加器衰减，只用基础手续费不会低估汇率
+        let base_fee = pool.get_base_fee().ok()?;
+        Some(
+            bin_price(pool.active_id, pool.bin_step, swap_direction)?
+                * (1.0 - base_fee as f64 / FEE_PRECISION as f64),
+        )
+    }
+}
+
+/// 池子、Clock和transfer fee配置，bin array按方向在第一次quote时读取
+pub struct MeteoraDLMMPreparedQuote {
+    pool_id: Pubkey,
+    pool: LbPair,
+    bitmap_extension: Option<BinArrayBitmapExtension>,
+    token_transfer_configs: [Option<TransferFeeConfig>; 2],
+    clock: Clock,
+    // 下标为swap_direction
+    bin_arrays: [OnceLock<Option<(Vec<Pubkey>, VecDeque<BinArray>)>>; 2],
+}
+
+impl MeteoraDLMMPreparedQuote {
+    fn bin_arrays(
+        &self,
+        swap_direction: bool,
+    ) -> Result<&(Vec<Pubkey>, VecDeque<BinArray>), QuoteError> {
+        self.bin_arrays[usize::from(swap_direction)]
+            .get_or_init(|| {
+                get_bin_arrays(
+                    &self.pool_id,
+                    &self.pool,
+                    self.bitmap_extension.as_ref(),
                     swap_direction,
-                    bin_arrays,
-                    crate::dex::global_cache::get_clock()?,
-                    token_transfer_configs[0],
-                    token_transfer_configs[1],
-                ) {
-                    Ok(quote) => Some(QuoteResult {
-                        amount_out: quote.amount_out,
-                    }),
-                    Err(_e) => {
-                        // error!("【MeteoraDLMM】[{pool_id}]Quote失败，原因：{}", e);
-                        None
-                    }
-                }
-            }
-        }
+                )
+            })
+            .as_ref()
+            .ok_or(QuoteError::MissingTickArray)
     }
 }
 
+impl PreparedQuote for MeteoraDLMMPreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        let (bin_array_keys, bin_arrays) = self.bin_arrays(swap_direction)?;
+        // swap会消耗bin array，每次quote使用副本
+        let quote = quote_exact_in(
+            self.pool.clone(),
+            amount_in,
+            swap_direction,
+            bin_arrays.clone(),
+            self.clock.clone(),
+            self.token_transfer_configs[0],
+            self.token_transfer_configs[1],
+        )
+        .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))?;
+        Ok(QuoteResult {
+            amount_in,
+            amount_out: quote.amount_out,
+            lp_fee: quote.fee - quote.protocol_fee,
+            protocol_fee: quote.protocol_fee,
+            transfer_fee_in: quote.transfer_fee_in,
+            transfer_fee_out: quote.transfer_fee_out,
+            start_price: bin_price(self.pool.active_id, self.pool.bin_step, swap_direction)
+                .ok_or(QuoteError::MathOverflow)?,
+            end_price: bin_price(quote.end_active_id, self.pool.bin_step, swap_direction)
+                .ok_or(QuoteError::MathOverflow)?,
+            crossed: quote.bins_crossed,
+            tick_arrays: bin_array_keys
+                .iter()
+                .take(quote.bin_arrays_consumed)
+                .copied()
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        let (bin_array_keys, bin_arrays) = self.bin_arrays(swap_direction)?;
+        let quote = quote_exact_out(
+            self.pool.clone(),
+            amount_out,
+            swap_direction,
+            bin_arrays.clone(),
+            self.clock.clone(),
+            self.token_transfer_configs[0],
+            self.token_transfer_configs[1],
+        )
+        .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))?;
+        Ok(QuoteResult {
+            amount_in: quote.amount_in,
+            amount_out,
+            lp_fee: quote.fee - quote.protocol_fee,
+            protocol_fee: quote.protocol_fee,
+            transfer_fee_in: quote.transfer_fee_in,
+            transfer_fee_out: quote.transfer_fee_out,
+            start_price: bin_price(self.pool.active_id, self.pool.bin_step, swap_direction)
+                .ok_or(QuoteError::MathOverflow)?,
+            end_price: bin_price(quote.end_active_id, self.pool.bin_step, swap_direction)
+                .ok_or(QuoteError::MathOverflow)?,
+            crossed: quote.bins_crossed,
+            tick_arrays: bin_array_keys
+                .iter()
+                .take(quote.bin_arrays_consumed)
+                .copied()
+                .collect(),
+            ..Default::default()
+        })
+    }
+}
+
+/// 在加载bin array之前排除被禁用或未到激活时间的池子
+fn validate_pool(pool: &LbPair, clock: &Clock) -> Result<(), QuoteError> {
+    validate_swap_activation(pool, clock.unix_timestamp as u64, clock.slot)
+        .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))
+}
+
+/// bin的价格(token_y/token_x)转换为 输出Mint/输入Mint
+fn bin_price(active_id: i32, bin_step: u16, swap_for_y: bool) -> Option<f64> {
+    Some(x64_price(
+        get_price_from_id(active_id, bin_step).ok()?,
+        swap_for_y,
+    ))
+}
+
 fn get_bitmap_extension(pool_id: &Pubkey) -> Option<BinArrayBitmapExtension> {
     get_account_data::<BinArrayBitmapExtension>(
         &crate::dex::meteora_dlmm::commons::derive_bin_array_bitmap_extension(pool_id),
@@ -51,45 +189,127 @@ fn get_bitmap_extension(pool_id: &Pubkey) -> Option<BinArrayBitmapExtension> {
 
 fn get_token_transfer_config(pool: &LbPair) -> [Option<TransferFeeConfig>; 2] {
     [
-        get_token2022_data(&pool.token_x_mint),
-        get_token2022_data(&pool.token_y_mint),
+        get_transfer_fee_config(&pool.token_x_mint),
+        get_transfer_fee_config(&pool.token_y_mint),
     ]
 }
 
+/// 沿swap方向加载有流动性的bin array，数量上限见 TickArrayDepth
+///
+/// 只取缓存中连续存在的部分，流动性是否足够由quote判断
 fn get_bin_arrays(
     lb_pair_pubkey: &Pubkey,
     lb_pair: &LbPair,
     bitmap_extension: Option<&BinArrayBitmapExtension>,
     swap_for_y: bool,
-    take_count: u8,
-) -> Option<VecDeque<BinArray>> {
+) -> Option<(Vec<Pubkey>, VecDeque<BinArray>)> {
     match get_bin_array_pubkeys_for_swap(
         lb_pair_pubkey,
         lb_pair,
         bitmap_extension,
         swap_for_y,
-        take_count,
+        tick_array_depth().meteora_dlmm,
     ) {
-        Ok(keys) => {
-            let expect_count = keys.len();
-            let bin_array_map = keys
-                .into_iter()
-                .filter_map(|key| {
-                    let bin_array = crate::dex::global_cache::get_account_data::<BinArray>(&key);
-                    if let Some(bin_array) = bin_array {
-                        Some(bin_array)
-                    } else {
-                        None
-                    }
-                })
+        Ok(mut keys) => {
+            let bin_arrays = keys
+                .iter()
+                .map_while(|key| get_account_data::<BinArray>(key))
                 .collect::<VecDeque<_>>();
-            if bin_array_map.len() != expect_count {
-                error!("转换BinArray失败");
-                None
-            } else {
-                Some(bin_array_map)
+            if bin_arrays.is_empty() {
+                if !keys.is_empty() {
+                    error!("转换BinArray失败");
+                }
+                return None;
             }
+            keys.truncate(bin_arrays.len());
+            Some((keys, bin_arrays))
         }
         Err(_) => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::dex::meteora_dlmm::interface::{Bin, StaticParameters, VariableParameters};
+    use crate::dex::meteora_dlmm::quote::MeteoraDLMMPreparedQuote;
+    use crate::dex::quoter::{assert_exact_out_round_trip, PreparedQuote};
+    use crate::dex::{BinArray, LbPair, QuoteError};
+    use solana_sdk::clock::Clock;
+    use solana_sdk::pubkey::Pubkey;
+    use std::collections::VecDeque;
+    use std::sync::OnceLock;
+
+    /// bin_step为10，active_id为35，bin array 0中每个bin两边各有1e9
+    fn prepared_quote() -> MeteoraDLMMPreparedQuote {
+        let pool_id = Pubkey::new_unique();
+        let bin_array = BinArray {
+            index: 0,
+            lb_pair: pool_id,
+            bins: [Bin {
+                amount_x: 1_000_000_000,
+                amount_y: 1_000_000_000,
+                price: 0,
+            }; 70],
+        };
+        let bin_arrays = [OnceLock::new(), OnceLock::new()];
+        for direction in bin_arrays.iter() {
+            direction
+                .set(Some((vec![pool_id], VecDeque::from([bin_array.clone()]))))
+                .unwrap();
+        }
+        MeteoraDLMMPreparedQuote {
+            pool_id,
+            pool: LbPair {
+                parameters: StaticParameters {
+                    base_factor: 10_000,
+                    filter_period: 30,
+                    decay_period: 600,
+                    reduction_factor: 5_000,
+                    variable_fee_control: 40_000,
+                    max_volatility_accumulator: 350_000,
+                    protocol_share: 500,
+                    base_fee_power_factor: 0,
+                },
+                pair_type: 0,
+                bin_step: 10,
+                status: 0,
+                activation_type: 0,
+                token_x_mint: Pubkey::new_unique(),
+                token_y_mint: Pubkey::new_unique(),
+                reserve_x: Pubkey::new_unique(),
+                reserve_y: Pubkey::new_unique(),
+                oracle: Pubkey::new_unique(),
+                activation_point: 0,
+                token_mint_x_program_flag: 0,
+                token_mint_y_program_flag: 0,
+                v_parameters: VariableParameters::default(),
+                active_id: 35,
+                bin_array_bitmap: [0; 16],
+            },
+            bitmap_extension: None,
+            token_transfer_configs: [None, None],
+            clock: Clock {
+                unix_timestamp: 1_700_000_000,
+                ..Default::default()
+            },
+            bin_arrays,
+        }
+    }
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        let prepared = prepared_quote();
+        for swap_direction in [true, false] {
+            assert_exact_out_round_trip(
+                &prepared,
+                swap_direction,
+                &[1, 999, 1_000_000, 1_000_000_000, 2_500_000_000],
+            );
+        }
+        // bin array中的流动性不足
+        assert_eq!(
+            prepared.quote_exact_out(100_000_000_000, true).unwrap_err(),
+            QuoteError::MissingTickArray
+        );
+    }
+}
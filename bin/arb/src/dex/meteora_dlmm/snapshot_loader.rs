@@ -21,13 +21,13 @@ impl SnapshotInitializer for MeteoraDLMMSnapshotInitializer {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| json.owner == METEORA_DLMM_PROGRAM_ID)
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【MeteoraDLMM】开始初始化Snapshot...");
         let mut invalid_pool = AHashSet::with_capacity(dex_data.len());
@@ -43,7 +43,7 @@ impl SnapshotInitializer for MeteoraDLMMSnapshotInitializer {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none()
                 || account.dynamic_slice_data.as_ref().is_none()
@@ -57,7 +57,7 @@ impl SnapshotInitializer for MeteoraDLMMSnapshotInitializer {
         // bitmap extension
         let all_bitmap_extension_account_data = self
             .get_bitmap_extension_accounts(rpc_client.clone(), &all_pool_account_data)
-            .await;
+            .await?;
         // bin array
         let all_bin_array_account_data = self
             .get_bin_array_accounts(
@@ -67,7 +67,7 @@ impl SnapshotInitializer for MeteoraDLMMSnapshotInitializer {
                 10,
                 &mut invalid_pool,
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| !invalid_pool.contains(&account.account_key));
         dex_json.retain(|json| !invalid_pool.contains(&json.pool));
         info!(
@@ -75,14 +75,13 @@ impl SnapshotInitializer for MeteoraDLMMSnapshotInitializer {
             all_pool_account_data.len()
         );
         if dex_json.is_empty() {
-            vec![]
-        } else {
-            all_pool_account_data
-                .into_iter()
-                .chain(all_bitmap_extension_account_data.into_iter())
-                .chain(all_bin_array_account_data.into_iter())
-                .collect::<Vec<_>>()
+            return Ok(vec![]);
         }
+        Ok(all_pool_account_data
+            .into_iter()
+            .chain(all_bitmap_extension_account_data.into_iter())
+            .chain(all_bin_array_account_data.into_iter())
+            .collect::<Vec<_>>())
     }
 
     fn print_snapshot(&self, dex_json: &[DexJson]) -> anyhow::Result<()> {
@@ -140,7 +139,7 @@ impl MeteoraDLMMSnapshotInitializer {
         &self,
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         // bitmap_extension
         let mut all_bitmap_extension_accounts = all_pool_account_data
             .iter()
@@ -173,7 +172,7 @@ impl MeteoraDLMMSnapshotInitializer {
         all_bitmap_extension_account_data: &[AccountDataSlice],
         load_count: u8,
         invalid_pool: &mut AHashSet<Pubkey>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let bin_array_keys = all_pool_account_data
             .iter()
             .filter_map(|account| {
@@ -242,7 +241,7 @@ impl MeteoraDLMMSnapshotInitializer {
                 AccountType::BinArray,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_bin_array_account_data.retain(|account| {
             if account.dynamic_slice_data.as_ref().is_none() {
                 invalid_pool.insert(bin_array_keys.get(&account.account_key).unwrap().clone());
@@ -251,6 +250,6 @@ impl MeteoraDLMMSnapshotInitializer {
                 true
             }
         });
-        all_bin_array_account_data
+        Ok(all_bin_array_account_data)
     }
 }
@@ -0,0 +1,335 @@
+use crate::dex::{DexType, MINT2022_PROGRAM_ID, MINT_PROGRAM_ID};
+use anyhow::anyhow;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::default_account_state::DefaultAccountState;
+use spl_token_2022::extension::interest_bearing_mint::InterestBearingConfig;
+use spl_token_2022::extension::permanent_delegate::PermanentDelegate;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::transfer_hook;
+use spl_token_2022::extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions};
+use spl_token_2022::state::{AccountState, Mint};
+
+/// transfer hook程序保存额外账户的PDA的seed
+const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+
+/// Mint的token program、精度和Token2022扩展
+#[derive(Debug, Clone)]
+pub struct MintInfo {
+    pub token_program: Pubkey,
+    pub decimals: u8,
+    pub transfer_fee: Option<TransferFeeConfig>,
+    pub transfer_hook: Option<TransferHook>,
+    // 只影响UI数量，不影响swap的数量
+    pub interest_bearing: Option<InterestBearingConfig>,
+    pub permanent_delegate: Option<Pubkey>,
+    // 无法支持的扩展
+    pub unsupported_extensions: Vec<ExtensionType>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransferHook {
+    pub program_id: Pubkey,
+    // 只有固定地址的额外账户时才能预先解析，需要按seed推导时为None
+    pub extra_account_metas: Option<Vec<AccountMeta>>,
+}
+
+impl TransferHook {
+    /// 已解析且无需签名的额外账户
+    fn resolved_extra_account_metas(&self) -> Option<&[AccountMeta]> {
+        self.extra_account_metas
+            .as_deref()
+            .filter(|metas| metas.iter().all(|meta| !meta.is_signer))
+    }
+
+    /// 转账时需要附带的账户 : 额外账户 + hook程序 + extra account meta list
+    pub fn accounts(&self, mint: &Pubkey) -> Option<Vec<AccountMeta>> {
+        let mut accounts = self.resolved_extra_account_metas()?.to_vec();
+        accounts.push(AccountMeta::new_readonly(self.program_id, false));
+        accounts.push(AccountMeta::new_readonly(
+            get_extra_account_metas_address(mint, &self.program_id),
+            false,
+        ));
+        Some(accounts)
+    }
+}
+
+impl MintInfo {
+    /// owner : Mint账户的owner，即token program
+    pub fn unpack(owner: &Pubkey, data: &[u8]) -> anyhow::Result<Self> {
+        if owner != &MINT_PROGRAM_ID && owner != &MINT2022_PROGRAM_ID {
+            return Err(anyhow!("账户owner[{}]不是token program", owner));
+        }
+        // Token的Mint与没有扩展的Token2022 Mint布局相同
+        let mint = StateWithExtensions::<Mint>::unpack(data)?;
+        let mut mint_info = Self {
+            token_program: *owner,
+            decimals: mint.base.decimals,
+            transfer_fee: None,
+            transfer_hook: None,
+            interest_bearing: None,
+            permanent_delegate: None,
+            unsupported_extensions: vec![],
+        };
+        for extension_type in mint.get_extension_types()? {
+            match extension_type {
+                ExtensionType::TransferFeeConfig => {
+                    mint_info.transfer_fee = Some(*mint.get_extension::<TransferFeeConfig>()?);
+                }
+                ExtensionType::TransferHook => {
+                    let hook = mint.get_extension::<transfer_hook::TransferHook>()?;
+                    // 未设置程序的transfer hook不影响转账
+                    mint_info.transfer_hook =
+                        Option::<Pubkey>::from(hook.program_id).map(|program_id| TransferHook {
+                            program_id,
+                            extra_account_metas: None,
+                        });
+                }
+                ExtensionType::InterestBearingConfig => {
+                    mint_info.interest_bearing =
+                        Some(*mint.get_extension::<InterestBearingConfig>()?);
+                }
+                ExtensionType::PermanentDelegate => {
+                    mint_info.permanent_delegate =
+                        Option::<Pubkey>::from(mint.get_extension::<PermanentDelegate>()?.delegate);
+                }
+                // 新建的ATA默认冻结，无法转账
+                ExtensionType::DefaultAccountState => {
+                    if mint.get_extension::<DefaultAccountState>()?.state
+                        == AccountState::Frozen as u8
+                    {
+                        mint_info.unsupported_extensions.push(extension_type);
+                    }
+                }
+                ExtensionType::Uninitialized
+                | ExtensionType::MintCloseAuthority
+                | ExtensionType::ConfidentialTransferMint
+                | ExtensionType::ConfidentialTransferFeeConfig
+                | ExtensionType::MetadataPointer
+                | ExtensionType::TokenMetadata
+                | ExtensionType::GroupPointer
+                | ExtensionType::TokenGroup
+                | ExtensionType::GroupMemberPointer
+                | ExtensionType::TokenGroupMember => {}
+                _ => mint_info.unsupported_extensions.push(extension_type),
+            }
+        }
+        Ok(mint_info)
+    }
+
+    pub fn is_token_2022(&self) -> bool {
+        self.token_program == MINT2022_PROGRAM_ID
+    }
+
+    /// 池子所在的Dex能否交易该Mint
+    pub fn check_supported(&self, dex_type: &DexType) -> anyhow::Result<()> {
+        if !self.unsupported_extensions.is_empty() {
+            return Err(anyhow!(
+                "不支持的Token2022扩展 : {:?}",
+                self.unsupported_extensions
+            ));
+        }
+        if let Some(hook) = &self.transfer_hook {
+            // 只有Orca swap_v2能通过remaining accounts携带transfer hook的额外账户
+            if dex_type != &DexType::OrcaWhirl {
+                return Err(anyhow!("{}不支持transfer hook", dex_type));
+            }
+            if hook.resolved_extra_account_metas().is_none() {
+                return Err(anyhow!(
+                    "transfer hook[{}]的额外账户未能预先解析",
+                    hook.program_id
+                ));
+            }
+        }
+        match dex_type {
+            // swap指令只传入Token program
+            DexType::RaydiumAMM | DexType::RaydiumCLMM if self.is_token_2022() => {
+                Err(anyhow!("{}不支持Token2022", dex_type))
+            }
+            // quote未计算transfer fee
+            DexType::PumpFunAMM if self.transfer_fee.is_some() => {
+                Err(anyhow!("{}不支持transfer fee", dex_type))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// transfer hook程序保存额外账户的PDA
+pub fn get_extra_account_metas_address(mint: &Pubkey, hook_program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[EXTRA_ACCOUNT_METAS_SEED, mint.as_ref()], hook_program_id).0
+}
+
+/// 解析extra account meta list账户
+///
+/// TLV(8字节discriminator + 4字节长度) + 4字节数量 + ExtraAccountMeta(35字节)的数组
+///
+/// ExtraAccountMeta : discriminator(1字节，0为固定地址) + address_config(32字节) + is_signer + is_writable
+pub fn unpack_extra_account_metas(data: &[u8]) -> anyhow::Result<Vec<AccountMeta>> {
+    const META_LEN: usize = 35;
+    let count = data
+        .get(12..16)
+        .ok_or(anyhow!("extra account meta list数据长度不足"))?;
+    let count = u32::from_le_bytes(count.try_into()?) as usize;
+    let metas = data
+        .get(16..16 + count * META_LEN)
+        .ok_or(anyhow!("extra account meta list数据长度不足"))?;
+    metas
+        .chunks_exact(META_LEN)
+        .map(|meta| {
+            if meta[0] != 0 {
+                return Err(anyhow!("额外账户需要按seed推导"));
+            }
+            Ok(AccountMeta {
+                pubkey: Pubkey::try_from(&meta[1..33])?,
+                is_signer: meta[33] != 0,
+                is_writable: meta[34] != 0,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::mint_info::{
+        get_extra_account_metas_address, unpack_extra_account_metas, MintInfo, TransferHook,
+    };
+    use crate::dex::{DexType, MINT2022_PROGRAM_ID, MINT_PROGRAM_ID};
+    use solana_sdk::instruction::AccountMeta;
+    use solana_sdk::program_pack::Pack;
+    use solana_sdk::pubkey::Pubkey;
+    use spl_token_2022::extension::non_transferable::NonTransferable;
+    use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+    use spl_token_2022::state::Mint;
+
+    fn mint_data(extension_types: &[ExtensionType]) -> anyhow::Result<Vec<u8>> {
+        let mut data = vec![0; ExtensionType::try_calculate_account_len::<Mint>(extension_types)?];
+        let mut mint = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data)?;
+        for extension_type in extension_types {
+            match extension_type {
+                ExtensionType::TransferFeeConfig => {
+                    let fee_config = mint.init_extension::<TransferFeeConfig>(true)?;
+                    fee_config.newer_transfer_fee.transfer_fee_basis_points = 100u16.into();
+                    fee_config.newer_transfer_fee.maximum_fee = 1_000u64.into();
+                }
+                ExtensionType::NonTransferable => {
+                    mint.init_extension::<NonTransferable>(true)?;
+                }
+                _ => unreachable!(),
+            }
+        }
+        mint.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        mint.pack_base();
+        mint.init_account_type()?;
+        Ok(data)
+    }
+
+    #[test]
+    fn test_mint_info_unpack() -> anyhow::Result<()> {
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals: 9,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        let mint_info = MintInfo::unpack(&MINT_PROGRAM_ID, &data)?;
+        assert_eq!(mint_info.decimals, 9);
+        assert!(!mint_info.is_token_2022());
+        assert!(mint_info.check_supported(&DexType::RaydiumAMM).is_ok());
+
+        let data = mint_data(&[ExtensionType::TransferFeeConfig])?;
+        let mint_info = MintInfo::unpack(&MINT2022_PROGRAM_ID, &data)?;
+        assert_eq!(mint_info.decimals, 6);
+        assert_eq!(
+            mint_info
+                .transfer_fee
+                .unwrap()
+                .calculate_epoch_fee(0, 10_000),
+            Some(100)
+        );
+        assert!(mint_info.check_supported(&DexType::RaydiumCPMM).is_ok());
+        assert!(mint_info.check_supported(&DexType::RaydiumCLMM).is_err());
+        assert!(mint_info.check_supported(&DexType::PumpFunAMM).is_err());
+
+        let data = mint_data(&[ExtensionType::NonTransferable])?;
+        let mint_info = MintInfo::unpack(&MINT2022_PROGRAM_ID, &data)?;
+        assert_eq!(
+            mint_info.unsupported_extensions,
+            vec![ExtensionType::NonTransferable]
+        );
+        assert!(mint_info.check_supported(&DexType::OrcaWhirl).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_transfer_hook_supported() {
+        let mint = Pubkey::new_unique();
+        let extra = Pubkey::new_unique();
+        let mut hook = TransferHook {
+            program_id: Pubkey::new_unique(),
+            extra_account_metas: None,
+        };
+        let mut mint_info = MintInfo {
+            token_program: MINT2022_PROGRAM_ID,
+            decimals: 6,
+            transfer_fee: None,
+            transfer_hook: Some(hook.clone()),
+            interest_bearing: None,
+            permanent_delegate: None,
+            unsupported_extensions: vec![],
+        };
+        // 额外账户未能解析
+        assert!(hook.accounts(&mint).is_none());
+        assert!(mint_info.check_supported(&DexType::OrcaWhirl).is_err());
+
+        hook.extra_account_metas = Some(vec![AccountMeta::new(extra, false)]);
+        mint_info.transfer_hook = Some(hook.clone());
+        assert!(mint_info.check_supported(&DexType::OrcaWhirl).is_ok());
+        assert!(mint_info.check_supported(&DexType::RaydiumCPMM).is_err());
+        assert!(mint_info.check_supported(&DexType::MeteoraDLMM).is_err());
+        let accounts = hook.accounts(&mint).unwrap();
+        assert_eq!(
+            accounts
+                .iter()
+                .map(|account| account.pubkey)
+                .collect::<Vec<_>>(),
+            vec![
+                extra,
+                hook.program_id,
+                get_extra_account_metas_address(&mint, &hook.program_id)
+            ]
+        );
+        assert!(accounts[0].is_writable && !accounts[1].is_writable);
+
+        // 需要签名的额外账户无法由路由指令提供
+        hook.extra_account_metas = Some(vec![AccountMeta::new(extra, true)]);
+        mint_info.transfer_hook = Some(hook.clone());
+        assert!(hook.accounts(&mint).is_none());
+        assert!(mint_info.check_supported(&DexType::OrcaWhirl).is_err());
+    }
+
+    #[test]
+    fn test_unpack_extra_account_metas() {
+        let key = Pubkey::new_unique();
+        let mut data = vec![0; 12];
+        data.extend(1u32.to_le_bytes());
+        data.push(0);
+        data.extend(key.to_bytes());
+        data.extend([0, 1]);
+        let metas = unpack_extra_account_metas(&data).unwrap();
+        assert_eq!(metas.len(), 1);
+        assert_eq!(metas[0].pubkey, key);
+        assert!(!metas[0].is_signer && metas[0].is_writable);
+        // 按seed推导的账户
+        data[16] = 1;
+        assert!(unpack_extra_account_metas(&data).is_err());
+    }
+}
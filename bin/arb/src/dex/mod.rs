@@ -3,35 +3,46 @@ use solana_sdk::instruction::AccountMeta;
 use solana_sdk::message::AddressLookupTableAccount;
 use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::transfer_fee::{TransferFeeConfig, MAX_FEE_BASIS_POINTS};
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 
 mod account_relation;
+mod cache_version;
 mod data_slice;
 mod global_cache;
 pub mod meteora_damm_v2;
 pub mod meteora_dlmm;
+mod mint_info;
 pub mod orca_whirlpools;
 mod pump_fun;
+mod quote_error;
 mod quoter;
 pub mod raydium_amm;
 pub mod raydium_clmm;
 pub mod raydium_cpmm;
 mod snapshot;
+mod snapshot_store;
+mod stale_state;
 mod subscriber;
 mod swap_instruction;
 mod utils;
 
 pub use account_relation::*;
+pub use cache_version::*;
 pub use data_slice::*;
 pub use global_cache::*;
 pub use meteora_dlmm::{BinArray, BinArrayBitmapExtension, LbPair};
+pub use mint_info::*;
 pub use orca_whirlpools::accounts::*;
 pub use pump_fun::state::*;
+pub use quote_error::*;
 pub use quoter::*;
 pub use raydium_amm::state::*;
 pub use raydium_clmm::state::*;
 pub use snapshot::*;
+pub use snapshot_store::*;
+pub use stale_state::*;
 pub use subscriber::*;
 pub use swap_instruction::*;
 pub use utils::read_from;
@@ -127,7 +138,16 @@ impl DexType {
 }
 
 pub(crate) fn get_transfer_fee(mint: &Pubkey, epoch: u64, pre_fee_amount: u64) -> u64 {
-    if let Some(fee_config) = get_token2022_data(mint) {
+    calculate_transfer_fee(get_transfer_fee_config(mint).as_ref(), epoch, pre_fee_amount)
+}
+
+/// 同 get_transfer_fee，使用已读取的TransferFeeConfig
+pub(crate) fn calculate_transfer_fee(
+    fee_config: Option<&TransferFeeConfig>,
+    epoch: u64,
+    pre_fee_amount: u64,
+) -> u64 {
+    if let Some(fee_config) = fee_config {
         fee_config
             .calculate_epoch_fee(epoch, pre_fee_amount)
             .unwrap()
@@ -136,6 +156,26 @@ pub(crate) fn get_transfer_fee(mint: &Pubkey, epoch: u64, pre_fee_amount: u64) -
     }
 }
 
+/// 转账后到账post_fee_amount需要额外支付的transfer fee
+pub(crate) fn calculate_inverse_transfer_fee(
+    fee_config: Option<&TransferFeeConfig>,
+    epoch: u64,
+    post_fee_amount: u64,
+) -> u64 {
+    if let Some(fee_config) = fee_config {
+        let transfer_fee = fee_config.get_epoch_fee(epoch);
+        if u16::from(transfer_fee.transfer_fee_basis_points) == MAX_FEE_BASIS_POINTS {
+            u64::from(transfer_fee.maximum_fee)
+        } else {
+            fee_config
+                .calculate_inverse_epoch_fee(epoch, post_fee_amount)
+                .unwrap()
+        }
+    } else {
+        0
+    }
+}
+
 pub trait FromCache {
     fn from_cache(
         static_cache: Option<Arc<Vec<u8>>>,
@@ -31,3 +31,5 @@ pub const TICK_INDEX_NOT_IN_ARRAY: CoreError = "Tick index not in array";
 pub const INVALID_TICK_ARRAY_SEQUENCE: CoreError = "Invalid tick array sequence";
 
 pub const INVALID_ADAPTIVE_FEE_INFO: CoreError = "Invalid adaptive fee info";
+
+pub const PARTIAL_FILL_ERROR: CoreError = "Liquidity insufficient for exact output";
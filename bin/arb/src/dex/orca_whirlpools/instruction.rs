@@ -1,11 +1,13 @@
-use crate::dex::global_cache::{get_account_data, get_alt, get_token_program};
+use crate::dex::global_cache::{
+    get_account_data, get_alt, get_token_program, get_transfer_hook_accounts,
+};
 use crate::dex::oracle::get_oracle_address;
 use crate::dex::orca_whirlpools::math::get_tick_array_start_tick_index;
 use crate::dex::swap_instruction::{InstructionMaterial, InstructionMaterialConverter};
 use crate::dex::tick_array::{get_tick_array_address, TICK_ARRAY_SIZE};
 use crate::dex::whirlpool::Whirlpool;
 use crate::dex::DexType::OrcaWhirl;
-use crate::dex::{ATA_PROGRAM_ID, MEMO_PROGRAM_V2, MINT_PROGRAM_ID};
+use crate::dex::{QuoteResult, ATA_PROGRAM_ID, MEMO_PROGRAM_V2};
 use crate::metadata::{get_keypair, MintAtaPair};
 use anyhow::anyhow;
 use solana_sdk::instruction::AccountMeta;
@@ -19,6 +21,7 @@ impl InstructionMaterialConverter for OrcaWhirlInstructionMaterialConverter {
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        quote_result: Option<&QuoteResult>,
     ) -> anyhow::Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let pool = get_account_data::<Whirlpool>(pool_id)
@@ -44,7 +47,7 @@ impl InstructionMaterialConverter for OrcaWhirlInstructionMaterialConverter {
         let (token_mint_a_ata, _) = Pubkey::find_program_address(
             &[
                 wallet.as_ref(),
-                MINT_PROGRAM_ID.as_ref(),
+                token_program_a.as_ref(),
                 pool.token_mint_a.as_ref(),
             ],
             &ATA_PROGRAM_ID,
@@ -56,7 +59,7 @@ impl InstructionMaterialConverter for OrcaWhirlInstructionMaterialConverter {
         let (token_mint_b_ata, _) = Pubkey::find_program_address(
             &[
                 wallet.as_ref(),
-                MINT_PROGRAM_ID.as_ref(),
+                token_program_b.as_ref(),
                 pool.token_mint_b.as_ref(),
             ],
             &ATA_PROGRAM_ID,
@@ -64,13 +67,27 @@ impl InstructionMaterialConverter for OrcaWhirlInstructionMaterialConverter {
         accounts.push(AccountMeta::new(token_mint_b_ata, false));
         // 11.token_vault_b
         accounts.push(AccountMeta::new(pool.token_vault_b, false));
-        // tick_arrays
-        let mut tick_array_keys = get_tick_arrays_or_default(
+        // tick_arrays，优先使用quote实际遍历的tick array，不足3个时用后续的tick array补齐
+        let mut tick_array_keys = quote_result
+            .map(|quote| quote.tick_arrays.clone())
+            .unwrap_or_default();
+        // 超过3个的部分作为supplemental tick array
+        let supplemental_tick_arrays = if tick_array_keys.len() > 3 {
+            tick_array_keys.split_off(3)
+        } else {
+            vec![]
+        };
+        for key in get_tick_arrays_or_default(
             pool_id,
             pool.tick_current_index,
             pool.tick_spacing,
             swap_direction,
-        );
+        ) {
+            if tick_array_keys.len() < 3 && !tick_array_keys.contains(&key) {
+                tick_array_keys.push(key);
+            }
+        }
+        tick_array_keys.truncate(3);
         // 12.tick_array_0
         accounts.push(AccountMeta::new(
             tick_array_keys
@@ -94,30 +111,36 @@ impl InstructionMaterialConverter for OrcaWhirlInstructionMaterialConverter {
         ));
         // 15.oracle
         accounts.push(AccountMeta::new(get_oracle_address(pool_id)?, false));
-        // 16..remaining tick_array
-        // accounts.push(AccountMeta::new(
-        //     tick_array_keys
-        //         .pop()
-        //         .map_or(Err(anyhow!("tick_array_3 pop failed")), |v| Ok(v))?,
-        //     false,
-        // ));
-        // accounts.push(AccountMeta::new(
-        //     tick_array_keys
-        //         .pop()
-        //         .map_or(Err(anyhow!("tick_array_4 pop failed")), |v| Ok(v))?,
-        //     false,
-        // ));
-        Ok(InstructionMaterial::new(
+        // 16..transfer hook a, transfer hook b
+        let transfer_hook_accounts_a = get_transfer_hook_accounts(&pool.token_mint_a)?;
+        let transfer_hook_accounts_b = get_transfer_hook_accounts(&pool.token_mint_b)?;
+        let transfer_hook_account_num = (
+            (!transfer_hook_accounts_a.is_empty()).then_some(transfer_hook_accounts_a.len() as u8),
+            (!transfer_hook_accounts_b.is_empty()).then_some(transfer_hook_accounts_b.len() as u8),
+        );
+        accounts.extend(transfer_hook_accounts_a);
+        accounts.extend(transfer_hook_accounts_b);
+        // supplemental tick_array
+        let remaining_account_num =
+            (!supplemental_tick_arrays.is_empty()).then_some(supplemental_tick_arrays.len() as u8);
+        accounts.extend(
+            supplemental_tick_arrays
+                .into_iter()
+                .map(|key| AccountMeta::new(key, false)),
+        );
+        let mut material = InstructionMaterial::new(
             OrcaWhirl,
             swap_direction,
             accounts,
-            None,
+            remaining_account_num,
             get_alt(pool_id),
             vec![
                 MintAtaPair::new(pool.token_mint_a, token_mint_a_ata),
                 MintAtaPair::new(pool.token_mint_b, token_mint_b_ata),
             ],
-        ))
+        );
+        material.transfer_hook_account_num = transfer_hook_account_num;
+        Ok(material)
     }
 }
 
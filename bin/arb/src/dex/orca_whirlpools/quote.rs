@@ -1,15 +1,25 @@
-use crate::dex::global_cache::{get_account_data, get_clock, get_token2022_data};
+use crate::dex::global_cache::{get_account_data, get_clock, get_transfer_fee_config};
 use crate::dex::oracle::{get_oracle_address, Oracle, OracleFacade};
-use crate::dex::orca_whirlpools::error::CoreError;
+use crate::dex::orca_whirlpools::error::{
+    CoreError, AMOUNT_EXCEEDS_MAX_U64, ARITHMETIC_OVERFLOW, INVALID_TICK_ARRAY_SEQUENCE,
+    PARTIAL_FILL_ERROR, SQRT_PRICE_OUT_OF_BOUNDS, TICK_ARRAY_NOT_EVENLY_SPACED,
+    TICK_INDEX_NOT_IN_ARRAY, TICK_INDEX_OUT_OF_BOUNDS, TICK_SEQUENCE_EMPTY, ZERO_TRADABLE_AMOUNT,
+};
 use crate::dex::orca_whirlpools::math::{get_tick_array_start_tick_index, TransferFee};
-use crate::dex::orca_whirlpools::swap_quote_by_input_token;
-use crate::dex::quoter::{QuoteResult, Quoter};
+use crate::dex::orca_whirlpools::{
+    swap_quote_by_input_token, swap_quote_by_output_token, FEE_RATE_DENOMINATOR,
+};
+use crate::dex::quoter::{
+    sqrt_x64_price, tick_array_depth, PreparedQuote, PreparedQuoteType, QuoteResult, Quoter,
+};
 use crate::dex::tick_array::{
-    get_tick_array_address, TickArray, TickArrayFacade, TickFacade, TICK_ARRAY_SIZE,
+    get_tick_array_address, TickArray, TickArrayFacade, TickArrays, TICK_ARRAY_SIZE,
 };
 use crate::dex::whirlpool::{Whirlpool, WhirlpoolFacade};
+use crate::dex::QuoteError;
 use solana_sdk::pubkey::Pubkey;
 use std::error::Error;
+use std::sync::OnceLock;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::error;
 
@@ -17,43 +27,164 @@ use tracing::error;
 pub struct OrcaWhirlQuoter;
 
 impl Quoter for OrcaWhirlQuoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
-        let pool = get_account_data::<Whirlpool>(pool_id)?;
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.prepare(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        let pool = WhirlpoolFacade::from(
+            get_account_data::<Whirlpool>(pool_id).ok_or(QuoteError::MissingCache)?,
+        );
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let pool = WhirlpoolFacade::from(pool);
-        let tick_arrays = get_tick_arrays_or_default(
-            pool_id,
-            pool.tick_current_index,
-            pool.tick_spacing,
-            swap_direction,
+        Ok(PreparedQuoteType::from(OrcaWhirlPreparedQuote {
+            pool_id: *pool_id,
+            oracle: get_oracle_account(pool_id, &pool),
+            timestamp,
+            transfer_fee_a: get_current_transfer_fee(&pool.token_mint_a),
+            transfer_fee_b: get_current_transfer_fee(&pool.token_mint_b),
+            pool,
+            tick_arrays: Default::default(),
+        }))
+    }
+
+    fn marginal_price(&self, swap_direction: bool, pool_id: &Pubkey) -> Option<f64> {
+        let pool = get_account_data::<Whirlpool>(pool_id)?;
+        // 自适应手续费在fee_rate之上叠加，只用fee_rate不会低估汇率
+        Some(
+            sqrt_x64_price(pool.sqrt_price, swap_direction)
+                * (1.0 - pool.fee_rate as f64 / FEE_RATE_DENOMINATOR as f64),
         )
-        .unwrap();
-        match swap_quote_by_input_token(
+    }
+}
+
+/// 池子、oracle和transfer fee，tick array按方向在第一次quote时读取
+pub struct OrcaWhirlPreparedQuote {
+    pool_id: Pubkey,
+    pool: WhirlpoolFacade,
+    oracle: Option<OracleFacade>,
+    timestamp: u64,
+    transfer_fee_a: Option<TransferFee>,
+    transfer_fee_b: Option<TransferFee>,
+    // 下标为swap_direction
+    tick_arrays: [OnceLock<Option<TickArrays>>; 2],
+}
+
+impl OrcaWhirlPreparedQuote {
+    fn tick_arrays(&self, swap_direction: bool) -> Result<&TickArrays, QuoteError> {
+        self.tick_arrays[usize::from(swap_direction)]
+            .get_or_init(|| {
+                get_tick_arrays(
+                    &self.pool_id,
+                    self.pool.tick_current_index,
+                    self.pool.tick_spacing,
+                    swap_direction,
+                )
+                .ok()
+            })
+            .as_ref()
+            .ok_or(QuoteError::MissingTickArray)
+    }
+}
+
+impl PreparedQuote for OrcaWhirlPreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        let pool = self.pool;
+        let quote_result = swap_quote_by_input_token(
             amount_in,
             swap_direction,
             pool,
-            get_oracle_account(pool_id, &pool),
-            tick_arrays.into(),
-            timestamp,
-            get_current_transfer_fee(&pool.token_mint_a),
-            get_current_transfer_fee(&pool.token_mint_b),
-        ) {
-            Ok(quote_result) => Some(QuoteResult {
-                amount_out: quote_result.token_est_out,
-            }),
-            Err(e) => {
-                // error!("【OracWhirl】[{pool_id}]Quote失败，原因：{}", e);
-                None
-            }
-        }
+            self.oracle,
+            self.tick_arrays(swap_direction)?.clone(),
+            self.timestamp,
+            self.transfer_fee_a,
+            self.transfer_fee_b,
+        )
+        .map_err(quote_error)?;
+        Ok(QuoteResult {
+            amount_in,
+            amount_out: quote_result.token_est_out,
+            // 未缓存protocol_fee_rate，trade_fee整体计入lp_fee
+            lp_fee: quote_result.trade_fee,
+            transfer_fee_in: quote_result.transfer_fee_in,
+            transfer_fee_out: quote_result.transfer_fee_out,
+            start_price: sqrt_x64_price(pool.sqrt_price, swap_direction),
+            end_price: sqrt_x64_price(quote_result.end_sqrt_price, swap_direction),
+            crossed: quote_result.ticks_crossed,
+            tick_arrays: get_touched_tick_array_keys(
+                &self.pool_id,
+                pool.tick_spacing,
+                pool.tick_current_index,
+                quote_result.end_tick_index,
+                swap_direction,
+            ),
+            ..Default::default()
+        })
+    }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        let pool = self.pool;
+        // swap_direction为a->b时输出的是token b
+        let quote_result = swap_quote_by_output_token(
+            amount_out,
+            !swap_direction,
+            pool,
+            self.oracle,
+            self.tick_arrays(swap_direction)?.clone(),
+            self.timestamp,
+            self.transfer_fee_a,
+            self.transfer_fee_b,
+        )
+        .map_err(quote_error)?;
+        Ok(QuoteResult {
+            amount_in: quote_result.token_est_in,
+            amount_out,
+            lp_fee: quote_result.trade_fee,
+            transfer_fee_in: quote_result.transfer_fee_in,
+            transfer_fee_out: quote_result.transfer_fee_out,
+            start_price: sqrt_x64_price(pool.sqrt_price, swap_direction),
+            end_price: sqrt_x64_price(quote_result.end_sqrt_price, swap_direction),
+            crossed: quote_result.ticks_crossed,
+            tick_arrays: get_touched_tick_array_keys(
+                &self.pool_id,
+                pool.tick_spacing,
+                pool.tick_current_index,
+                quote_result.end_tick_index,
+                swap_direction,
+            ),
+            ..Default::default()
+        })
+    }
+}
+
+/// swap走出已加载的tick array时返回TICK_INDEX_OUT_OF_BOUNDS等tick array相关的错误
+fn quote_error(e: CoreError) -> QuoteError {
+    match e {
+        TICK_SEQUENCE_EMPTY
+        | TICK_INDEX_OUT_OF_BOUNDS
+        | TICK_INDEX_NOT_IN_ARRAY
+        | INVALID_TICK_ARRAY_SEQUENCE
+        | TICK_ARRAY_NOT_EVENLY_SPACED => QuoteError::MissingTickArray,
+        PARTIAL_FILL_ERROR | SQRT_PRICE_OUT_OF_BOUNDS => QuoteError::InsufficientLiquidity,
+        ARITHMETIC_OVERFLOW | AMOUNT_EXCEEDS_MAX_U64 => QuoteError::MathOverflow,
+        ZERO_TRADABLE_AMOUNT => QuoteError::InvalidAmount,
+        _ => QuoteError::Other,
     }
 }
 
 fn get_current_transfer_fee(mint: &Pubkey) -> Option<TransferFee> {
-    get_token2022_data(mint).map_or(None, |transfer_fee_config| {
+    get_transfer_fee_config(mint).map_or(None, |transfer_fee_config| {
         let fee = transfer_fee_config.get_epoch_fee(get_clock().unwrap().epoch);
         Some(TransferFee {
             fee_bps: fee.transfer_fee_basis_points.into(),
@@ -73,56 +204,135 @@ fn get_oracle_account(pool_id: &Pubkey, pool: &WhirlpoolFacade) -> Option<Oracle
     }
 }
 
-fn get_tick_arrays_or_default(
+/// 从当前价格所在的tick array起沿swap方向连续的tick array，数量见 TickArrayDepth
+///
+/// 缓存中没有的tick array不伪造为空，序列在此截断，swap走出已加载的部分时返回MissingTickArray
+fn get_tick_arrays(
     whirlpool_address: &Pubkey,
     tick_current_index: i32,
     tick_spacing: u16,
     swap_direction: bool,
-) -> anyhow::Result<[TickArrayFacade; 3]> {
+) -> Result<TickArrays, QuoteError> {
     let tick_array_start_index = get_tick_array_start_tick_index(tick_current_index, tick_spacing);
     let offset = tick_spacing as i32 * TICK_ARRAY_SIZE as i32;
+    let step = if swap_direction { -offset } else { offset };
 
-    let tick_array_indexes = if swap_direction {
-        [
-            tick_array_start_index,
-            tick_array_start_index - offset,
-            tick_array_start_index - offset * 2,
-            // tick_array_start_index - offset * 3,
-            // tick_array_start_index - offset * 4,
-            // tick_array_start_index - offset * 5,
-        ]
-    } else {
-        [
-            tick_array_start_index,
-            tick_array_start_index + offset,
-            tick_array_start_index + offset * 2,
-            // tick_array_start_index + offset * 3,
-            // tick_array_start_index + offset * 4,
-            // tick_array_start_index + offset * 5,
-        ]
-    };
-
-    let tick_arrays = tick_array_indexes
-        .iter()
-        .zip(0..6)
-        .map(|(tick_index, i)| {
-            let key = get_tick_array_address(whirlpool_address, *tick_index)
-                .unwrap()
-                .0;
-            get_account_data::<TickArray>(&key).map_or(
-                uninitialized_tick_array(tick_array_indexes[i as usize]),
-                |tick_array| TickArrayFacade::from(tick_array),
-            )
-        })
-        .collect::<Vec<_>>()
-        .try_into()
-        .unwrap();
-    Ok(tick_arrays)
+    let mut tick_arrays = Vec::with_capacity(usize::from(tick_array_depth().orca_whirl));
+    for i in 0..i32::from(tick_array_depth().orca_whirl) {
+        let start_tick_index = tick_array_start_index + step * i;
+        let Ok((key, _)) = get_tick_array_address(whirlpool_address, start_tick_index) else {
+            break;
+        };
+        match get_account_data::<TickArray>(&key) {
+            Some(tick_array) => tick_arrays.push(TickArrayFacade::from(tick_array)),
+            None => break,
+        }
+    }
+    Ok(match tick_arrays[..] {
+        [a] => TickArrays::from(a),
+        [a, b] => TickArrays::from([a, b]),
+        [a, b, c] => TickArrays::from([a, b, c]),
+        [a, b, c, d] => TickArrays::from([a, b, c, d]),
+        [a, b, c, d, e] => TickArrays::from([a, b, c, d, e]),
+        [a, b, c, d, e, f] => TickArrays::from([a, b, c, d, e, f]),
+        _ => return Err(QuoteError::MissingTickArray),
+    })
 }
 
-fn uninitialized_tick_array(start_tick_index: i32) -> TickArrayFacade {
-    TickArrayFacade {
-        start_tick_index,
-        ticks: [TickFacade::default(); TICK_ARRAY_SIZE],
+/// swap从start_tick_index走到end_tick_index经过的tick array，按遍历顺序
+fn get_touched_tick_array_keys(
+    whirlpool_address: &Pubkey,
+    tick_spacing: u16,
+    start_tick_index: i32,
+    end_tick_index: i32,
+    swap_direction: bool,
+) -> Vec<Pubkey> {
+    let offset = tick_spacing as i32 * TICK_ARRAY_SIZE as i32;
+    let step = if swap_direction { -offset } else { offset };
+    let start = get_tick_array_start_tick_index(start_tick_index, tick_spacing);
+    let end = get_tick_array_start_tick_index(end_tick_index, tick_spacing);
+    // quote最多只加载了 TickArrayDepth 个tick array
+    let count = ((end - start) / step).clamp(0, i32::from(tick_array_depth().orca_whirl) - 1) + 1;
+    (0..count)
+        .filter_map(|i| get_tick_array_address(whirlpool_address, start + step * i).ok())
+        .map(|(key, _)| key)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::orca_whirlpools::math::{tick_index_to_sqrt_price, TransferFee};
+    use crate::dex::orca_whirlpools::quote::OrcaWhirlPreparedQuote;
+    use crate::dex::quoter::{assert_exact_out_round_trip, PreparedQuote};
+    use crate::dex::tick_array::{TickArrayFacade, TickArrays, TickFacade};
+    use crate::dex::whirlpool::WhirlpoolFacade;
+    use crate::dex::QuoteError;
+    use solana_sdk::pubkey::Pubkey;
+    use std::sync::OnceLock;
+
+    /// tick_spacing为1，当前tick为40，流动性区间为tick array [0, 88)中的[0, 87]
+    fn prepared_quote(transfer_fee_a: Option<TransferFee>) -> OrcaWhirlPreparedQuote {
+        let liquidity = 1_000_000_000_000u128;
+        let mut tick_array = TickArrayFacade {
+            start_tick_index: 0,
+            ticks: [TickFacade::default(); 88],
+        };
+        tick_array.ticks[0] = TickFacade {
+            initialized: true,
+            liquidity_net: liquidity as i128,
+            liquidity_gross: liquidity,
+        };
+        tick_array.ticks[87] = TickFacade {
+            initialized: true,
+            liquidity_net: -(liquidity as i128),
+            liquidity_gross: liquidity,
+        };
+        let tick_arrays = [OnceLock::new(), OnceLock::new()];
+        for direction in tick_arrays.iter() {
+            direction.set(Some(TickArrays::from(tick_array))).unwrap();
+        }
+        OrcaWhirlPreparedQuote {
+            pool_id: Pubkey::new_unique(),
+            pool: WhirlpoolFacade {
+                // fee tier与tick_spacing相同，不是自适应手续费池
+                fee_tier_index_seed: 1u16.to_le_bytes(),
+                tick_spacing: 1,
+                fee_rate: 3000,
+                liquidity,
+                sqrt_price: tick_index_to_sqrt_price(40),
+                tick_current_index: 40,
+                token_mint_a: Pubkey::new_unique(),
+                token_mint_b: Pubkey::new_unique(),
+            },
+            oracle: None,
+            timestamp: 1_700_000_000,
+            transfer_fee_a,
+            transfer_fee_b: None,
+            tick_arrays,
+        }
+    }
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        let transfer_fee = TransferFee {
+            fee_bps: 100,
+            max_fee: u64::MAX,
+        };
+        for prepared in [prepared_quote(None), prepared_quote(Some(transfer_fee))] {
+            for swap_direction in [true, false] {
+                assert_exact_out_round_trip(
+                    &prepared,
+                    swap_direction,
+                    &[999, 1_000_000, 100_000_000],
+                );
+            }
+        }
+        // 超出区间内的流动性，swap走出已加载的tick array
+        assert_eq!(
+            prepared_quote(None)
+                .quote_exact_out(1_000_000_000_000, true)
+                .unwrap_err(),
+            QuoteError::MissingTickArray
+        );
     }
 }
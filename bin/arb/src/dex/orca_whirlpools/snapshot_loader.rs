@@ -14,6 +14,7 @@ use tracing::info;
 use crate::dex::oracle::{get_oracle_address, Oracle};
 use crate::dex::orca_whirlpools::math::get_tick_array_start_tick_index;
 use crate::dex::orca_whirlpools::WHIRLPOOL_ID;
+use crate::dex::quoter::tick_array_depth;
 use crate::dex::tick_array::{get_tick_array_address, get_tick_array_keys, TickArray};
 use crate::dex::whirlpool::Whirlpool;
 
@@ -25,13 +26,13 @@ impl SnapshotInitializer for OrcaWhirlpoolsSnapshotInitializer {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| json.owner == WHIRLPOOL_ID)
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【OrcaWhirls】开始初始化Snapshot...");
         let mut invalid_pool = AHashSet::with_capacity(dex_data.len());
@@ -47,7 +48,7 @@ impl SnapshotInitializer for OrcaWhirlpoolsSnapshotInitializer {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none()
                 || account.dynamic_slice_data.as_ref().is_none()
@@ -61,25 +62,28 @@ impl SnapshotInitializer for OrcaWhirlpoolsSnapshotInitializer {
         // oracle
         let all_oracle_account_data = self
             .get_oracle_accounts(rpc_client.clone(), &all_pool_account_data)
-            .await;
-        // tick array
+            .await?;
+        // tick array，与quote遍历的数量一致
         let tick_array_account_data = self
-            .get_tick_array_accounts(rpc_client.clone(), &all_pool_account_data, 3)
-            .await;
+            .get_tick_array_accounts(
+                rpc_client.clone(),
+                &all_pool_account_data,
+                tick_array_depth().orca_whirl,
+            )
+            .await?;
         dex_json.retain(|json| !invalid_pool.contains(&json.pool));
         info!(
             "【OrcaWhirls】初始化Snapshot完毕, 初始化池子数量 : {}",
             all_pool_account_data.len()
         );
         if dex_json.is_empty() {
-            vec![]
-        } else {
-            all_pool_account_data
-                .into_iter()
-                .chain(all_oracle_account_data.into_iter())
-                .chain(tick_array_account_data.into_iter())
-                .collect::<Vec<_>>()
+            return Ok(vec![]);
         }
+        Ok(all_pool_account_data
+            .into_iter()
+            .chain(all_oracle_account_data.into_iter())
+            .chain(tick_array_account_data.into_iter())
+            .collect::<Vec<_>>())
     }
 
     // #[cfg(feature = "print_slice_data")]
@@ -131,7 +135,7 @@ impl OrcaWhirlpoolsSnapshotInitializer {
         &self,
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let all_oracle_accounts = all_pool_account_data
             .iter()
             .map(|pool| get_oracle_address(&pool.account_key).unwrap())
@@ -145,13 +149,13 @@ impl OrcaWhirlpoolsSnapshotInitializer {
                 AccountType::Oracle,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         // 并不是每个pool都有
         all_oracle_account_data.retain(|account| {
             account.dynamic_slice_data.as_ref().is_some()
                 && account.static_slice_data.as_ref().is_some()
         });
-        all_oracle_account_data
+        Ok(all_oracle_account_data)
     }
 
     async fn get_bitmap_extension_accounts(
@@ -159,7 +163,7 @@ impl OrcaWhirlpoolsSnapshotInitializer {
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
         invalid_pool: &mut AHashSet<Pubkey>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         // bitmap_extension
         let mut all_bitmap_extension_accounts = all_pool_account_data
             .iter()
@@ -183,7 +187,7 @@ impl OrcaWhirlpoolsSnapshotInitializer {
                 AccountType::TickArrayBitmap,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_bitmap_extension_account_data.retain(|account| {
             if account.dynamic_slice_data.as_ref().is_none_or(|data| {
                 data.len()
@@ -206,7 +210,7 @@ impl OrcaWhirlpoolsSnapshotInitializer {
                 true
             }
         });
-        all_bitmap_extension_account_data
+        Ok(all_bitmap_extension_account_data)
     }
 
     async fn get_tick_array_accounts(
@@ -214,7 +218,7 @@ impl OrcaWhirlpoolsSnapshotInitializer {
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
         load_count: u8,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let tick_array_state_keys = all_pool_account_data
             .iter()
             .filter_map(|account| {
@@ -246,9 +250,9 @@ impl OrcaWhirlpoolsSnapshotInitializer {
                 AccountType::TickArray,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_tick_array_state_account_data
             .retain(|account| account.dynamic_slice_data.as_ref().is_some());
-        all_tick_array_state_account_data
+        Ok(all_tick_array_state_account_data)
     }
 }
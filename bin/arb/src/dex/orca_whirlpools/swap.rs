@@ -1,12 +1,13 @@
 use crate::dex::oracle::{AdaptiveFeeInfo, OracleFacade};
 use crate::dex::orca_whirlpools::error::{
     CoreError, AMOUNT_EXCEEDS_MAX_U64, ARITHMETIC_OVERFLOW, INVALID_ADAPTIVE_FEE_INFO,
-    INVALID_SQRT_PRICE_LIMIT_DIRECTION, SQRT_PRICE_LIMIT_OUT_OF_BOUNDS, ZERO_TRADABLE_AMOUNT,
+    INVALID_SQRT_PRICE_LIMIT_DIRECTION, PARTIAL_FILL_ERROR, SQRT_PRICE_LIMIT_OUT_OF_BOUNDS,
+    ZERO_TRADABLE_AMOUNT,
 };
 use crate::dex::orca_whirlpools::math::{
     sqrt_price_to_tick_index, tick_index_to_sqrt_price, try_apply_swap_fee, try_apply_transfer_fee,
     try_get_amount_delta_a, try_get_amount_delta_b, try_get_next_sqrt_price_from_a,
-    try_get_next_sqrt_price_from_b, try_reverse_apply_swap_fee,
+    try_get_next_sqrt_price_from_b, try_reverse_apply_swap_fee, try_reverse_apply_transfer_fee,
     FeeRateManager, TickArraySequence, TransferFee,
 };
 use crate::dex::tick_array::{TickArrays, TickFacade};
@@ -21,6 +22,11 @@ pub const MAX_SQRT_PRICE: u128 = 79226673515401279992447579055;
 pub struct ExactInSwapQuote {
     pub token_est_out: u64,
     pub trade_fee: u64,
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+    pub end_sqrt_price: u128,
+    pub end_tick_index: i32,
+    pub ticks_crossed: u32,
 }
 
 /// Computes the exact input or output amount for a swap transaction.
@@ -85,6 +91,94 @@ pub fn swap_quote_by_input_token(
     Ok(ExactInSwapQuote {
         token_est_out: amount_out,
         trade_fee: swap_result.trade_fee,
+        transfer_fee_in: token_in - token_in_after_fee,
+        transfer_fee_out: token_est_out_before_fee - amount_out,
+        end_sqrt_price: swap_result.end_sqrt_price,
+        end_tick_index: swap_result.end_tick_index,
+        ticks_crossed: swap_result.ticks_crossed,
+    })
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct ExactOutSwapQuote {
+    pub token_est_in: u64,
+    pub trade_fee: u64,
+    pub transfer_fee_in: u64,
+    pub transfer_fee_out: u64,
+    pub end_sqrt_price: u128,
+    pub end_tick_index: i32,
+    pub ticks_crossed: u32,
+}
+
+/// Computes the input amount needed for an exact output swap transaction.
+///
+/// # Arguments
+/// - `token_out`: The output token amount.
+/// - `specified_token_a`: If `true`, the output token is token A. Otherwise, it is token B.
+/// - `whirlpool`: The whirlpool state.
+/// - `oracle`: The oracle data for the whirlpool.
+/// - `tick_arrays`: The tick arrays needed for the swap.
+/// - `timestamp`: The timestamp for the swap.
+/// - `transfer_fee_a`: The transfer fee for token A.
+/// - `transfer_fee_b`: The transfer fee for token B.
+///
+/// # Returns
+/// The estimated input amount for the swap transaction.
+#[allow(clippy::too_many_arguments)]
+pub fn swap_quote_by_output_token(
+    token_out: u64,
+    specified_token_a: bool,
+    whirlpool: WhirlpoolFacade,
+    oracle: Option<OracleFacade>,
+    tick_arrays: TickArrays,
+    timestamp: u64,
+    transfer_fee_a: Option<TransferFee>,
+    transfer_fee_b: Option<TransferFee>,
+) -> Result<ExactOutSwapQuote, CoreError> {
+    let (transfer_fee_in, transfer_fee_out) = if specified_token_a {
+        (transfer_fee_b, transfer_fee_a)
+    } else {
+        (transfer_fee_a, transfer_fee_b)
+    };
+    // 池子需要转出的数量(包含token2022 fee)
+    let token_out_before_fee =
+        try_reverse_apply_transfer_fee(token_out, transfer_fee_out.unwrap_or_default())?;
+    let tick_sequence = TickArraySequence::new(tick_arrays.into(), whirlpool.tick_spacing)?;
+
+    let swap_result = compute_swap(
+        token_out_before_fee,
+        0,
+        whirlpool,
+        tick_sequence,
+        !specified_token_a,
+        false,
+        timestamp,
+        oracle.map(|oracle| oracle.into()),
+    )?;
+
+    let (token_est_in_before_fee, token_swapped_out) = if specified_token_a {
+        (swap_result.token_b, swap_result.token_a)
+    } else {
+        (swap_result.token_a, swap_result.token_b)
+    };
+    // 到达价格边界时只能部分换出
+    if token_swapped_out < token_out_before_fee {
+        return Err(PARTIAL_FILL_ERROR);
+    }
+
+    let token_est_in = try_reverse_apply_transfer_fee(
+        token_est_in_before_fee,
+        transfer_fee_in.unwrap_or_default(),
+    )?;
+
+    Ok(ExactOutSwapQuote {
+        token_est_in,
+        trade_fee: swap_result.trade_fee,
+        transfer_fee_in: token_est_in - token_est_in_before_fee,
+        transfer_fee_out: token_out_before_fee - token_out,
+        end_sqrt_price: swap_result.end_sqrt_price,
+        end_tick_index: swap_result.end_tick_index,
+        ticks_crossed: swap_result.ticks_crossed,
     })
 }
 
@@ -94,6 +188,11 @@ pub struct SwapResult {
     pub trade_fee: u64,
     pub applied_fee_rate_min: u32,
     pub applied_fee_rate_max: u32,
+    /// swap结束时的价格和tick
+    pub end_sqrt_price: u128,
+    pub end_tick_index: i32,
+    /// 跨越的已初始化tick数量
+    pub ticks_crossed: u32,
 }
 
 /// Computes the amounts of tokens A and B based on the current Whirlpool state and tick sequence.
@@ -160,6 +259,7 @@ pub fn compute_swap<const SIZE: usize>(
     let mut current_liquidity = whirlpool.liquidity;
     // fee amount
     let mut trade_fee = 0u64;
+    let mut ticks_crossed = 0u32;
 
     let base_fee_rate = whirlpool.fee_rate;
     let mut applied_fee_rate_min: Option<u32> = None;
@@ -250,6 +350,9 @@ pub fn compute_swap<const SIZE: usize>(
             if step_quote.next_sqrt_price == next_tick_sqrt_price {
                 // 累加流动性
                 current_liquidity = get_next_liquidity(current_liquidity, next_tick, a_to_b);
+                if next_tick.is_some() {
+                    ticks_crossed += 1;
+                }
                 // 移动tick
                 current_tick_index = if a_to_b {
                     next_tick_index - 1
@@ -307,6 +410,9 @@ pub fn compute_swap<const SIZE: usize>(
         trade_fee,
         applied_fee_rate_min: applied_fee_rate_min.unwrap_or(base_fee_rate as u32),
         applied_fee_rate_max: applied_fee_rate_max.unwrap_or(base_fee_rate as u32),
+        end_sqrt_price: current_sqrt_price,
+        end_tick_index: current_tick_index,
+        ticks_crossed,
     })
 }
 
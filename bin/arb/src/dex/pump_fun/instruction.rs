@@ -1,8 +1,8 @@
 use crate::dex::pump_fun::state::{global_config_key, Pool};
 use crate::dex::swap_instruction::{InstructionMaterial, InstructionMaterialConverter};
 use crate::dex::DexType::PumpFunAMM;
-use crate::dex::{ATA_PROGRAM_ID, MINT_PROGRAM_ID, SYSTEM_PROGRAM_ID};
-use crate::dex::global_cache::get_alt;
+use crate::dex::{QuoteResult, ATA_PROGRAM_ID, MINT_PROGRAM_ID, SYSTEM_PROGRAM_ID};
+use crate::dex::global_cache::{get_alt, get_token_program};
 use crate::metadata::{get_keypair, MintAtaPair};
 use anyhow::Result;
 use solana_sdk::instruction::AccountMeta;
@@ -17,6 +17,7 @@ impl InstructionMaterialConverter for PumpFunAMMInstructionMaterialConverter {
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        _quote_result: Option<&QuoteResult>,
     ) -> Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let pool = crate::dex::global_cache::get_account_data::<Pool>(pool_id).unwrap();
@@ -31,11 +32,13 @@ impl InstructionMaterialConverter for PumpFunAMMInstructionMaterialConverter {
         accounts.push(AccountMeta::new_readonly(pool.base_mint, false));
         // 5.quote mint
         accounts.push(AccountMeta::new_readonly(pool.quote_mint, false));
+        let base_token_program = get_token_program(&pool.base_mint);
+        let quote_token_program = get_token_program(&pool.quote_mint);
         // 6.base mint ata
         let (base_ata, _) = Pubkey::find_program_address(
             &[
                 wallet.as_ref(),
-                MINT_PROGRAM_ID.as_ref(),
+                base_token_program.as_ref(),
                 pool.base_mint.as_ref(),
             ],
             &ATA_PROGRAM_ID,
@@ -45,7 +48,7 @@ impl InstructionMaterialConverter for PumpFunAMMInstructionMaterialConverter {
         let (quote_ata, _) = Pubkey::find_program_address(
             &[
                 wallet.as_ref(),
-                MINT_PROGRAM_ID.as_ref(),
+                quote_token_program.as_ref(),
                 pool.quote_mint.as_ref(),
             ],
             &ATA_PROGRAM_ID,
@@ -71,10 +74,10 @@ impl InstructionMaterialConverter for PumpFunAMMInstructionMaterialConverter {
             .0,
             false,
         ));
-        // 12.base quote program
-        accounts.push(AccountMeta::new_readonly(MINT_PROGRAM_ID, false));
-        // 13.quote quote program
-        accounts.push(AccountMeta::new_readonly(MINT_PROGRAM_ID, false));
+        // 12.base token program
+        accounts.push(AccountMeta::new_readonly(base_token_program, false));
+        // 13.quote token program
+        accounts.push(AccountMeta::new_readonly(quote_token_program, false));
         // 14.system program
         accounts.push(AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false));
         // 15.system program
@@ -78,7 +78,7 @@ mod test {
         use crate::dex::pump_fun::test::setup;
         use crate::dex::{
             get_account_data, global_config_key, slice_data_auto_get_dex_type, update_cache,
-            MintVault, Pool, SliceType,
+            CacheVersion, MintVault, Pool, SliceType,
         };
         use solana_rpc_client::nonblocking::rpc_client::RpcClient;
         use solana_sdk::program_pack::Pack;
@@ -156,7 +156,12 @@ mod test {
                     vault_account_slice.to_vec(),
                     SliceType::Subscribed,
                 )?,
-            )?;
+                CacheVersion::Account {
+                    slot: 1,
+                    write_version: 0,
+                    endpoint: None,
+                },
+            );
             let slice_vault_amount = get_account_data::<MintVault>(&dex_json.vault_a)
                 .unwrap()
                 .amount;
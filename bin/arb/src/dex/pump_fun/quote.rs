@@ -1,8 +1,11 @@
 use crate::dex::global_cache::get_account_data;
 use crate::dex::pump_fun::state::Pool;
-use crate::dex::quoter::{QuoteResult, Quoter};
+use crate::dex::quoter::{
+    constant_product_amount_in, reserve_price, ConstantProductReserves, PreparedQuote,
+    PreparedQuoteType, QuoteResult, Quoter,
+};
 use crate::dex::utils::CheckedCeilDiv;
-use crate::dex::MintVault;
+use crate::dex::{get_transfer_fee_config, MintVault, QuoteError};
 use solana_sdk::pubkey::Pubkey;
 use std::ops::{Add, Div, Mul, Sub};
 
@@ -10,62 +13,281 @@ use std::ops::{Add, Div, Mul, Sub};
 pub struct PumpFunAMMQuoter;
 
 impl Quoter for PumpFunAMMQuoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        PumpFunAMMPreparedQuote::new(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        Ok(PreparedQuoteType::from(PumpFunAMMPreparedQuote::new(
+            pool_id,
+        )?))
+    }
+
+    fn constant_product_reserves(
+        &self,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Option<ConstantProductReserves> {
         let pool = get_account_data::<Pool>(pool_id)?;
+        // Token2022的transfer fee不是恒定乘积
+        if get_transfer_fee_config(&pool.base_mint).is_some()
+            || get_transfer_fee_config(&pool.quote_mint).is_some()
+        {
+            return None;
+        }
         let base_vault_amount =
             u128::from(get_account_data::<MintVault>(&pool.pool_base_token_account)?.amount);
         let quote_vault_amount =
             u128::from(get_account_data::<MintVault>(&pool.pool_quote_token_account)?.amount);
+        let coin_creator_fee_basis_points = if pool.coin_creator == Pubkey::default() {
+            0
+        } else {
+            pool.coin_creator_fee_basis_points
+        };
+        let fee = 1.0
+            - (pool.lp_fee_basis_points
+                + pool.protocol_fee_basis_points
+                + coin_creator_fee_basis_points) as f64
+                / 10_000.0;
+        // 卖出时手续费从quote的输出中扣除，买入时从quote的输入中扣除
+        if swap_direction {
+            ConstantProductReserves::new(base_vault_amount, quote_vault_amount, 1.0, fee)
+        } else {
+            ConstantProductReserves::new(quote_vault_amount, base_vault_amount, fee, 1.0)
+        }
+    }
+}
+
+/// 池子和两个金库的数量
+pub struct PumpFunAMMPreparedQuote {
+    pool: Pool,
+    base_vault_amount: u128,
+    quote_vault_amount: u128,
+}
+
+impl PumpFunAMMPreparedQuote {
+    fn new(pool_id: &Pubkey) -> Result<Self, QuoteError> {
+        let pool = get_account_data::<Pool>(pool_id).ok_or(QuoteError::MissingCache)?;
+        let base_vault_amount = get_account_data::<MintVault>(&pool.pool_base_token_account)
+            .ok_or(QuoteError::MissingCache)?
+            .amount;
+        let quote_vault_amount = get_account_data::<MintVault>(&pool.pool_quote_token_account)
+            .ok_or(QuoteError::MissingCache)?
+            .amount;
+        Ok(Self {
+            pool,
+            base_vault_amount: u128::from(base_vault_amount),
+            quote_vault_amount: u128::from(quote_vault_amount),
+        })
+    }
+}
+
+impl PreparedQuote for PumpFunAMMPreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        let pool = &self.pool;
+        let (base_vault_amount, quote_vault_amount) =
+            (self.base_vault_amount, self.quote_vault_amount);
         let amount_in = u128::from(amount_in);
-        let amount_out = if swap_direction {
+        let (amount_out, fees, end_price) = if swap_direction {
             let quote_amount_out = quote_vault_amount
                 .mul(amount_in)
                 .div(base_vault_amount.add(amount_in));
-            let lp_fee = quote_amount_out
-                .mul(u128::from(pool.lp_fee_basis_points))
-                .checked_ceil_div(10_000)?
-                .0;
-            let protocol_fee = quote_amount_out
-                .mul(u128::from(pool.protocol_fee_basis_points))
-                .checked_ceil_div(10_000)?
-                .0;
-            let coin_creator_fee = if pool.coin_creator == Pubkey::default() {
-                0
-            } else {
+            let fees = Fees::new(pool, quote_amount_out).ok_or(QuoteError::MathOverflow)?;
+            (
                 quote_amount_out
-                    .mul(u128::from(pool.coin_creator_fee_basis_points))
-                    .checked_ceil_div(10_000)?
-                    .0
-            };
-            quote_amount_out
-                .sub(lp_fee)
-                .sub(protocol_fee)
-                .sub(coin_creator_fee)
+                    .checked_sub(fees.total())
+                    .ok_or(QuoteError::InvalidAmount)?,
+                fees,
+                reserve_price(
+                    base_vault_amount.add(amount_in),
+                    quote_vault_amount.sub(quote_amount_out),
+                ),
+            )
         } else {
-            let lp_fee = amount_in
-                .mul(u128::from(pool.lp_fee_basis_points))
-                .checked_ceil_div(10_000)?
-                .0;
-            let protocol_fee = amount_in
-                .mul(u128::from(pool.protocol_fee_basis_points))
-                .checked_ceil_div(10_000)?
-                .0;
-            let coin_creator_fee = if pool.coin_creator == Pubkey::default() {
-                0
-            } else {
-                amount_in
-                    .mul(u128::from(pool.coin_creator_fee_basis_points))
-                    .checked_ceil_div(10_000)?
-                    .0
-            };
-            let total_fee = lp_fee.add(protocol_fee).add(coin_creator_fee);
-            let effective_amount = amount_in.sub(total_fee);
-            base_vault_amount
+            let fees = Fees::new(pool, amount_in).ok_or(QuoteError::MathOverflow)?;
+            let effective_amount = amount_in
+                .checked_sub(fees.total())
+                .ok_or(QuoteError::InvalidAmount)?;
+            let base_amount_out = base_vault_amount
                 .mul(effective_amount)
-                .div(quote_vault_amount.add(effective_amount))
+                .div(quote_vault_amount.add(effective_amount));
+            (
+                base_amount_out,
+                fees,
+                reserve_price(
+                    quote_vault_amount.add(effective_amount),
+                    base_vault_amount.sub(base_amount_out),
+                ),
+            )
+        };
+        Ok(QuoteResult {
+            amount_in: u64::try_from(amount_in).map_err(|_| QuoteError::MathOverflow)?,
+            amount_out: u64::try_from(amount_out).map_err(|_| QuoteError::MathOverflow)?,
+            start_price: start_price(base_vault_amount, quote_vault_amount, swap_direction),
+            end_price,
+            ..fees.into_quote_result().ok_or(QuoteError::MathOverflow)?
+        })
+    }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        let pool = &self.pool;
+        let (base_vault_amount, quote_vault_amount) =
+            (self.base_vault_amount, self.quote_vault_amount);
+        let (amount_in, fees, end_price) = if swap_direction {
+            // 卖出：手续费从quote输出中扣除，先求扣费前的quote数量
+            let quote_amount_out =
+                amount_before_fees(pool, u128::from(amount_out)).ok_or(QuoteError::MathOverflow)?;
+            let base_amount_in =
+                constant_product_amount_in(base_vault_amount, quote_vault_amount, quote_amount_out)
+                    .ok_or(QuoteError::InsufficientLiquidity)?;
+            (
+                base_amount_in,
+                Fees::new(pool, quote_amount_out).ok_or(QuoteError::MathOverflow)?,
+                reserve_price(
+                    base_vault_amount.add(base_amount_in),
+                    quote_vault_amount.sub(quote_amount_out),
+                ),
+            )
+        } else {
+            // 买入：手续费从quote输入中扣除
+            let effective_amount = constant_product_amount_in(
+                quote_vault_amount,
+                base_vault_amount,
+                u128::from(amount_out),
+            )
+            .ok_or(QuoteError::InsufficientLiquidity)?;
+            let quote_amount_in =
+                amount_before_fees(pool, effective_amount).ok_or(QuoteError::MathOverflow)?;
+            (
+                quote_amount_in,
+                Fees::new(pool, quote_amount_in).ok_or(QuoteError::MathOverflow)?,
+                reserve_price(
+                    quote_vault_amount.add(effective_amount),
+                    base_vault_amount.sub(u128::from(amount_out)),
+                ),
+            )
+        };
+        Ok(QuoteResult {
+            amount_in: u64::try_from(amount_in).map_err(|_| QuoteError::MathOverflow)?,
+            amount_out,
+            start_price: start_price(base_vault_amount, quote_vault_amount, swap_direction),
+            end_price,
+            ..fees.into_quote_result().ok_or(QuoteError::MathOverflow)?
+        })
+    }
+}
+
+fn start_price(base_vault_amount: u128, quote_vault_amount: u128, swap_direction: bool) -> f64 {
+    if swap_direction {
+        reserve_price(base_vault_amount, quote_vault_amount)
+    } else {
+        reserve_price(quote_vault_amount, base_vault_amount)
+    }
+}
+
+/// lp、protocol、coin_creator三项手续费，分别向上取整，均以quote计价
+struct Fees {
+    lp_fee: u128,
+    protocol_fee: u128,
+    coin_creator_fee: u128,
+}
+
+impl Fees {
+    fn new(pool: &Pool, amount: u128) -> Option<Self> {
+        let lp_fee = amount
+            .mul(u128::from(pool.lp_fee_basis_points))
+            .checked_ceil_div(10_000)?
+            .0;
+        let protocol_fee = amount
+            .mul(u128::from(pool.protocol_fee_basis_points))
+            .checked_ceil_div(10_000)?
+            .0;
+        let coin_creator_fee = if pool.coin_creator == Pubkey::default() {
+            0
+        } else {
+            amount
+                .mul(u128::from(pool.coin_creator_fee_basis_points))
+                .checked_ceil_div(10_000)?
+                .0
         };
+        Some(Self {
+            lp_fee,
+            protocol_fee,
+            coin_creator_fee,
+        })
+    }
+
+    fn total(&self) -> u128 {
+        self.lp_fee
+            .add(self.protocol_fee)
+            .add(self.coin_creator_fee)
+    }
+
+    fn into_quote_result(self) -> Option<QuoteResult> {
         Some(QuoteResult {
-            amount_out: u64::try_from(amount_out).ok()?,
+            lp_fee: u64::try_from(self.lp_fee).ok()?,
+            protocol_fee: u64::try_from(self.protocol_fee).ok()?,
+            creator_fee: u64::try_from(self.coin_creator_fee).ok()?,
+            ..Default::default()
         })
     }
-}
\ No newline at end of file
+}
+
+/// 扣除手续费后剩余不少于amount的最小数量
+fn amount_before_fees(pool: &Pool, amount: u128) -> Option<u128> {
+    let coin_creator_fee_basis_points = if pool.coin_creator == Pubkey::default() {
+        0
+    } else {
+        pool.coin_creator_fee_basis_points
+    };
+    let total_fee_basis_points = u128::from(pool.lp_fee_basis_points)
+        + u128::from(pool.protocol_fee_basis_points)
+        + u128::from(coin_creator_fee_basis_points);
+    // 每项手续费单独取整，checked_ceil_div在商小于0.5时取0，三项合计比按总费率计算的少不到2个单位，
+    // 扣费后的数量不单调，从下界开始逐个查找
+    let mut pre_fee_amount = amount.checked_mul(10_000)?.saturating_sub(20_000)
+        / 10_000u128
+            .checked_sub(total_fee_basis_points)
+            .filter(|d| *d > 0)?;
+    while pre_fee_amount.saturating_sub(Fees::new(pool, pre_fee_amount)?.total()) < amount {
+        pre_fee_amount += 1;
+    }
+    Some(pre_fee_amount)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::pump_fun::quote::PumpFunAMMPreparedQuote;
+    use crate::dex::pump_fun::state::Pool;
+    use crate::dex::quoter::assert_exact_out_round_trip;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        let amounts_in = [1, 999, 10_u64.pow(6), 10_u64.pow(9), 10_u64.pow(12)];
+        for coin_creator in [Pubkey::default(), Pubkey::new_unique()] {
+            let prepared = PumpFunAMMPreparedQuote {
+                pool: Pool {
+                    coin_creator,
+                    lp_fee_basis_points: 20,
+                    protocol_fee_basis_points: 5,
+                    coin_creator_fee_basis_points: 5,
+                    ..Default::default()
+                },
+                base_vault_amount: 200_000_000 * 10_u128.pow(6),
+                quote_vault_amount: 80 * 10_u128.pow(9),
+            };
+            assert_exact_out_round_trip(&prepared, true, &amounts_in);
+            assert_exact_out_round_trip(&prepared, false, &amounts_in);
+        }
+    }
+}
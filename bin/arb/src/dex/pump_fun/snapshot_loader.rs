@@ -23,14 +23,14 @@ impl SnapshotInitializer for PumpFunAMMSnapshotInitializer {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| json.owner == PUMP_FUN_AMM_PROGRAM_ID)
             .map(|json| json.clone())
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【PumpFunAMM】开始初始化Snapshot...");
 
@@ -42,10 +42,10 @@ impl SnapshotInitializer for PumpFunAMMSnapshotInitializer {
             vault_accounts.insert(json.vault_b);
         }
         // global config
-        let global_config_account = self.get_global_config_account(rpc_client.clone()).await;
+        let global_config_account = self.get_global_config_account(rpc_client.clone()).await?;
         if global_config_account.as_ref().is_none() {
             dex_json.retain(|json| json.owner != PUMP_FUN_AMM_PROGRAM_ID);
-            return vec![];
+            return Ok(vec![]);
         }
         let all_pool_account_data = self
             .get_pool_snapshot(
@@ -53,22 +53,22 @@ impl SnapshotInitializer for PumpFunAMMSnapshotInitializer {
                 global_config_account.unwrap(),
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         if all_pool_account_data.is_empty() {
             dex_json.retain(|json| json.owner != PUMP_FUN_AMM_PROGRAM_ID);
-            return vec![];
+            return Ok(vec![]);
         }
         let all_mint_vault_data = self
             .get_mint_vault_snapshot(vault_accounts.into_iter().collect(), rpc_client.clone())
-            .await;
+            .await?;
         info!(
             "【PumpFunAMM】初始化Snapshot完毕, 初始化池子数量 : {}",
             dex_data.len()
         );
-        all_pool_account_data
+        Ok(all_pool_account_data
             .into_iter()
             .chain(all_mint_vault_data.into_iter())
-            .collect::<Vec<AccountDataSlice>>()
+            .collect::<Vec<AccountDataSlice>>())
     }
 
     fn print_snapshot(&self, dex_json: &[DexJson]) -> anyhow::Result<()> {
@@ -94,7 +94,7 @@ impl PumpFunAMMSnapshotInitializer {
     async fn get_global_config_account(
         &self,
         rpc_client: Arc<RpcClient>,
-    ) -> Option<AccountDataSlice> {
+    ) -> anyhow::Result<Option<AccountDataSlice>> {
         let global_config_account = self
             .get_account_data_with_data_slice(
                 vec![crate::dex::pump_fun::state::global_config_key()],
@@ -102,12 +102,12 @@ impl PumpFunAMMSnapshotInitializer {
                 AccountType::PumpFunGlobalConfig,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         let g = global_config_account.first();
         if g.is_none_or(|v| v.static_slice_data.is_none()) {
-            None
+            Ok(None)
         } else {
-            g.cloned()
+            Ok(g.cloned())
         }
     }
 
@@ -116,7 +116,7 @@ impl PumpFunAMMSnapshotInitializer {
         pool_keys: Vec<Pubkey>,
         global_config_account_data: AccountDataSlice,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         // lp_fee_basis_points 和 protocol_fee_basis_points
         let global_config_account_data = global_config_account_data.static_slice_data.unwrap();
         let mut all_pool_account_data = self
@@ -126,7 +126,7 @@ impl PumpFunAMMSnapshotInitializer {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| account.static_slice_data.is_some());
         for account in all_pool_account_data.iter_mut() {
             let pool_static_data = account.static_slice_data.as_ref().unwrap();
@@ -141,12 +141,7 @@ impl PumpFunAMMSnapshotInitializer {
             let token_program = if quote_mint == spl_token::native_mint::ID {
                 spl_token::ID
             } else {
-                rpc_client
-                    .clone()
-                    .get_account(&quote_mint)
-                    .await
-                    .unwrap()
-                    .owner
+                rpc_client.get_account(&quote_mint).await?.owner
             };
             let (coin_creator_vault_authority, _) = Pubkey::find_program_address(
                 &[b"creator_vault", coin_creator.to_bytes().as_ref()],
@@ -166,14 +161,14 @@ impl PumpFunAMMSnapshotInitializer {
             combine_data.extend(coin_creator_vault_ata.to_bytes());
             account.static_slice_data.replace(combine_data);
         }
-        all_pool_account_data
+        Ok(all_pool_account_data)
     }
 
     async fn get_mint_vault_snapshot(
         &self,
         mint_vault_keys: Vec<Pubkey>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let mut all_vault_account_data = self
             .get_account_data_with_data_slice(
                 mint_vault_keys,
@@ -181,9 +176,9 @@ impl PumpFunAMMSnapshotInitializer {
                 AccountType::MintVault,
                 rpc_client,
             )
-            .await;
+            .await?;
         all_vault_account_data.retain(|account| account.dynamic_slice_data.is_some());
-        all_vault_account_data
+        Ok(all_vault_account_data)
     }
 }
 
@@ -253,7 +248,7 @@ mod test {
         let rpc_client = Arc::new(RpcClient::new_mock_with_mocks("success".to_string(), mocks));
         let mut snapshot_data = PumpFunAMMSnapshotInitializer
             .get_mint_vault_snapshot(vec![dex_json.vault_a], rpc_client.clone())
-            .await;
+            .await?;
         assert_eq!(snapshot_data.len(), 1);
         let data_slice = snapshot_data.pop().unwrap();
         assert_eq!(data_slice.account_key, dex_json.vault_a);
@@ -294,7 +289,7 @@ mod test {
                 "success".to_string(),
                 mocks,
             )))
-            .await;
+            .await?;
         assert!(global_config_data.is_some());
         assert!(global_config_data
             .as_ref()
@@ -345,7 +340,7 @@ mod test {
                 global_config_data.unwrap(),
                 Arc::new(RpcClient::new_mock_with_mocks("success".to_string(), mocks)),
             )
-            .await;
+            .await?;
         assert_eq!(pool_snapshot_data.len(), 1);
         let data_slice = pool_snapshot_data.pop().unwrap();
         assert_eq!(data_slice.account_key, dex_json.pool);
@@ -397,7 +392,7 @@ mod test {
                 &mut dex_json,
                 Arc::new(RpcClient::new_mock("success".to_string())),
             )
-            .await;
+            .await?;
         assert_eq!(data.len(), 0);
         Ok(())
     }
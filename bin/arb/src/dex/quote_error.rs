@@ -0,0 +1,142 @@
+use ahash::RandomState;
+use dashmap::DashMap;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt::{Display, Formatter};
+use tokio::sync::OnceCell;
+
+static QUOTE_ERROR_STATS: OnceCell<DashMap<(Pubkey, QuoteError), u64, RandomState>> =
+    OnceCell::const_new();
+
+/// quote失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuoteError {
+    /// 缓存中缺少池子、金库、配置等账户
+    MissingCache,
+    /// 缓存中缺少Clock
+    MissingClock,
+    /// 缓存中没有可用的tick array / bin array，或swap走出了已加载的范围
+    MissingTickArray,
+    /// 流动性不足或超出价格区间
+    InsufficientLiquidity,
+    /// 池子被禁用或未到激活时间
+    PoolDisabled,
+    /// 计算溢出或类型转换失败
+    MathOverflow,
+    /// 数量为0或扣除手续费后为0
+    InvalidAmount,
+    /// 缓存中的池子、金库落后于触发交易的slot
+    StaleState,
+    Other,
+}
+
+impl Display for QuoteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            QuoteError::MissingCache => "缓存中缺少账户",
+            QuoteError::MissingClock => "缓存中缺少Clock",
+            QuoteError::MissingTickArray => "缺少tick array/bin array",
+            QuoteError::InsufficientLiquidity => "流动性不足",
+            QuoteError::PoolDisabled => "池子未启用",
+            QuoteError::MathOverflow => "计算溢出",
+            QuoteError::InvalidAmount => "数量无效",
+            QuoteError::StaleState => "缓存落后于交易",
+            QuoteError::Other => "其他错误",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+impl std::error::Error for QuoteError {}
+
+impl QuoteError {
+    /// 按DEX数学库返回的错误信息归类，errors : (错误信息, 原因)，未列出的归为Other
+    pub(crate) fn classify(e: &anyhow::Error, errors: &[(&str, QuoteError)]) -> QuoteError {
+        if let Some(quote_error) = e.downcast_ref::<QuoteError>() {
+            return *quote_error;
+        }
+        e.downcast_ref::<&'static str>()
+            .and_then(|message| {
+                errors
+                    .iter()
+                    .find(|(error_message, _)| error_message == message)
+            })
+            .map_or(QuoteError::Other, |(_, quote_error)| *quote_error)
+    }
+}
+
+/// 开启按池子、原因统计quote失败次数，未开启时不统计
+pub fn init_quote_error_stats() -> anyhow::Result<()> {
+    Ok(QUOTE_ERROR_STATS.set(DashMap::with_hasher(RandomState::default()))?)
+}
+
+pub fn record_quote_error(pool_id: &Pubkey, quote_error: QuoteError) {
+    if let Some(stats) = QUOTE_ERROR_STATS.get() {
+        *stats.entry((*pool_id, quote_error)).or_insert(0) += 1;
+    }
+}
+
+/// 累计的失败次数 : (池子, 原因, 次数)，按次数从多到少
+pub fn quote_error_stats() -> Vec<(Pubkey, QuoteError, u64)> {
+    let mut stats = QUOTE_ERROR_STATS.get().map_or(vec![], |stats| {
+        stats
+            .iter()
+            .map(|entry| (entry.key().0, entry.key().1, *entry.value()))
+            .collect::<Vec<_>>()
+    });
+    stats.sort_unstable_by(|a, b| b.2.cmp(&a.2));
+    stats
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::quote_error::{
+        init_quote_error_stats, quote_error_stats, record_quote_error, QuoteError,
+    };
+    use anyhow::{anyhow, Context};
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_quote_error_classify() {
+        let errors = [
+            ("Pair is disabled", QuoteError::PoolDisabled),
+            ("MathOverflow", QuoteError::MathOverflow),
+        ];
+        assert_eq!(
+            QuoteError::classify(&anyhow!("Pair is disabled"), &errors),
+            QuoteError::PoolDisabled
+        );
+        assert_eq!(
+            QuoteError::classify(&None::<u64>.context("MathOverflow").unwrap_err(), &errors),
+            QuoteError::MathOverflow
+        );
+        assert_eq!(
+            QuoteError::classify(&anyhow!(QuoteError::MissingClock), &errors),
+            QuoteError::MissingClock
+        );
+        assert_eq!(
+            QuoteError::classify(&anyhow!("池子[{}]不存在", 1), &errors),
+            QuoteError::Other
+        );
+    }
+
+    #[test]
+    fn test_quote_error_stats() -> anyhow::Result<()> {
+        init_quote_error_stats()?;
+        let pool_id = Pubkey::new_unique();
+        record_quote_error(&pool_id, QuoteError::MissingTickArray);
+        record_quote_error(&pool_id, QuoteError::MissingTickArray);
+        record_quote_error(&pool_id, QuoteError::MissingClock);
+        let stats = quote_error_stats()
+            .into_iter()
+            .filter(|(id, _, _)| *id == pool_id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stats,
+            vec![
+                (pool_id, QuoteError::MissingTickArray, 2),
+                (pool_id, QuoteError::MissingClock, 1)
+            ]
+        );
+        Ok(())
+    }
+}
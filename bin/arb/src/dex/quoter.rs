@@ -1,17 +1,96 @@
-use crate::dex::meteora_damm_v2::MeteoraDAMMV2Quoter;
-use crate::dex::meteora_dlmm::MeteoraDLMMQuoter;
-use crate::dex::orca_whirlpools::OrcaWhirlQuoter;
-use crate::dex::pump_fun::quote::PumpFunAMMQuoter;
-use crate::dex::raydium_amm::quote::RaydiumAMMQuoter;
-use crate::dex::raydium_clmm::quote::RaydiumCLMMQuoter;
-use crate::dex::raydium_cpmm::RaydiumCPMMQuoter;
-use crate::dex::DexType;
+use crate::dex::meteora_damm_v2::{MeteoraDAMMV2PreparedQuote, MeteoraDAMMV2Quoter};
+use crate::dex::meteora_dlmm::{MeteoraDLMMPreparedQuote, MeteoraDLMMQuoter};
+use crate::dex::orca_whirlpools::{OrcaWhirlPreparedQuote, OrcaWhirlQuoter};
+use crate::dex::pump_fun::quote::{PumpFunAMMPreparedQuote, PumpFunAMMQuoter};
+use crate::dex::raydium_amm::quote::{RaydiumAMMPreparedQuote, RaydiumAMMQuoter};
+use crate::dex::raydium_clmm::quote::{RaydiumCLMMPreparedQuote, RaydiumCLMMQuoter};
+use crate::dex::raydium_cpmm::{RaydiumCPMMPreparedQuote, RaydiumCPMMQuoter};
+use crate::dex::{DexType, QuoteError};
 use enum_dispatch::enum_dispatch;
 use solana_sdk::pubkey::Pubkey;
+use tokio::sync::OnceCell;
+
+static TICK_ARRAY_DEPTH: OnceCell<TickArrayDepth> = OnceCell::const_new();
+
+/// Whirlpool swap_v2 固定3个tick array，最多再带3个supplemental tick array
+pub const MAX_WHIRLPOOL_TICK_ARRAYS: u8 = 6;
+
+/// quote时沿swap方向最多遍历的tick array / bin array数量(含当前价格所在的)
+///
+/// 只加载不超过该数量的已初始化的array，交易中只带上quote实际遍历的部分
+#[derive(Debug, Clone, Copy)]
+pub struct TickArrayDepth {
+    pub raydium_clmm: u8,
+    pub meteora_dlmm: u8,
+    pub orca_whirl: u8,
+}
+
+impl Default for TickArrayDepth {
+    fn default() -> Self {
+        Self {
+            raydium_clmm: 5,
+            meteora_dlmm: 5,
+            orca_whirl: MAX_WHIRLPOOL_TICK_ARRAYS,
+        }
+    }
+}
+
+pub fn init_tick_array_depth(depth: TickArrayDepth) -> anyhow::Result<()> {
+    Ok(TICK_ARRAY_DEPTH.set(TickArrayDepth {
+        raydium_clmm: depth.raydium_clmm.max(1),
+        meteora_dlmm: depth.meteora_dlmm.max(1),
+        orca_whirl: depth.orca_whirl.clamp(3, MAX_WHIRLPOOL_TICK_ARRAYS),
+    })?)
+}
+
+/// 未初始化时使用默认值
+pub(crate) fn tick_array_depth() -> TickArrayDepth {
+    TICK_ARRAY_DEPTH.get().copied().unwrap_or_default()
+}
 
 #[enum_dispatch]
 pub trait Quoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult>;
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError>;
+
+    /// 从缓存读取并解码池子当前的状态，之后对任意数量、两个方向的quote都不再访问缓存
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError>;
+
+    /// 指定输出数量，计算需要的最少输入数量
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.prepare(pool_id)?
+            .quote_exact_out(amount_out, swap_direction)
+    }
+
+    /// 恒定乘积(x·y=k)池子返回当前的储备和手续费，用于解析求解最佳amount_in
+    ///
+    /// 集中流动性池子或无法用恒定乘积描述的情况返回None
+    fn constant_product_reserves(
+        &self,
+        _swap_direction: bool,
+        _pool_id: &Pubkey,
+    ) -> Option<ConstantProductReserves> {
+        None
+    }
+
+    /// 当前的边际汇率(输出Mint/输入Mint，最小单位)，已扣除交易手续费，不含Token2022的transfer fee
+    ///
+    /// 任意数量的实际汇率都不高于该值，用于在完整quote前排除不可能盈利的路径，无法计算时返回None
+    fn marginal_price(&self, swap_direction: bool, pool_id: &Pubkey) -> Option<f64> {
+        Some(
+            self.constant_product_reserves(swap_direction, pool_id)?
+                .marginal_price(),
+        )
+    }
 }
 
 #[derive(Debug)]
@@ -26,6 +105,30 @@ pub enum QuoterType {
     RaydiumCPMM(RaydiumCPMMQuoter),
 }
 
+/// 解码后的池子状态，同一轮路由内对同一池子的多次quote(数量搜索、拆单)共用
+#[enum_dispatch]
+pub trait PreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError>;
+
+    /// 指定输出数量，计算需要的最少输入数量，池子的检查与quote相同
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError>;
+}
+
+#[enum_dispatch(PreparedQuote)]
+pub enum PreparedQuoteType {
+    MeteoraDLMM(MeteoraDLMMPreparedQuote),
+    MeteoraDAMMV2(MeteoraDAMMV2PreparedQuote),
+    OrcaWhirl(OrcaWhirlPreparedQuote),
+    PumpFunAMM(PumpFunAMMPreparedQuote),
+    RaydiumAmm(RaydiumAMMPreparedQuote),
+    RaydiumCLMM(RaydiumCLMMPreparedQuote),
+    RaydiumCPMM(RaydiumCPMMPreparedQuote),
+}
+
 pub fn get_quoter_type(dex_type: DexType) -> anyhow::Result<QuoterType> {
     match dex_type {
         DexType::RaydiumAMM => Ok(QuoterType::from(RaydiumAMMQuoter)),
@@ -38,7 +141,141 @@ pub fn get_quoter_type(dex_type: DexType) -> anyhow::Result<QuoterType> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct QuoteResult {
+    pub amount_in: u64,
     pub amount_out: u64,
+    /// 留给LP的手续费
+    pub lp_fee: u64,
+    /// 协议手续费(Raydium CLMM包含fund fee)
+    pub protocol_fee: u64,
+    /// 代币创建者手续费
+    pub creator_fee: u64,
+    /// Token2022 输入端的transfer fee
+    pub transfer_fee_in: u64,
+    /// Token2022 输出端的transfer fee
+    pub transfer_fee_out: u64,
+    /// swap前的价格，输出Mint/输入Mint(最小单位)，不含手续费
+    pub start_price: f64,
+    /// swap后的价格，输出Mint/输入Mint(最小单位)，不含手续费
+    pub end_price: f64,
+    /// 跨越的已初始化tick数量或bin数量
+    pub crossed: u32,
+    /// quote实际遍历的tick array / bin array，按遍历顺序
+    pub tick_arrays: Vec<Pubkey>,
+}
+
+impl QuoteResult {
+    /// 价格影响(万分之)
+    pub fn price_impact_bps(&self) -> f64 {
+        if self.start_price > 0.0 {
+            (1.0 - self.end_price / self.start_price) * 10_000.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// 恒定乘积池子的价格 : 输出Mint/输入Mint
+#[inline]
+pub(crate) fn reserve_price(reserve_in: u128, reserve_out: u128) -> f64 {
+    if reserve_in == 0 {
+        0.0
+    } else {
+        reserve_out as f64 / reserve_in as f64
+    }
+}
+
+/// Q64.64 的价格(token_b/token_a)转换为 输出Mint/输入Mint 的价格
+#[inline]
+pub(crate) fn x64_price(price_x64: u128, a_to_b: bool) -> f64 {
+    let price = price_x64 as f64 / 2_f64.powi(64);
+    if a_to_b || price == 0.0 {
+        price
+    } else {
+        1.0 / price
+    }
+}
+
+/// Q64.64 的sqrt price转换为 输出Mint/输入Mint 的价格
+#[inline]
+pub(crate) fn sqrt_x64_price(sqrt_price_x64: u128, a_to_b: bool) -> f64 {
+    let sqrt_price = sqrt_price_x64 as f64 / 2_f64.powi(64);
+    let price = sqrt_price * sqrt_price;
+    if a_to_b || price == 0.0 {
+        price
+    } else {
+        1.0 / price
+    }
+}
+
+/// 恒定乘积池子换出amount_out需要的最少输入(不含手续费)，流动性不足时返回None
+pub(crate) fn constant_product_amount_in(
+    reserve_in: u128,
+    reserve_out: u128,
+    amount_out: u128,
+) -> Option<u128> {
+    let remaining_out = reserve_out.checked_sub(amount_out).filter(|r| *r > 0)?;
+    Some(reserve_in.checked_mul(amount_out)?.div_ceil(remaining_out))
+}
+
+/// amount_out = fee_out * reserve_out * fee_in * amount_in / (reserve_in + fee_in * amount_in)
+#[derive(Debug, Clone, Copy)]
+pub struct ConstantProductReserves {
+    pub reserve_in: u128,
+    pub reserve_out: u128,
+    // 输入端扣除手续费后剩余的比例
+    pub fee_in: f64,
+    // 输出端扣除手续费后剩余的比例
+    pub fee_out: f64,
+}
+
+impl ConstantProductReserves {
+    pub fn new(reserve_in: u128, reserve_out: u128, fee_in: f64, fee_out: f64) -> Option<Self> {
+        (reserve_in > 0 && reserve_out > 0).then_some(Self {
+            reserve_in,
+            reserve_out,
+            fee_in,
+            fee_out,
+        })
+    }
+
+    /// amount_in趋近于0时的汇率
+    pub fn marginal_price(&self) -> f64 {
+        self.fee_in * self.fee_out * self.reserve_out as f64 / self.reserve_in as f64
+    }
+}
+
+/// exact-in的输出再按exact-out计算，需要的输入不超过原输入，且该输入的exact-in输出不少于目标
+#[cfg(test)]
+pub(crate) fn assert_exact_out_round_trip(
+    prepared: &impl PreparedQuote,
+    swap_direction: bool,
+    amounts_in: &[u64],
+) {
+    for &amount_in in amounts_in {
+        let amount_out = prepared
+            .quote(amount_in, swap_direction)
+            .unwrap()
+            .amount_out;
+        if amount_out == 0 {
+            continue;
+        }
+        let exact_out = prepared
+            .quote_exact_out(amount_out, swap_direction)
+            .unwrap();
+        assert_eq!(exact_out.amount_out, amount_out);
+        assert!(
+            exact_out.amount_in <= amount_in,
+            "amount_in : {amount_in}, amount_out : {amount_out}, exact_out amount_in : {}",
+            exact_out.amount_in
+        );
+        assert!(
+            prepared
+                .quote(exact_out.amount_in, swap_direction)
+                .unwrap()
+                .amount_out
+                >= amount_out
+        );
+    }
 }
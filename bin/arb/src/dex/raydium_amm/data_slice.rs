@@ -5,7 +5,7 @@ use crate::dex::data_slice::{retain_intervals_unsafe, DataSliceInitializer, Slic
 
 // ========================= dynamic data 账户订阅的数据切片 =========================
 // amm pool
-static DYNAMIC_RAYDIUM_AMM_POOL_SLICE: OnceCell<([(usize, usize); 2], usize)> =
+static DYNAMIC_RAYDIUM_AMM_POOL_SLICE: OnceCell<([(usize, usize); 4], usize)> =
     OnceCell::const_new();
 // ========================= static data 账户未订阅的数据切片 =========================
 // amm pool
@@ -78,8 +78,12 @@ fn init_pool_data_slice() -> anyhow::Result<()> {
                 (192, 192 + 8),
                 // state_data.need_take_pnl_pc
                 (200, 200 + 8),
+                // status
+                (0, 8),
+                // state_data.pool_open_time
+                (224, 224 + 8),
             ],
-            8 + 8,
+            8 + 8 + 8 + 8,
         )
     })?;
     STATIC_RAYDIUM_AMM_POOL_SLICE.set({
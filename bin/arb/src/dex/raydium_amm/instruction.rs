@@ -3,7 +3,7 @@ use crate::dex::raydium_amm::state::AmmInfo;
 use crate::dex::raydium_amm::SERUM_PROGRAM_ID;
 use crate::dex::swap_instruction::{InstructionMaterial, InstructionMaterialConverter};
 use crate::dex::DexType::RaydiumAMM;
-use crate::dex::{ATA_PROGRAM_ID, MINT_PROGRAM_ID};
+use crate::dex::{QuoteResult, ATA_PROGRAM_ID, MINT_PROGRAM_ID};
 use crate::metadata::{get_keypair, MintAtaPair};
 use anyhow::Result;
 use solana_sdk::instruction::AccountMeta;
@@ -17,6 +17,7 @@ impl InstructionMaterialConverter for RaydiumAMMInstructionMaterialConverter {
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        _quote_result: Option<&QuoteResult>,
     ) -> Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let amm_info = crate::dex::global_cache::get_account_data::<AmmInfo>(pool_id).unwrap();
@@ -138,7 +139,7 @@ mod test {
         let data = bytemuck::bytes_of(&amm_info);
         let static_data = &data[0..144];
         let dynamic_data = &data[144..];
-        let mut global_cache = GlobalCache::init();
+        let global_cache = GlobalCache::init();
         global_cache.upsert_static(dex_json.pool, static_data.to_vec());
         global_cache.upsert_dynamic(dex_json.pool, dynamic_data.to_vec());
         init_global_cache(global_cache);
@@ -161,8 +162,11 @@ mod test {
             &ATA_PROGRAM_ID,
         )
         .0;
-        let a_to_b_result = RaydiumAMMInstructionMaterialConverter
-            .convert_to_instruction_material(&dex_json.pool, true);
+        let a_to_b_result = RaydiumAMMInstructionMaterialConverter.convert_to_instruction_material(
+            &dex_json.pool,
+            true,
+            None,
+        );
 
         assert!(a_to_b_result.is_ok());
         let a_to_b_result = a_to_b_result?;
@@ -183,8 +187,11 @@ mod test {
         ]
         .iter()
         .all(|a| a_to_b_result.used_atas.contains(a)));
-        let b_to_a_result = RaydiumAMMInstructionMaterialConverter
-            .convert_to_instruction_material(&dex_json.pool, false);
+        let b_to_a_result = RaydiumAMMInstructionMaterialConverter.convert_to_instruction_material(
+            &dex_json.pool,
+            false,
+            None,
+        );
         assert!(b_to_a_result.is_ok());
         let b_to_a_result = b_to_a_result?;
         assert_eq!(b_to_a_result.dex_type, DexType::RaydiumAMM);
@@ -60,8 +60,8 @@ mod test {
         use crate::dex::raydium_amm::old_state;
         use crate::dex::raydium_amm::test::setup;
         use crate::dex::{
-            get_account_data, slice_data_auto_get_dex_type, update_cache, AmmInfo, MintVault,
-            SliceType,
+            get_account_data, slice_data_auto_get_dex_type, update_cache, AmmInfo, CacheVersion,
+            MintVault, SliceType,
         };
         use solana_rpc_client::nonblocking::rpc_client::RpcClient;
         use solana_sdk::program_pack::Pack;
@@ -120,7 +120,12 @@ mod test {
                     update_pool_data.to_vec(),
                     SliceType::Subscribed,
                 )?,
-            )?;
+                CacheVersion::Account {
+                    slot: 1,
+                    write_version: 0,
+                    endpoint: None,
+                },
+            );
             let update_amm_info = get_account_data::<AmmInfo>(&dex_json.pool).unwrap();
             let cache_data = update_amm_info.need_take_pnl_coin;
             let origin_data = test_amm_info.state_data.need_take_pnl_coin;
@@ -166,7 +171,12 @@ mod test {
                     account_data.to_vec(),
                     SliceType::Subscribed,
                 )?,
-            )?;
+                CacheVersion::Account {
+                    slot: 1,
+                    write_version: 0,
+                    endpoint: None,
+                },
+            );
             let update_amount = get_account_data::<MintVault>(&dex_json.vault_b)
                 .unwrap()
                 .amount;
@@ -93,6 +93,8 @@ impl TryInto<crate::dex::AmmInfo> for AmmInfo {
             pc_vault_mint: self.pc_vault_mint,
             need_take_pnl_coin: self.state_data.need_take_pnl_coin,
             need_take_pnl_pc: self.state_data.need_take_pnl_pc,
+            status: self.status,
+            pool_open_time: self.state_data.pool_open_time,
         })
     }
 }
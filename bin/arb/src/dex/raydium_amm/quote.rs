@@ -1,51 +1,202 @@
-use crate::dex::global_cache::get_account_data;
-use crate::dex::quoter::{QuoteResult, Quoter};
+use crate::dex::global_cache::{get_account_data, get_clock};
+use crate::dex::quoter::{
+    constant_product_amount_in, reserve_price, ConstantProductReserves, PreparedQuote,
+    PreparedQuoteType, QuoteResult, Quoter,
+};
 use crate::dex::raydium_amm::state::AmmInfo;
 use crate::dex::utils::CheckedCeilDiv;
-use crate::dex::MintVault;
+use crate::dex::{MintVault, QuoteError};
 use solana_sdk::pubkey::Pubkey;
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Mul, Sub};
 
 #[derive(Debug)]
 pub struct RaydiumAMMQuoter;
 
 impl Quoter for RaydiumAMMQuoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
-        let amm_info = get_account_data::<AmmInfo>(pool_id)?;
-        let coin_vault_amount = get_account_data::<MintVault>(&amm_info.coin_vault)?.amount;
-        let pc_vault_amount = get_account_data::<MintVault>(&amm_info.pc_vault)?.amount;
-        let amount_in = u128::from(amount_in);
-        let swap_fee_numerator = u128::from(amm_info.swap_fee_numerator);
-        let swap_fee_denominator = u128::from(amm_info.swap_fee_denominator);
-        let swap_fee = amount_in
-            .mul(swap_fee_numerator)
-            .checked_ceil_div(swap_fee_denominator)
-            .unwrap()
-            .0;
-        let swap_in_after_deduct_fee = amount_in.sub(swap_fee);
-
-        let mint_0_amount_without_pnl =
-            u128::from(coin_vault_amount.sub(amm_info.need_take_pnl_coin));
-        let mint_1_amount_without_pnl = u128::from(pc_vault_amount.sub(amm_info.need_take_pnl_pc));
-        let amount_out = if swap_direction {
-            mint_1_amount_without_pnl
-                .mul(swap_in_after_deduct_fee)
-                .div(mint_0_amount_without_pnl.add(swap_in_after_deduct_fee))
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        RaydiumAMMPreparedQuote::new(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        Ok(PreparedQuoteType::from(RaydiumAMMPreparedQuote::new(
+            pool_id,
+        )?))
+    }
+
+    fn constant_product_reserves(
+        &self,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Option<ConstantProductReserves> {
+        let prepared = RaydiumAMMPreparedQuote::new(pool_id).ok()?;
+        let (reserve_in, reserve_out) = prepared.reserves(swap_direction);
+        let fee_in =
+            1.0 - prepared.swap_fee_numerator as f64 / prepared.swap_fee_denominator.max(1) as f64;
+        ConstantProductReserves::new(reserve_in, reserve_out, fee_in, 1.0)
+    }
+}
+
+/// 扣除待提取pnl后的储备和手续费率
+pub struct RaydiumAMMPreparedQuote {
+    swap_fee_numerator: u128,
+    swap_fee_denominator: u128,
+    mint_0_amount_without_pnl: u128,
+    mint_1_amount_without_pnl: u128,
+}
+
+impl RaydiumAMMPreparedQuote {
+    fn new(pool_id: &Pubkey) -> Result<Self, QuoteError> {
+        let amm_info = get_account_data::<AmmInfo>(pool_id).ok_or(QuoteError::MissingCache)?;
+        validate_swap_status(&amm_info)?;
+        let coin_vault_amount = get_account_data::<MintVault>(&amm_info.coin_vault)
+            .ok_or(QuoteError::MissingCache)?
+            .amount;
+        let pc_vault_amount = get_account_data::<MintVault>(&amm_info.pc_vault)
+            .ok_or(QuoteError::MissingCache)?
+            .amount;
+        Ok(Self {
+            swap_fee_numerator: u128::from(amm_info.swap_fee_numerator),
+            swap_fee_denominator: u128::from(amm_info.swap_fee_denominator),
+            mint_0_amount_without_pnl: u128::from(
+                coin_vault_amount
+                    .checked_sub(amm_info.need_take_pnl_coin)
+                    .ok_or(QuoteError::MathOverflow)?,
+            ),
+            mint_1_amount_without_pnl: u128::from(
+                pc_vault_amount
+                    .checked_sub(amm_info.need_take_pnl_pc)
+                    .ok_or(QuoteError::MathOverflow)?,
+            ),
+        })
+    }
+
+    /// (输入储备, 输出储备)
+    fn reserves(&self, swap_direction: bool) -> (u128, u128) {
+        if swap_direction {
+            (
+                self.mint_0_amount_without_pnl,
+                self.mint_1_amount_without_pnl,
+            )
         } else {
-            mint_0_amount_without_pnl
-                .mul(swap_in_after_deduct_fee)
-                .div(mint_1_amount_without_pnl.add(swap_in_after_deduct_fee))
-        };
-        Some(QuoteResult {
-            amount_out: u64::try_from(amount_out).ok()?,
+            (
+                self.mint_1_amount_without_pnl,
+                self.mint_0_amount_without_pnl,
+            )
+        }
+    }
+}
+
+impl RaydiumAMMPreparedQuote {
+    fn swap_fee(&self, amount_in: u128) -> Option<u128> {
+        Some(
+            amount_in
+                .mul(self.swap_fee_numerator)
+                .checked_ceil_div(self.swap_fee_denominator)?
+                .0,
+        )
+    }
+}
+
+impl PreparedQuote for RaydiumAMMPreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        let amount_in = u128::from(amount_in);
+        let swap_fee = self.swap_fee(amount_in).ok_or(QuoteError::MathOverflow)?;
+        let swap_in_after_deduct_fee = amount_in
+            .checked_sub(swap_fee)
+            .ok_or(QuoteError::MathOverflow)?;
+        let (reserve_in, reserve_out) = self.reserves(swap_direction);
+        let amount_out = reserve_out
+            .mul(swap_in_after_deduct_fee)
+            .checked_div(reserve_in.add(swap_in_after_deduct_fee))
+            .ok_or(QuoteError::InsufficientLiquidity)?;
+        Ok(QuoteResult {
+            amount_in: u64::try_from(amount_in).map_err(|_| QuoteError::MathOverflow)?,
+            amount_out: u64::try_from(amount_out).map_err(|_| QuoteError::MathOverflow)?,
+            lp_fee: u64::try_from(swap_fee).map_err(|_| QuoteError::MathOverflow)?,
+            start_price: reserve_price(reserve_in, reserve_out),
+            end_price: reserve_price(
+                reserve_in.add(swap_in_after_deduct_fee),
+                reserve_out.sub(amount_out),
+            ),
+            ..Default::default()
         })
     }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        let (reserve_in, reserve_out) = self.reserves(swap_direction);
+        let swap_in_after_deduct_fee =
+            constant_product_amount_in(reserve_in, reserve_out, u128::from(amount_out))
+                .ok_or(QuoteError::InsufficientLiquidity)?;
+        // 手续费向上取整，amount_in - ceil(amount_in * n / d) >= x 等价于 amount_in >= x * d / (d - n)
+        let mut amount_in = swap_in_after_deduct_fee
+            .checked_mul(self.swap_fee_denominator)
+            .ok_or(QuoteError::MathOverflow)?
+            .div_ceil(
+                self.swap_fee_denominator
+                    .checked_sub(self.swap_fee_numerator)
+                    .filter(|d| *d > 0)
+                    .ok_or(QuoteError::MathOverflow)?,
+            );
+        // checked_ceil_div在商小于0.5时取0，小数量的手续费可能比向上取整少1
+        while amount_in > swap_in_after_deduct_fee
+            && amount_in
+                - 1
+                - self
+                    .swap_fee(amount_in - 1)
+                    .ok_or(QuoteError::MathOverflow)?
+                >= swap_in_after_deduct_fee
+        {
+            amount_in -= 1;
+        }
+        Ok(QuoteResult {
+            amount_in: u64::try_from(amount_in).map_err(|_| QuoteError::MathOverflow)?,
+            amount_out,
+            lp_fee: u64::try_from(amount_in.sub(swap_in_after_deduct_fee))
+                .map_err(|_| QuoteError::MathOverflow)?,
+            start_price: reserve_price(reserve_in, reserve_out),
+            end_price: reserve_price(
+                reserve_in.add(swap_in_after_deduct_fee),
+                reserve_out.sub(u128::from(amount_out)),
+            ),
+            ..Default::default()
+        })
+    }
+}
+
+/// Initialized、SwapOnly可以swap，WaitingTrade到pool_open_time后才可以swap
+fn validate_swap_status(amm_info: &AmmInfo) -> Result<(), QuoteError> {
+    let status = amm_info.status;
+    let pool_open_time = amm_info.pool_open_time;
+    match status {
+        1 | 6 => Ok(()),
+        7 => {
+            let clock = get_clock().ok_or(QuoteError::MissingClock)?;
+            if (clock.unix_timestamp as u64) < pool_open_time {
+                Err(QuoteError::PoolDisabled)
+            } else {
+                Ok(())
+            }
+        }
+        _ => Err(QuoteError::PoolDisabled),
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::dex::raydium_amm::quote::RaydiumAMMQuoter;
-    use crate::dex::{init_global_cache, AmmInfo, GlobalCache, MintVault, Quoter};
+    use crate::dex::quoter::assert_exact_out_round_trip;
+    use crate::dex::raydium_amm::quote::{RaydiumAMMPreparedQuote, RaydiumAMMQuoter};
+    use crate::dex::{
+        init_global_cache, AmmInfo, GlobalCache, MintVault, PreparedQuote, QuoteError, Quoter,
+    };
     use crate::dex_data::DexJson;
     use solana_sdk::pubkey::Pubkey;
     use std::str::FromStr;
@@ -70,14 +221,25 @@ mod test {
             swap_fee_denominator: 10_000,
             coin_vault: dex_json.vault_a,
             pc_vault: dex_json.vault_b,
+            // SwapOnly
+            status: 6,
             ..Default::default()
         };
         let data = bytemuck::bytes_of(&amm_info);
         let static_data = &data[0..144];
         let dynamic_data = &data[144..];
-        let mut global_cache = GlobalCache::init();
+        let global_cache = GlobalCache::init();
         global_cache.upsert_static(dex_json.pool, static_data.to_vec());
         global_cache.upsert_dynamic(dex_json.pool, dynamic_data.to_vec());
+        // 禁用的池子
+        let disabled_pool = Pubkey::new_unique();
+        let disabled_amm_info = AmmInfo {
+            status: 2,
+            ..amm_info
+        };
+        let data = bytemuck::bytes_of(&disabled_amm_info);
+        global_cache.upsert_static(disabled_pool, data[0..144].to_vec());
+        global_cache.upsert_dynamic(disabled_pool, data[144..].to_vec());
 
         let coin_vault_amount = MintVault {
             amount: 26_324 * 10_u64.pow(9),
@@ -98,6 +260,68 @@ mod test {
             .quote(10_u64.pow(9), true, &dex_json.pool)
             .unwrap();
         assert_eq!(quote_result.amount_out, 133552322);
+
+        let exact_out_result = RaydiumAMMQuoter
+            .quote_exact_out(133552322, true, &dex_json.pool)
+            .unwrap();
+        assert!(exact_out_result.amount_in <= 10_u64.pow(9));
+        let round_trip = RaydiumAMMQuoter
+            .quote(exact_out_result.amount_in, true, &dex_json.pool)
+            .unwrap();
+        assert!(round_trip.amount_out >= 133552322);
+        let less_in = RaydiumAMMQuoter
+            .quote(exact_out_result.amount_in - 1, true, &dex_json.pool)
+            .unwrap();
+        assert!(less_in.amount_out < 133552322);
+
+        // 预解码的状态对任意数量、两个方向的结果与直接quote一致
+        let prepared = RaydiumAMMQuoter.prepare(&dex_json.pool).unwrap();
+        for (amount_in, swap_direction) in [(10_u64.pow(9), true), (10_u64.pow(8), false)] {
+            assert_eq!(
+                prepared
+                    .quote(amount_in, swap_direction)
+                    .unwrap()
+                    .amount_out,
+                RaydiumAMMQuoter
+                    .quote(amount_in, swap_direction, &dex_json.pool)
+                    .unwrap()
+                    .amount_out
+            );
+        }
+
+        // 缓存中没有的池子
+        assert_eq!(
+            RaydiumAMMQuoter
+                .quote(10_u64.pow(9), true, &Pubkey::new_unique())
+                .unwrap_err(),
+            QuoteError::MissingCache
+        );
+        assert_eq!(
+            RaydiumAMMQuoter
+                .quote(10_u64.pow(9), true, &disabled_pool)
+                .unwrap_err(),
+            QuoteError::PoolDisabled
+        );
         Ok(())
     }
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        let prepared = RaydiumAMMPreparedQuote {
+            swap_fee_numerator: 25,
+            swap_fee_denominator: 10_000,
+            mint_0_amount_without_pnl: 26_324 * 10_u128.pow(9),
+            mint_1_amount_without_pnl: 3_524_576 * 10_u128.pow(6),
+        };
+        let amounts_in = [1, 999, 10_u64.pow(6), 10_u64.pow(9), 10_u64.pow(13)];
+        assert_exact_out_round_trip(&prepared, true, &amounts_in);
+        assert_exact_out_round_trip(&prepared, false, &amounts_in);
+        // 输出超过储备
+        assert_eq!(
+            prepared
+                .quote_exact_out(3_524_576 * 10_u64.pow(6), true)
+                .unwrap_err(),
+            QuoteError::InsufficientLiquidity
+        );
+    }
 }
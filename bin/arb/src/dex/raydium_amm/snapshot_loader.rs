@@ -20,7 +20,7 @@ impl RaydiumAmmSnapshotInitializer {
         &self,
         pool_accounts: Vec<Pubkey>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let mut all_pool_account_data = self
             .get_account_data_with_data_slice(
                 pool_accounts,
@@ -28,19 +28,19 @@ impl RaydiumAmmSnapshotInitializer {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| {
             account.static_slice_data.as_ref().is_some()
                 && account.dynamic_slice_data.as_ref().is_some()
         });
-        all_pool_account_data
+        Ok(all_pool_account_data)
     }
 
     async fn get_mint_vault_snapshot_data(
         &self,
         vault_accounts: Vec<Pubkey>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let mut all_vault_account_data = self
             .get_account_data_with_data_slice(
                 vault_accounts,
@@ -48,9 +48,9 @@ impl RaydiumAmmSnapshotInitializer {
                 AccountType::MintVault,
                 rpc_client,
             )
-            .await;
+            .await?;
         all_vault_account_data.retain(|account| account.dynamic_slice_data.as_ref().is_some());
-        all_vault_account_data
+        Ok(all_vault_account_data)
     }
 }
 
@@ -60,13 +60,13 @@ impl SnapshotInitializer for RaydiumAmmSnapshotInitializer {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| json.owner == RAYDIUM_AMM_PROGRAM_ID)
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【RaydiumAMM】开始初始化Snapshot...");
         let mut pool_accounts = AHashSet::with_capacity(dex_data.len());
@@ -81,21 +81,21 @@ impl SnapshotInitializer for RaydiumAmmSnapshotInitializer {
                 pool_accounts.into_iter().collect::<Vec<_>>(),
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         let all_vault_account_data = self
             .get_mint_vault_snapshot_data(
                 vault_accounts.into_iter().collect::<Vec<_>>(),
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         info!(
             "【RaydiumAmm】初始化Snapshot完毕, 初始化池子数量 : {}",
             all_pool_account_data.len()
         );
-        all_pool_account_data
+        Ok(all_pool_account_data
             .into_iter()
             .chain(all_vault_account_data.into_iter())
-            .collect()
+            .collect())
     }
 
     fn print_snapshot(&self, dex_json: &[DexJson]) -> anyhow::Result<()> {
@@ -199,7 +199,7 @@ mod test {
         let rpc_client = Arc::new(RpcClient::new_mock_with_mocks("success".to_string(), mocks));
         let mut snapshot_data = RaydiumAmmSnapshotInitializer
             .get_mint_vault_snapshot_data(vec![dex_json.vault_a], rpc_client.clone())
-            .await;
+            .await?;
         assert_eq!(snapshot_data.len(), 1);
         let data_slice = snapshot_data.pop().unwrap();
         assert_eq!(data_slice.account_key, dex_json.vault_a);
@@ -223,8 +223,10 @@ mod test {
             state_data: old_state::pool::StateData {
                 need_take_pnl_coin: 10,
                 need_take_pnl_pc: 20,
+                pool_open_time: 1_700_000_000,
                 ..Default::default()
             },
+            status: 7,
             coin_vault: dex_json.vault_a,
             pc_vault: dex_json.vault_b,
             coin_vault_mint: dex_json.mint_a,
@@ -250,7 +252,7 @@ mod test {
         let rpc_client = Arc::new(RpcClient::new_mock_with_mocks("failed".to_string(), mocks));
         let mut pool_snapshot_data = RaydiumAmmSnapshotInitializer
             .get_pool_snapshot_data(vec![dex_json.pool], rpc_client.clone())
-            .await;
+            .await?;
         assert_eq!(pool_snapshot_data.len(), 1);
         let data_slice = pool_snapshot_data.pop().unwrap();
         assert_eq!(data_slice.account_key, dex_json.pool);
@@ -266,6 +268,12 @@ mod test {
         let slice_data = slice_amm_info.need_take_pnl_pc;
         let origin_data = amm_info.state_data.need_take_pnl_pc;
         assert_eq!(slice_data, origin_data);
+        let slice_data = slice_amm_info.status;
+        let origin_data = amm_info.status;
+        assert_eq!(slice_data, origin_data);
+        let slice_data = slice_amm_info.pool_open_time;
+        let origin_data = amm_info.state_data.pool_open_time;
+        assert_eq!(slice_data, origin_data);
         let slice_data = slice_amm_info.swap_fee_numerator;
         let origin_data = amm_info.fees.swap_fee_numerator;
         assert_eq!(slice_data, origin_data);
@@ -305,7 +313,7 @@ mod test {
                 &mut dex_json,
                 Arc::new(RpcClient::new_mock("aa".to_string())),
             )
-            .await;
+            .await?;
         assert_eq!(data.len(), 0);
         Ok(())
     }
@@ -25,6 +25,10 @@ pub struct AmmInfo {
     // dynamic data 订阅的属性
     pub need_take_pnl_coin: u64,
     pub need_take_pnl_pc: u64,
+    // AmmStatus
+    pub status: u64,
+    // status为WaitingTrade时，到该时间后才可以swap
+    pub pool_open_time: u64,
     // // dynamic data 金库
     // pub coin_vault_amount: u64,
     // pub pc_vault_amount: u64,
@@ -52,6 +56,8 @@ impl FromCache for AmmInfo {
 
             let need_take_pnl_coin = read_from::<u64>(&pool_dynamic_data[0..8]);
             let need_take_pnl_pc = read_from::<u64>(&pool_dynamic_data[8..16]);
+            let status = read_from::<u64>(&pool_dynamic_data[16..24]);
+            let pool_open_time = read_from::<u64>(&pool_dynamic_data[24..32]);
             Ok(Self {
                 swap_fee_numerator,
                 swap_fee_denominator,
@@ -61,6 +67,8 @@ impl FromCache for AmmInfo {
                 pc_vault_mint,
                 need_take_pnl_coin,
                 need_take_pnl_pc,
+                status,
+                pool_open_time,
             })
         }
     }
@@ -5,7 +5,7 @@ use tokio::sync::OnceCell;
 
 // ========================= dynamic data 账户订阅的数据切片 =========================
 // clmm pool
-static DYNAMIC_RAYDIUM_CLMM_POOL_SLICE: OnceCell<([(usize, usize); 5], usize)> =
+static DYNAMIC_RAYDIUM_CLMM_POOL_SLICE: OnceCell<([(usize, usize); 7], usize)> =
     OnceCell::const_new();
 // clmm bitmap extension
 static DYNAMIC_RAYDIUM_CLMM_BITMAP_EXTENSION_SLICE: OnceCell<([(usize, usize); 1], usize)> =
@@ -149,8 +149,12 @@ fn init_pool_data_slice() -> anyhow::Result<()> {
                 (904, 904 + 128),
                 // recent_epoch
                 (1088, 1088 + 8),
+                // status
+                (389, 389 + 1),
+                // open_time
+                (1080, 1080 + 8),
             ],
-            16 + 16 + 4 + 128 + 8,
+            16 + 16 + 4 + 128 + 8 + 1 + 8,
         )
     })?;
     Ok(())
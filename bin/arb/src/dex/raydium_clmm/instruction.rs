@@ -4,7 +4,7 @@ use crate::dex::raydium_clmm::state::{
 use crate::dex::raydium_clmm::utils::load_cur_and_next_specify_count_tick_array_key;
 use crate::dex::swap_instruction::{InstructionMaterial, InstructionMaterialConverter};
 use crate::dex::DexType::RaydiumCLMM;
-use crate::dex::{ATA_PROGRAM_ID, MINT_PROGRAM_ID};
+use crate::dex::{QuoteResult, ATA_PROGRAM_ID, MINT_PROGRAM_ID};
 use crate::dex::global_cache::get_alt;
 use crate::metadata::{get_keypair, MintAtaPair};
 use anyhow::{anyhow, Result};
@@ -19,6 +19,7 @@ impl InstructionMaterialConverter for RaydiumCLMMInstructionMaterialConverter {
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        quote_result: Option<&QuoteResult>,
     ) -> Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let pool_state = crate::dex::global_cache::get_account_data::<PoolState>(pool_id)
@@ -71,24 +72,24 @@ impl InstructionMaterialConverter for RaydiumCLMMInstructionMaterialConverter {
         accounts.push(AccountMeta::new_readonly(MINT_PROGRAM_ID, false));
         // 10.current tick array
         let bit_map_extension_key = pda_bit_map_extension_key(pool_id);
-        let mut tick_arrays = load_cur_and_next_specify_count_tick_array_key(
-            2,
-            pool_id,
-            &pool_state,
-            &crate::dex::global_cache::get_account_data::<TickArrayBitmapExtension>(
-                &bit_map_extension_key,
-            ),
-            swap_direction,
-        )
-        .map_or(
-            Err(anyhow!("生成指令，获取TickArray失败")),
-            |keys| {
-                Ok(keys
-                    .into_iter()
-                    .map(|k| AccountMeta::new(k, false))
-                    .collect::<Vec<_>>())
-            },
-        )?;
+        // 优先使用quote实际遍历的tick array
+        let tick_array_keys = match quote_result.filter(|quote| !quote.tick_arrays.is_empty()) {
+            Some(quote) => quote.tick_arrays.clone(),
+            None => load_cur_and_next_specify_count_tick_array_key(
+                2,
+                pool_id,
+                &pool_state,
+                &crate::dex::global_cache::get_account_data::<TickArrayBitmapExtension>(
+                    &bit_map_extension_key,
+                ),
+                swap_direction,
+            )
+            .ok_or(anyhow!("生成指令，获取TickArray失败"))?,
+        };
+        let mut tick_arrays = tick_array_keys
+            .into_iter()
+            .map(|k| AccountMeta::new(k, false))
+            .collect::<Vec<_>>();
         accounts.push(tick_arrays.remove(0));
         // 11.bitmap_extension
         accounts.push(AccountMeta::new(bit_map_extension_key, false));
@@ -108,6 +108,8 @@ impl TryInto<crate::dex::raydium_clmm::PoolState> for PoolState {
             tick_current: self.tick_current,
             tick_array_bitmap: self.tick_array_bitmap,
             recent_epoch: self.recent_epoch,
+            status: self.status,
+            open_time: self.open_time,
         })
     }
 }
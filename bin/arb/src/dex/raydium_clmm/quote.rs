@@ -1,39 +1,165 @@
-use crate::dex::quoter::{QuoteResult, Quoter};
+use crate::dex::quoter::{
+    sqrt_x64_price, tick_array_depth, PreparedQuote, PreparedQuoteType, QuoteResult, Quoter,
+};
 use crate::dex::raydium_clmm::state::{
-    pda_bit_map_extension_key, AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState,
+    pda_bit_map_extension_key, AmmConfig, PoolState, PoolStatusBitIndex, TickArrayBitmapExtension,
+    TickArrayState, FEE_RATE_DENOMINATOR_VALUE,
 };
 use crate::dex::raydium_clmm::utils;
 use crate::dex::raydium_clmm::utils::load_cur_and_next_specify_count_tick_array_key;
-use crate::dex::global_cache::get_account_data;
+use crate::dex::raydium_clmm::utils::{get_tick_array_key, SwapComputeResult};
+use crate::dex::global_cache::{get_account_data, get_clock};
+use crate::dex::QuoteError;
 use solana_sdk::pubkey::Pubkey;
 use std::collections::VecDeque;
-use tracing::error;
+use std::sync::OnceLock;
+
+/// utils中swap计算返回的错误，tick array用完后下一个tick array的start index不匹配
+const SWAP_ERRORS: &[(&str, QuoteError)] = &[
+    ("amountSpecified must not be 0", QuoteError::InvalidAmount),
+    (
+        "tick array start tick index does not match",
+        QuoteError::MissingTickArray,
+    ),
+    (
+        "tick array start tick index out of range limit",
+        QuoteError::InsufficientLiquidity,
+    ),
+    (
+        "liquidity insufficient for exact out",
+        QuoteError::InsufficientLiquidity,
+    ),
+];
 
 #[derive(Debug)]
 pub struct RaydiumCLMMQuoter;
 
 impl Quoter for RaydiumCLMMQuoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.prepare(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        let pool_state = get_account_data::<PoolState>(pool_id).ok_or(QuoteError::MissingCache)?;
+        let clock = get_clock().ok_or(QuoteError::MissingClock)?;
+        validate_swap_status(&pool_state, clock.unix_timestamp as u64)?;
+        Ok(PreparedQuoteType::from(RaydiumCLMMPreparedQuote {
+            pool_id: *pool_id,
+            amm_config: get_amm_config(&pool_state.amm_config).ok_or(QuoteError::MissingCache)?,
+            bitmap_extension: Some(get_bitmap_extension(pool_id).ok_or(QuoteError::MissingCache)?),
+            pool_state,
+            tick_arrays: Default::default(),
+        }))
+    }
+
+    fn marginal_price(&self, swap_direction: bool, pool_id: &Pubkey) -> Option<f64> {
         let pool_state = get_account_data::<PoolState>(pool_id)?;
-        let bitmap_extension = Some(get_bitmap_extension(pool_id)?);
-        let mut tick_arrays =
-            get_tick_arrays(pool_id, &pool_state, &bitmap_extension, swap_direction, 2)?;
-        match utils::get_out_put_amount_and_remaining_accounts(
-            amount_in,
+        let trade_fee_rate = get_amm_config(&pool_state.amm_config)?.trade_fee_rate;
+        Some(
+            sqrt_x64_price(pool_state.sqrt_price_x64, swap_direction)
+                * (1.0 - trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64),
+        )
+    }
+}
+
+/// 池子、AmmConfig和bitmap extension，tick array按方向在第一次quote时读取
+pub struct RaydiumCLMMPreparedQuote {
+    pool_id: Pubkey,
+    pool_state: PoolState,
+    amm_config: AmmConfig,
+    bitmap_extension: Option<TickArrayBitmapExtension>,
+    // 下标为swap_direction
+    tick_arrays: [OnceLock<Option<VecDeque<TickArrayState>>>; 2],
+}
+
+impl RaydiumCLMMPreparedQuote {
+    /// is_base_input为true时amount是输入数量，否则是输出数量
+    fn swap(
+        &self,
+        amount: u64,
+        swap_direction: bool,
+        is_base_input: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        // swap会消耗tick array，每次quote使用副本
+        let mut tick_arrays = self.tick_arrays[usize::from(swap_direction)]
+            .get_or_init(|| {
+                get_tick_arrays(
+                    &self.pool_id,
+                    &self.pool_state,
+                    &self.bitmap_extension,
+                    swap_direction,
+                )
+            })
+            .clone()
+            .ok_or(QuoteError::MissingTickArray)?;
+        let swap_compute_result = utils::get_out_put_amount_and_remaining_accounts(
+            amount,
             None,
             swap_direction,
-            true,
-            &get_amm_config(&pool_state.amm_config)?,
-            &pool_state,
-            &bitmap_extension,
+            is_base_input,
+            &self.amm_config,
+            &self.pool_state,
+            &self.bitmap_extension,
             &mut tick_arrays,
-        ) {
-            Ok((amount_out, _, _)) => Some(QuoteResult { amount_out }),
-            Err(_e) => {
-                // error!("【RaydiumCLMM】池子[{}]quote失败，原因 : {}", pool_id, e);
-                None
-            }
-        }
+        )
+        .map_err(|e| QuoteError::classify(&e, SWAP_ERRORS))?;
+        let (amount_in, amount_out) = if is_base_input {
+            (amount, swap_compute_result.amount_calculated)
+        } else {
+            (swap_compute_result.amount_calculated, amount)
+        };
+        Ok(to_quote_result(
+            &self.pool_id,
+            &self.pool_state,
+            swap_direction,
+            amount_in,
+            amount_out,
+            swap_compute_result,
+        ))
+    }
+}
+
+impl PreparedQuote for RaydiumCLMMPreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        self.swap(amount_in, swap_direction, true)
+    }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.swap(amount_out, swap_direction, false)
+    }
+}
+
+fn to_quote_result(
+    pool_id: &Pubkey,
+    pool_state: &PoolState,
+    swap_direction: bool,
+    amount_in: u64,
+    amount_out: u64,
+    swap_compute_result: SwapComputeResult,
+) -> QuoteResult {
+    QuoteResult {
+        amount_in,
+        amount_out,
+        lp_fee: swap_compute_result.fee_amount,
+        protocol_fee: swap_compute_result.protocol_fee + swap_compute_result.fund_fee,
+        start_price: sqrt_x64_price(pool_state.sqrt_price_x64, swap_direction),
+        end_price: sqrt_x64_price(swap_compute_result.sqrt_price_x64, swap_direction),
+        crossed: swap_compute_result.ticks_crossed,
+        tick_arrays: swap_compute_result
+            .tick_array_start_indexes
+            .iter()
+            .map(|start_index| get_tick_array_key(pool_id, *start_index))
+            .collect(),
+        ..Default::default()
     }
 }
 
@@ -47,35 +173,143 @@ fn get_bitmap_extension(pool_id: &Pubkey) -> Option<TickArrayBitmapExtension> {
     ))
 }
 
+/// 沿swap方向加载已初始化的tick array，数量上限见 TickArrayDepth
+///
+/// 只取缓存中连续存在的部分，流动性是否足够由quote判断
 fn get_tick_arrays(
     pool_id: &Pubkey,
     pool_state: &PoolState,
     tick_array_bitmap_extension: &Option<TickArrayBitmapExtension>,
     swap_direction: bool,
-    take_count: u8,
 ) -> Option<VecDeque<TickArrayState>> {
     let tick_array_keys = load_cur_and_next_specify_count_tick_array_key(
-        take_count,
+        tick_array_depth().raydium_clmm - 1,
         pool_id,
         pool_state,
         tick_array_bitmap_extension,
         swap_direction,
-    );
-    if tick_array_keys.as_ref()?.is_empty() {
-        return None;
-    }
-    let expect_count = tick_array_keys.as_ref()?.len();
-    let deque = tick_array_keys?
-        .into_iter()
-        .filter_map(|key| {
-            let account_data = crate::dex::global_cache::get_account_data::<TickArrayState>(&key);
-            // info!("key : {:?}\n{:#?}", key, account_data);
-            account_data
-        })
+    )?;
+    let deque = tick_array_keys
+        .iter()
+        .map_while(|key| get_account_data::<TickArrayState>(key))
         .collect::<VecDeque<_>>();
-    if expect_count != deque.len() {
-        None
-    } else {
-        Some(deque)
+    (!deque.is_empty()).then_some(deque)
+}
+
+/// 禁止swap或未过open_time的池子不能swap
+fn validate_swap_status(pool_state: &PoolState, block_timestamp: u64) -> Result<(), QuoteError> {
+    let open_time = pool_state.open_time;
+    if !pool_state.get_status_by_bit(PoolStatusBitIndex::Swap) || block_timestamp <= open_time {
+        return Err(QuoteError::PoolDisabled);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::quoter::{assert_exact_out_round_trip, PreparedQuote};
+    use crate::dex::raydium_clmm::quote::{validate_swap_status, RaydiumCLMMPreparedQuote};
+    use crate::dex::raydium_clmm::state::{
+        AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState,
+        EXTENSION_TICKARRAY_BITMAP_SIZE,
+    };
+    use crate::dex::raydium_clmm::tick_math;
+    use crate::dex::QuoteError;
+    use solana_sdk::pubkey::Pubkey;
+    use std::collections::VecDeque;
+    use std::sync::OnceLock;
+
+    #[test]
+    fn test_validate_swap_status() {
+        let pool_state = PoolState {
+            open_time: 1_700_000_000,
+            ..Default::default()
+        };
+        assert_eq!(validate_swap_status(&pool_state, 1_700_000_001), Ok(()));
+        // 未过open_time
+        assert_eq!(
+            validate_swap_status(&pool_state, 1_700_000_000),
+            Err(QuoteError::PoolDisabled)
+        );
+        // bit4禁止swap，其他位不影响swap
+        let disabled = PoolState {
+            status: 1 << 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_swap_status(&disabled, 1_700_000_001),
+            Err(QuoteError::PoolDisabled)
+        );
+        let deposit_disabled = PoolState {
+            status: 0b1111,
+            ..Default::default()
+        };
+        assert_eq!(
+            validate_swap_status(&deposit_disabled, 1_700_000_001),
+            Ok(())
+        );
+    }
+
+    /// tick_spacing为1，当前tick为30，流动性区间为tick array [0, 60)中的[0, 59]
+    fn prepared_quote() -> RaydiumCLMMPreparedQuote {
+        let liquidity = 1_000_000_000_000u128;
+        let mut tick_array = TickArrayState::default();
+        tick_array.ticks[0].liquidity_net = liquidity as i128;
+        tick_array.ticks[0].liquidity_gross = liquidity;
+        tick_array.ticks[59].tick = 59;
+        tick_array.ticks[59].liquidity_net = -(liquidity as i128);
+        tick_array.ticks[59].liquidity_gross = liquidity;
+        let mut tick_array_bitmap = [0u64; 16];
+        // start index为0的tick array对应bitmap第512位
+        tick_array_bitmap[8] = 1;
+        let tick_arrays = [OnceLock::new(), OnceLock::new()];
+        for direction in tick_arrays.iter() {
+            direction
+                .set(Some(VecDeque::from([tick_array.clone()])))
+                .unwrap();
+        }
+        let pool_id = Pubkey::new_unique();
+        RaydiumCLMMPreparedQuote {
+            pool_id,
+            pool_state: PoolState {
+                tick_spacing: 1,
+                tick_current: 30,
+                sqrt_price_x64: tick_math::get_sqrt_price_at_tick(30).unwrap(),
+                liquidity,
+                tick_array_bitmap,
+                ..Default::default()
+            },
+            amm_config: AmmConfig {
+                trade_fee_rate: 2500,
+                protocol_fee_rate: 120_000,
+                fund_fee_rate: 40_000,
+                ..Default::default()
+            },
+            bitmap_extension: Some(TickArrayBitmapExtension {
+                pool_id,
+                positive_tick_array_bitmap: [[0; 8]; EXTENSION_TICKARRAY_BITMAP_SIZE],
+                negative_tick_array_bitmap: [[0; 8]; EXTENSION_TICKARRAY_BITMAP_SIZE],
+            }),
+            tick_arrays,
+        }
+    }
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        let prepared = prepared_quote();
+        for swap_direction in [true, false] {
+            assert_exact_out_round_trip(
+                &prepared,
+                swap_direction,
+                &[1, 999, 1_000_000, 100_000_000],
+            );
+        }
+        // 超出区间内的流动性
+        assert_eq!(
+            prepared
+                .quote_exact_out(1_000_000_000_000, true)
+                .unwrap_err(),
+            QuoteError::InsufficientLiquidity
+        );
     }
 }
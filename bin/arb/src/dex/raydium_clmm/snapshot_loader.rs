@@ -24,13 +24,13 @@ impl SnapshotInitializer for RaydiumCLMMSnapshotInitializer {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| json.owner == RAYDIUM_CLMM_PROGRAM_ID)
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【RaydiumCLMM】开始初始化Snapshot...");
         let mut invalid_pool = AHashSet::with_capacity(dex_data.len());
@@ -47,7 +47,7 @@ impl SnapshotInitializer for RaydiumCLMMSnapshotInitializer {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none()
                 || account.dynamic_slice_data.as_ref().is_none()
@@ -65,7 +65,7 @@ impl SnapshotInitializer for RaydiumCLMMSnapshotInitializer {
                 &all_pool_account_data,
                 &mut invalid_pool,
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| !invalid_pool.contains(&account.account_key));
         // bitmap extension
         let all_bitmap_extension_account_data = self
@@ -74,7 +74,7 @@ impl SnapshotInitializer for RaydiumCLMMSnapshotInitializer {
                 &all_pool_account_data,
                 &mut invalid_pool,
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| !invalid_pool.contains(&account.account_key));
         // tick array
         let tick_array_account_data = self
@@ -85,7 +85,7 @@ impl SnapshotInitializer for RaydiumCLMMSnapshotInitializer {
                 10,
                 &mut invalid_pool,
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| !invalid_pool.contains(&account.account_key));
         dex_json.retain(|json| !invalid_pool.contains(&json.pool));
         info!(
@@ -93,15 +93,14 @@ impl SnapshotInitializer for RaydiumCLMMSnapshotInitializer {
             all_pool_account_data.len()
         );
         if dex_json.is_empty() {
-            vec![]
-        } else {
-            all_pool_account_data
-                .into_iter()
-                .chain(all_amm_config_account_data.into_iter())
-                .chain(all_bitmap_extension_account_data.into_iter())
-                .chain(tick_array_account_data.into_iter())
-                .collect::<Vec<_>>()
+            return Ok(vec![]);
         }
+        Ok(all_pool_account_data
+            .into_iter()
+            .chain(all_amm_config_account_data.into_iter())
+            .chain(all_bitmap_extension_account_data.into_iter())
+            .chain(tick_array_account_data.into_iter())
+            .collect::<Vec<_>>())
     }
 
     // #[cfg(feature = "print_slice_data")]
@@ -158,7 +157,7 @@ impl RaydiumCLMMSnapshotInitializer {
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
         invalid_pool: &mut AHashSet<Pubkey>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let mut all_amm_config_accounts = AHashMap::with_capacity(50);
         for account in all_pool_account_data {
             // amm_config
@@ -180,7 +179,7 @@ impl RaydiumCLMMSnapshotInitializer {
                 AccountType::AmmConfig,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_amm_config_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none_or(|data| {
                 data.len()
@@ -200,7 +199,7 @@ impl RaydiumCLMMSnapshotInitializer {
                 true
             }
         });
-        all_amm_config_account_data
+        Ok(all_amm_config_account_data)
     }
 
     async fn get_bitmap_extension_accounts(
@@ -208,7 +207,7 @@ impl RaydiumCLMMSnapshotInitializer {
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
         invalid_pool: &mut AHashSet<Pubkey>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         // bitmap_extension
         let mut all_bitmap_extension_accounts = all_pool_account_data
             .iter()
@@ -232,7 +231,7 @@ impl RaydiumCLMMSnapshotInitializer {
                 AccountType::TickArrayBitmap,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_bitmap_extension_account_data.retain(|account| {
             if account.dynamic_slice_data.as_ref().is_none_or(|data| {
                 data.len()
@@ -255,7 +254,7 @@ impl RaydiumCLMMSnapshotInitializer {
                 true
             }
         });
-        all_bitmap_extension_account_data
+        Ok(all_bitmap_extension_account_data)
     }
 
     async fn get_tick_array_accounts(
@@ -265,7 +264,7 @@ impl RaydiumCLMMSnapshotInitializer {
         all_bitmap_extension_account_data: &[AccountDataSlice],
         load_count: u8,
         invalid_pool: &mut AHashSet<Pubkey>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let tick_array_state_keys = all_pool_account_data
             .iter()
             .filter_map(|account| {
@@ -331,7 +330,7 @@ impl RaydiumCLMMSnapshotInitializer {
                 AccountType::TickArray,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_tick_array_state_account_data.retain(|account| {
             if account.dynamic_slice_data.as_ref().is_none() {
                 invalid_pool.insert(
@@ -345,6 +344,6 @@ impl RaydiumCLMMSnapshotInitializer {
                 true
             }
         });
-        all_tick_array_state_account_data
+        Ok(all_tick_array_state_account_data)
     }
 }
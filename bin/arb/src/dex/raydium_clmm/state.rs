@@ -84,6 +84,18 @@ pub struct PoolState {
     /// Packed initialized tick array state
     pub tick_array_bitmap: [u64; 16],
     pub recent_epoch: u64,
+    /// bit4为1时禁止swap
+    pub status: u8,
+    /// 晚于该时间才可以swap
+    pub open_time: u64,
+}
+
+pub enum PoolStatusBitIndex {
+    OpenPositionOrIncreaseLiquidity,
+    DecreaseLiquidity,
+    CollectFee,
+    CollectReward,
+    Swap,
 }
 
 impl FromCache for PoolState {
@@ -123,6 +135,8 @@ impl PoolState {
             let recent_epoch = ptr::read_unaligned(
                 dynamic_data[16 + 16 + 4 + 8 * 16..16 + 16 + 4 + 8 * 16 + 8].as_ptr() as *const u64,
             );
+            let status = read_from::<u8>(&dynamic_data[172..173]);
+            let open_time = read_from::<u64>(&dynamic_data[173..181]);
             Self {
                 amm_config,
                 token_mint_0,
@@ -136,10 +150,18 @@ impl PoolState {
                 tick_current,
                 tick_array_bitmap,
                 recent_epoch,
+                status,
+                open_time,
             }
         }
     }
 
+    /// 对应的位为0时启用
+    pub fn get_status_by_bit(&self, bit: PoolStatusBitIndex) -> bool {
+        let status = 1u8 << (bit as u8);
+        self.status & status == 0
+    }
+
     pub fn get_first_initialized_tick_array(
         &self,
         tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
@@ -555,7 +577,7 @@ pub fn pda_bit_map_extension_key(pool_id: &Pubkey) -> Pubkey {
     )
     .0
 }
-const EXTENSION_TICKARRAY_BITMAP_SIZE: usize = 14;
+pub(crate) const EXTENSION_TICKARRAY_BITMAP_SIZE: usize = 14;
 #[repr(C, packed)]
 #[derive(Debug, Clone)]
 // #[serde_as]
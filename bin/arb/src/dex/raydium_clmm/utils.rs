@@ -33,6 +33,23 @@ pub struct SwapState {
     pub liquidity: u128,
     pub fee_amount: u64,
 }
+/// swap_compute的计算结果
+#[derive(Debug)]
+pub struct SwapComputeResult {
+    // exact in时为输出数量，exact out时为输入数量(含手续费)
+    pub amount_calculated: u64,
+    // 留给LP的手续费
+    pub fee_amount: u64,
+    pub protocol_fee: u64,
+    pub fund_fee: u64,
+    // swap后的sqrt(price)
+    pub sqrt_price_x64: u128,
+    // 跨越的已初始化tick数量
+    pub ticks_crossed: u32,
+    // 遍历到的tick array的start index
+    pub tick_array_start_indexes: VecDeque<i32>,
+}
+
 #[derive(Default)]
 struct StepComputations {
     // the price at the beginning of the step
@@ -60,7 +77,7 @@ pub fn get_out_put_amount_and_remaining_accounts(
     pool_state: &PoolState,
     tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
     tick_arrays: &mut VecDeque<TickArrayState>,
-) -> Result<(u64, u64, VecDeque<i32>)> {
+) -> Result<SwapComputeResult> {
     // let transfer_fee = get_transfer_fee(
     //     if zero_for_one {
     //         &pool_state.token_mint_0
@@ -74,7 +91,7 @@ pub fn get_out_put_amount_and_remaining_accounts(
     let (is_pool_current_tick_array, current_vaild_tick_array_start_index) =
         pool_state.get_first_initialized_tick_array(tickarray_bitmap_extension, zero_for_one)?;
 
-    let (amount_calculated, fee_amount, tick_array_start_index_vec) = swap_compute(
+    let swap_compute_result = swap_compute(
         pool_config,
         zero_for_one,
         is_base_input,
@@ -98,7 +115,7 @@ pub fn get_out_put_amount_and_remaining_accounts(
     //     amount_calculated,
     // );
     // Ok((amount_calculated - transfer_fee, fee_amount, tick_array_start_index_vec))
-    Ok((amount_calculated, fee_amount, tick_array_start_index_vec))
+    Ok(swap_compute_result)
 }
 
 fn swap_compute(
@@ -113,7 +130,7 @@ fn swap_compute(
     pool_state: &PoolState,
     tickarray_bitmap_extension: &Option<TickArrayBitmapExtension>,
     tick_arrays: &mut VecDeque<TickArrayState>,
-) -> Result<(u64, u64, VecDeque<i32>)> {
+) -> Result<SwapComputeResult> {
     if amount_specified == 0 {
         return Result::Err(anyhow!("amountSpecified must not be 0"));
     }
@@ -164,6 +181,9 @@ fn swap_compute(
     let mut tick_array_start_index_vec = VecDeque::new();
     tick_array_start_index_vec.push_back(tick_array_current.start_tick_index);
     let mut loop_count = 0;
+    let mut protocol_fee = 0u64;
+    let mut fund_fee = 0u64;
+    let mut ticks_crossed = 0u32;
     // loop across ticks until input liquidity is consumed, or the limit price is reached
     while state.amount_specified_remaining != 0
         && state.sqrt_price_x64 != sqrt_price_limit_x64
@@ -284,6 +304,7 @@ fn swap_compute(
                 .unwrap()
                 .as_u64();
             step.fee_amount = step.fee_amount.checked_sub(delta).unwrap();
+            protocol_fee = protocol_fee.checked_add(delta).unwrap();
         }
         if amm_config.fund_fee_rate > 0 {
             let delta = U128::from(step_fee_amount)
@@ -293,6 +314,7 @@ fn swap_compute(
                 .unwrap()
                 .as_u64();
             step.fee_amount = step.fee_amount.checked_sub(delta).unwrap();
+            fund_fee = fund_fee.checked_add(delta).unwrap();
         }
         if state.liquidity > 0 {
             state.fee_amount = state.fee_amount.checked_add(step.fee_amount).unwrap();
@@ -300,6 +322,7 @@ fn swap_compute(
         if state.sqrt_price_x64 == step.sqrt_price_next_x64 {
             // if the tick is initialized, run the tick transition
             if step.initialized {
+                ticks_crossed += 1;
                 let mut liquidity_net = next_initialized_tick.liquidity_net;
                 if zero_for_one {
                     liquidity_net = liquidity_net.neg();
@@ -328,12 +351,33 @@ fn swap_compute(
         }
         loop_count += 1;
     }
+    // exact out时流动性不足，无法换出指定数量
+    if !is_base_input && state.amount_specified_remaining != 0 {
+        return Result::Err(anyhow!("liquidity insufficient for exact out"));
+    }
 
-    Ok((
-        state.amount_calculated,
-        state.fee_amount,
-        tick_array_start_index_vec,
-    ))
+    Ok(SwapComputeResult {
+        amount_calculated: state.amount_calculated,
+        fee_amount: state.fee_amount,
+        protocol_fee,
+        fund_fee,
+        sqrt_price_x64: state.sqrt_price_x64,
+        ticks_crossed,
+        tick_array_start_indexes: tick_array_start_index_vec,
+    })
+}
+
+/// tick array的地址
+pub fn get_tick_array_key(pool_id: &Pubkey, start_tick_index: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED.as_bytes(),
+            pool_id.to_bytes().as_ref(),
+            &start_tick_index.to_be_bytes(),
+        ],
+        DexType::RaydiumCLMM.get_ref_program_id(),
+    )
+    .0
 }
 
 pub fn load_cur_and_next_specify_count_tick_array_key(
@@ -348,17 +392,10 @@ pub fn load_cur_and_next_specify_count_tick_array_key(
         Ok((_, mut current_vaild_tick_array_start_index)) => {
             let mut tick_array_keys = Vec::with_capacity(load_count as usize);
 
-            tick_array_keys.push(
-                Pubkey::find_program_address(
-                    &[
-                        TICK_ARRAY_SEED.as_bytes(),
-                        pool_id.to_bytes().as_ref(),
-                        &current_vaild_tick_array_start_index.to_be_bytes(),
-                    ],
-                    DexType::RaydiumCLMM.get_ref_program_id(),
-                )
-                .0,
-            );
+            tick_array_keys.push(get_tick_array_key(
+                pool_id,
+                current_vaild_tick_array_start_index,
+            ));
             let mut max_array_size = load_count;
             while max_array_size != 0 {
                 let next_tick_array_index = pool_state
@@ -372,17 +409,10 @@ pub fn load_cur_and_next_specify_count_tick_array_key(
                     break;
                 }
                 current_vaild_tick_array_start_index = next_tick_array_index.unwrap();
-                tick_array_keys.push(
-                    Pubkey::find_program_address(
-                        &[
-                            TICK_ARRAY_SEED.as_bytes(),
-                            pool_id.to_bytes().as_ref(),
-                            &current_vaild_tick_array_start_index.to_be_bytes(),
-                        ],
-                        DexType::RaydiumCLMM.get_ref_program_id(),
-                    )
-                    .0,
-                );
+                tick_array_keys.push(get_tick_array_key(
+                    pool_id,
+                    current_vaild_tick_array_start_index,
+                ));
                 max_array_size -= 1;
             }
             (!tick_array_keys.is_empty()).then_some(tick_array_keys)
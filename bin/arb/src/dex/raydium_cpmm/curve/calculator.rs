@@ -25,4 +25,19 @@ impl CurveCalculator {
         );
         Some(destination_amount_swapped)
     }
+
+    /// 计算换出destination_amount需要提供的source数量(含手续费)
+    pub fn swap_base_output(
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_fee_rate: u64,
+    ) -> Option<u128> {
+        let source_amount_swapped = ConstantProductCurve::swap_base_output_without_fees(
+            destination_amount,
+            swap_source_amount,
+            swap_destination_amount,
+        )?;
+        Fees::calculate_pre_fee_amount(source_amount_swapped, trade_fee_rate)
+    }
 }
@@ -23,4 +23,19 @@ impl ConstantProductCurve {
         let destinsation_amount_swapped = numerator.checked_div(denominator).unwrap();
         destinsation_amount_swapped
     }
+
+    /// 换出destination_amount需要的输入数量(向上取整)，流动性不足时返回None
+    pub fn swap_base_output_without_fees(
+        destination_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+    ) -> Option<u128> {
+        // (x + delta_x) * (y - delta_y) = x * y
+        // delta_x = (x * delta_y) / (y - delta_y)
+        let numerator = swap_source_amount.checked_mul(destination_amount)?;
+        let denominator = swap_destination_amount
+            .checked_sub(destination_amount)
+            .filter(|denominator| *denominator > 0)?;
+        Some(numerator.div_ceil(denominator))
+    }
 }
@@ -1,7 +1,8 @@
 use crate::dex::raydium_cpmm::states::PoolState;
 use crate::dex::raydium_cpmm::RAYDIUM_CPMM_AUTHORITY_ID;
 use crate::dex::{
-    get_alt, DexType, InstructionMaterial, InstructionMaterialConverter, ATA_PROGRAM_ID,
+    get_alt, DexType, InstructionMaterial, InstructionMaterialConverter, QuoteResult,
+    ATA_PROGRAM_ID,
 };
 use crate::metadata::{get_keypair, MintAtaPair};
 use solana_sdk::instruction::AccountMeta;
@@ -15,6 +16,7 @@ impl InstructionMaterialConverter for RaydiumCPMMInstructionMaterialConverter {
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        _quote_result: Option<&QuoteResult>,
     ) -> anyhow::Result<InstructionMaterial> {
         let wallet = get_keypair().pubkey();
         let pool_state = crate::dex::global_cache::get_account_data::<PoolState>(pool_id).unwrap();
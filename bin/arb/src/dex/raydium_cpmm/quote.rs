@@ -1,71 +1,267 @@
-use crate::dex::raydium_cpmm::curve::CurveCalculator;
-use crate::dex::raydium_cpmm::states::{AmmConfig, PoolState};
-use crate::dex::{get_account_data, get_clock, get_transfer_fee, MintVault, QuoteResult, Quoter};
-use anyhow::anyhow;
+use crate::dex::quoter::reserve_price;
+use crate::dex::raydium_cpmm::curve::{CurveCalculator, Fees, FEE_RATE_DENOMINATOR_VALUE};
+use crate::dex::raydium_cpmm::states::{AmmConfig, PoolState, PoolStatusBitIndex};
+use crate::dex::{
+    calculate_inverse_transfer_fee, calculate_transfer_fee, get_account_data, get_clock,
+    get_transfer_fee_config, ConstantProductReserves, MintVault, PreparedQuote, PreparedQuoteType,
+    QuoteError, QuoteResult, Quoter,
+};
 use solana_sdk::pubkey::Pubkey;
-use tracing::error;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
 
 #[derive(Debug)]
 pub struct RaydiumCPMMQuoter;
 
 impl Quoter for RaydiumCPMMQuoter {
-    fn quote(&self, amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> Option<QuoteResult> {
-        match get_quote(amount_in, swap_direction, pool_id) {
-            Ok(amount_out) => Some(QuoteResult { amount_out }),
-            Err(e) => {
-                error!("[RaydiumCPMM][{pool_id}] quote失败，原因：{}", e);
-                None
-            }
+    fn quote(
+        &self,
+        amount_in: u64,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Result<QuoteResult, QuoteError> {
+        self.prepare(pool_id)?.quote(amount_in, swap_direction)
+    }
+
+    fn prepare(&self, pool_id: &Pubkey) -> Result<PreparedQuoteType, QuoteError> {
+        Ok(PreparedQuoteType::from(RaydiumCPMMPreparedQuote::new(
+            pool_id,
+        )?))
+    }
+
+    fn constant_product_reserves(
+        &self,
+        swap_direction: bool,
+        pool_id: &Pubkey,
+    ) -> Option<ConstantProductReserves> {
+        let pool_state = get_account_data::<PoolState>(pool_id)?;
+        // Token2022的transfer fee不是恒定乘积
+        if get_transfer_fee_config(&pool_state.token_0_mint).is_some()
+            || get_transfer_fee_config(&pool_state.token_1_mint).is_some()
+        {
+            return None;
+        }
+        let trade_fee_rate = get_account_data::<AmmConfig>(&pool_state.amm_config)?.trade_fee_rate;
+        let token_0_vault_amount = get_account_data::<MintVault>(&pool_state.token_0_vault)?.amount;
+        let token_1_vault_amount = get_account_data::<MintVault>(&pool_state.token_1_vault)?.amount;
+        let (total_token_0_amount, total_token_1_amount) =
+            pool_state.vault_amount_without_fee(token_0_vault_amount, token_1_vault_amount);
+        let fee_in = 1.0 - trade_fee_rate as f64 / FEE_RATE_DENOMINATOR_VALUE as f64;
+        if swap_direction {
+            ConstantProductReserves::new(
+                u128::from(total_token_0_amount),
+                u128::from(total_token_1_amount),
+                fee_in,
+                1.0,
+            )
+        } else {
+            ConstantProductReserves::new(
+                u128::from(total_token_1_amount),
+                u128::from(total_token_0_amount),
+                fee_in,
+                1.0,
+            )
         }
     }
 }
 
-fn get_quote(amount_in: u64, swap_direction: bool, pool_id: &Pubkey) -> anyhow::Result<u64> {
-    let pool_state =
-        get_account_data::<PoolState>(pool_id).ok_or(anyhow!("缓存中无池子[{pool_id}]"))?;
-    let trade_fee_rate = get_account_data::<AmmConfig>(&pool_state.amm_config)
-        .ok_or(anyhow!("缓存中无AmmConfig[{}]", pool_state.amm_config))?
-        .trade_fee_rate;
+/// 扣除手续费后的金库数量、手续费率和两个Mint的transfer fee配置
+pub struct RaydiumCPMMPreparedQuote {
+    trade_fee_rate: u64,
+    total_token_0_amount: u64,
+    total_token_1_amount: u64,
+    epoch: u64,
+    token_0_transfer_fee: Option<TransferFeeConfig>,
+    token_1_transfer_fee: Option<TransferFeeConfig>,
+}
+
+impl RaydiumCPMMPreparedQuote {
+    fn new(pool_id: &Pubkey) -> Result<Self, QuoteError> {
+        let pool_state = get_account_data::<PoolState>(pool_id).ok_or(QuoteError::MissingCache)?;
+        let clock = get_clock().ok_or(QuoteError::MissingClock)?;
+        validate_swap_status(&pool_state, clock.unix_timestamp as u64)?;
+        let trade_fee_rate = get_account_data::<AmmConfig>(&pool_state.amm_config)
+            .ok_or(QuoteError::MissingCache)?
+            .trade_fee_rate;
 
-    let token_0_vault_amount = get_account_data::<MintVault>(&pool_state.token_0_vault)
-        .ok_or(anyhow!("缓存中无金库[{}]", pool_state.token_0_vault))?
-        .amount;
-    let token_1_vault_amount = get_account_data::<MintVault>(&pool_state.token_1_vault)
-        .ok_or(anyhow!("缓存中无金库[{}]", pool_state.token_1_vault))?
-        .amount;
-    let (total_token_0_amount, total_token_1_amount) =
-        pool_state.vault_amount_without_fee(token_0_vault_amount, token_1_vault_amount);
-    let epoch = get_clock().ok_or(anyhow!("缓存中无Clock"))?.epoch;
-    let (total_input_token_amount, total_output_token_amount, transfer_fee) = if swap_direction {
-        (
+        let token_0_vault_amount = get_account_data::<MintVault>(&pool_state.token_0_vault)
+            .ok_or(QuoteError::MissingCache)?
+            .amount;
+        let token_1_vault_amount = get_account_data::<MintVault>(&pool_state.token_1_vault)
+            .ok_or(QuoteError::MissingCache)?
+            .amount;
+        let (total_token_0_amount, total_token_1_amount) =
+            pool_state.vault_amount_without_fee(token_0_vault_amount, token_1_vault_amount);
+        Ok(Self {
+            trade_fee_rate,
             total_token_0_amount,
             total_token_1_amount,
-            get_transfer_fee(&pool_state.token_0_mint, epoch, amount_in),
-        )
-    } else {
-        (
-            total_token_1_amount,
-            total_token_0_amount,
-            get_transfer_fee(&pool_state.token_1_mint, epoch, amount_in),
+            epoch: clock.epoch,
+            token_0_transfer_fee: get_transfer_fee_config(&pool_state.token_0_mint),
+            token_1_transfer_fee: get_transfer_fee_config(&pool_state.token_1_mint),
+        })
+    }
+
+    /// (输入金库数量, 输出金库数量, 输入Mint的transfer fee配置, 输出Mint的transfer fee配置)
+    fn sides(
+        &self,
+        swap_direction: bool,
+    ) -> (
+        u64,
+        u64,
+        Option<&TransferFeeConfig>,
+        Option<&TransferFeeConfig>,
+    ) {
+        if swap_direction {
+            (
+                self.total_token_0_amount,
+                self.total_token_1_amount,
+                self.token_0_transfer_fee.as_ref(),
+                self.token_1_transfer_fee.as_ref(),
+            )
+        } else {
+            (
+                self.total_token_1_amount,
+                self.total_token_0_amount,
+                self.token_1_transfer_fee.as_ref(),
+                self.token_0_transfer_fee.as_ref(),
+            )
+        }
+    }
+}
+
+impl PreparedQuote for RaydiumCPMMPreparedQuote {
+    fn quote(&self, amount_in: u64, swap_direction: bool) -> Result<QuoteResult, QuoteError> {
+        let (total_input_token_amount, total_output_token_amount, fee_config_in, fee_config_out) =
+            self.sides(swap_direction);
+        let transfer_fee = calculate_transfer_fee(fee_config_in, self.epoch, amount_in);
+        // Take transfer fees into account for actual amount transferred in
+        let actual_amount_in = amount_in.saturating_sub(transfer_fee);
+        let trade_fee = Fees::trading_fee(u128::from(actual_amount_in), self.trade_fee_rate)
+            .ok_or(QuoteError::MathOverflow)?;
+        let amount_out = u64::try_from(
+            CurveCalculator::swap_base_input(
+                u128::from(actual_amount_in),
+                u128::from(total_input_token_amount),
+                u128::from(total_output_token_amount),
+                self.trade_fee_rate,
+            )
+            .ok_or(QuoteError::InsufficientLiquidity)?,
         )
-    };
-    // Take transfer fees into account for actual amount transferred in
-    let actual_amount_in = amount_in.saturating_sub(transfer_fee);
-    let amount_out = u64::try_from(
-        CurveCalculator::swap_base_input(
-            u128::from(actual_amount_in),
-            u128::from(total_input_token_amount),
-            u128::from(total_output_token_amount),
-            trade_fee_rate,
+        .map_err(|_| QuoteError::MathOverflow)?;
+        let transfer_fee_out = calculate_transfer_fee(fee_config_out, self.epoch, amount_out);
+        let reserve_in = u128::from(total_input_token_amount);
+        let reserve_out = u128::from(total_output_token_amount);
+        Ok(QuoteResult {
+            amount_in,
+            amount_out: amount_out
+                .checked_sub(transfer_fee_out)
+                .ok_or(QuoteError::InvalidAmount)?,
+            lp_fee: u64::try_from(trade_fee).map_err(|_| QuoteError::MathOverflow)?,
+            transfer_fee_in: transfer_fee,
+            transfer_fee_out,
+            start_price: reserve_price(reserve_in, reserve_out),
+            end_price: reserve_price(
+                reserve_in + u128::from(actual_amount_in) - trade_fee,
+                reserve_out - u128::from(amount_out),
+            ),
+            ..Default::default()
+        })
+    }
+
+    fn quote_exact_out(
+        &self,
+        amount_out: u64,
+        swap_direction: bool,
+    ) -> Result<QuoteResult, QuoteError> {
+        let (total_input_token_amount, total_output_token_amount, fee_config_in, fee_config_out) =
+            self.sides(swap_direction);
+        // 池子实际需要转出的数量要包含输出端的transfer fee
+        let transfer_fee_out =
+            calculate_inverse_transfer_fee(fee_config_out, self.epoch, amount_out);
+        let actual_amount_out = amount_out
+            .checked_add(transfer_fee_out)
+            .ok_or(QuoteError::MathOverflow)?;
+        let reserve_in = u128::from(total_input_token_amount);
+        let reserve_out = u128::from(total_output_token_amount);
+        let source_amount = u64::try_from(
+            CurveCalculator::swap_base_output(
+                u128::from(actual_amount_out),
+                reserve_in,
+                reserve_out,
+                self.trade_fee_rate,
+            )
+            .ok_or(QuoteError::InsufficientLiquidity)?,
         )
-        .ok_or(anyhow!("Quote返回None"))?,
-    )?;
-    let transfer_fee = if swap_direction {
-        get_transfer_fee(&pool_state.token_1_mint, epoch, amount_out)
-    } else {
-        get_transfer_fee(&pool_state.token_0_mint, epoch, amount_out)
-    };
-    amount_out
-        .checked_sub(transfer_fee)
-        .ok_or(anyhow!("扣减transfer_fee失败"))
+        .map_err(|_| QuoteError::MathOverflow)?;
+        let trade_fee = Fees::trading_fee(u128::from(source_amount), self.trade_fee_rate)
+            .ok_or(QuoteError::MathOverflow)?;
+        let transfer_fee_in =
+            calculate_inverse_transfer_fee(fee_config_in, self.epoch, source_amount);
+        Ok(QuoteResult {
+            amount_in: source_amount
+                .checked_add(transfer_fee_in)
+                .ok_or(QuoteError::MathOverflow)?,
+            amount_out,
+            lp_fee: u64::try_from(trade_fee).map_err(|_| QuoteError::MathOverflow)?,
+            transfer_fee_in,
+            transfer_fee_out,
+            start_price: reserve_price(reserve_in, reserve_out),
+            end_price: reserve_price(
+                reserve_in + u128::from(source_amount) - trade_fee,
+                reserve_out - u128::from(actual_amount_out),
+            ),
+            ..Default::default()
+        })
+    }
+}
+
+/// 禁止swap或未到open_time的池子不能swap
+fn validate_swap_status(pool_state: &PoolState, block_timestamp: u64) -> Result<(), QuoteError> {
+    let open_time = pool_state.open_time;
+    if !pool_state.get_status_by_bit(PoolStatusBitIndex::Swap) || block_timestamp < open_time {
+        return Err(QuoteError::PoolDisabled);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::quoter::assert_exact_out_round_trip;
+    use crate::dex::raydium_cpmm::quote::RaydiumCPMMPreparedQuote;
+    use crate::dex::{PreparedQuote, QuoteError};
+    use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+
+    fn transfer_fee_config(transfer_fee_basis_points: u16, maximum_fee: u64) -> TransferFeeConfig {
+        let mut fee_config = TransferFeeConfig::default();
+        fee_config.newer_transfer_fee.transfer_fee_basis_points = transfer_fee_basis_points.into();
+        fee_config.newer_transfer_fee.maximum_fee = maximum_fee.into();
+        fee_config
+    }
+
+    #[test]
+    fn test_quote_exact_out_round_trip() {
+        let amounts_in = [1, 999, 10_u64.pow(6), 10_u64.pow(9), 10_u64.pow(12)];
+        for (token_0_transfer_fee, token_1_transfer_fee) in [
+            (None, None),
+            (Some(transfer_fee_config(100, u64::MAX)), None),
+            (None, Some(transfer_fee_config(250, 5_000))),
+        ] {
+            let prepared = RaydiumCPMMPreparedQuote {
+                trade_fee_rate: 2_500,
+                total_token_0_amount: 5_000 * 10_u64.pow(9),
+                total_token_1_amount: 700_000 * 10_u64.pow(6),
+                epoch: 0,
+                token_0_transfer_fee,
+                token_1_transfer_fee,
+            };
+            assert_exact_out_round_trip(&prepared, true, &amounts_in);
+            assert_exact_out_round_trip(&prepared, false, &amounts_in);
+            assert_eq!(
+                prepared
+                    .quote_exact_out(700_000 * 10_u64.pow(6), true)
+                    .unwrap_err(),
+                QuoteError::InsufficientLiquidity
+            );
+        }
+    }
 }
@@ -20,13 +20,13 @@ impl SnapshotInitializer for RaydiumCPMMSnapshotLoader {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let dex_data = dex_json
             .iter()
             .filter(|json| &json.owner == DexType::RaydiumCPMM.get_ref_program_id())
             .collect::<Vec<_>>();
         if dex_data.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         info!("【RaydiumCPMM】开始初始化Snapshot...");
         let mut invalid_pool = AHashSet::with_capacity(dex_data.len());
@@ -45,7 +45,7 @@ impl SnapshotInitializer for RaydiumCPMMSnapshotLoader {
                 AccountType::Pool,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none()
                 || account.dynamic_slice_data.as_ref().is_none()
@@ -63,7 +63,7 @@ impl SnapshotInitializer for RaydiumCPMMSnapshotLoader {
                 all_pool_account_data.as_slice(),
                 &mut invalid_pool,
             )
-            .await;
+            .await?;
         all_pool_account_data.retain(|account| !invalid_pool.contains(&account.account_key));
         // mint vault
         let all_vault_accounts = all_pool_account_data
@@ -84,7 +84,7 @@ impl SnapshotInitializer for RaydiumCPMMSnapshotLoader {
                 AccountType::MintVault,
                 rpc_client,
             )
-            .await;
+            .await?;
         all_vault_account_data.retain(|account| {
             if account.dynamic_slice_data.as_ref().is_none() {
                 invalid_pool.insert(vault_to_pool.get(&account.account_key).unwrap().clone());
@@ -99,14 +99,13 @@ impl SnapshotInitializer for RaydiumCPMMSnapshotLoader {
             all_pool_account_data.len()
         );
         if dex_json.is_empty() {
-            vec![]
-        } else {
-            all_pool_account_data
-                .into_iter()
-                .chain(all_vault_account_data.into_iter())
-                .chain(all_amm_config_accounts.into_iter())
-                .collect()
+            return Ok(vec![]);
         }
+        Ok(all_pool_account_data
+            .into_iter()
+            .chain(all_vault_account_data.into_iter())
+            .chain(all_amm_config_accounts.into_iter())
+            .collect())
     }
 
     fn print_snapshot(&self, dex_json: &[DexJson]) -> anyhow::Result<()> {
@@ -144,7 +143,7 @@ impl RaydiumCPMMSnapshotLoader {
         rpc_client: Arc<RpcClient>,
         all_pool_account_data: &[AccountDataSlice],
         invalid_pool: &mut AHashSet<Pubkey>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         let mut all_amm_config_accounts = AHashMap::with_capacity(50);
         for account in all_pool_account_data {
             // amm_config
@@ -166,7 +165,7 @@ impl RaydiumCPMMSnapshotLoader {
                 AccountType::AmmConfig,
                 rpc_client.clone(),
             )
-            .await;
+            .await?;
         all_amm_config_account_data.retain(|account| {
             if account.static_slice_data.as_ref().is_none_or(|data| {
                 data.len()
@@ -186,6 +185,6 @@ impl RaydiumCPMMSnapshotLoader {
                 true
             }
         });
-        all_amm_config_account_data
+        Ok(all_amm_config_account_data)
     }
 }
@@ -2,4 +2,4 @@ mod config;
 mod pool;
 
 pub use config::AmmConfig;
-pub use pool::PoolState;
+pub use pool::{PoolState, PoolStatusBitIndex};
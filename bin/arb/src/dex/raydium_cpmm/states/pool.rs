@@ -134,6 +134,12 @@ impl PoolState {
         }
     }
 
+    /// 对应的位为0时启用
+    pub fn get_status_by_bit(&self, bit: PoolStatusBitIndex) -> bool {
+        let status = 1u8 << (bit as u8);
+        self.status & status == 0
+    }
+
     pub fn vault_amount_without_fee(&self, vault_0: u64, vault_1: u64) -> (u64, u64) {
         (
             vault_0
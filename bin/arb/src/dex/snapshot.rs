@@ -8,7 +8,10 @@ use crate::dex::raydium_amm::state::AmmInfo;
 use crate::dex::raydium_amm::RaydiumAmmSnapshotInitializer;
 use crate::dex::raydium_clmm::RaydiumCLMMSnapshotInitializer;
 use crate::dex::raydium_cpmm::RaydiumCPMMSnapshotLoader;
-use crate::dex::{AccountType, DexType, CLOCK_ID};
+use crate::dex::{
+    get_extra_account_metas_address, unpack_extra_account_metas, AccountType, CacheVersion,
+    DexType, MintInfo, CLOCK_ID,
+};
 use crate::dex_data::DexJson;
 use ahash::AHashSet;
 use anyhow::anyhow;
@@ -20,8 +23,6 @@ use solana_sdk::address_lookup_table::AddressLookupTableAccount;
 use solana_sdk::clock::Clock;
 use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
-use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
-use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
 use std::sync::Arc;
 use tokio::task::JoinSet;
 use tracing::{error, info};
@@ -33,7 +34,7 @@ pub trait SnapshotInitializer {
         &self,
         dex_json: &mut Vec<DexJson>,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice>;
+    ) -> anyhow::Result<Vec<AccountDataSlice>>;
 
     fn print_snapshot(&self, dex_json: &[DexJson]) -> anyhow::Result<()>;
 
@@ -43,9 +44,9 @@ pub trait SnapshotInitializer {
         dex_type: DexType,
         account_type: AccountType,
         rpc_client: Arc<RpcClient>,
-    ) -> Vec<AccountDataSlice> {
+    ) -> anyhow::Result<Vec<AccountDataSlice>> {
         if accounts.is_empty() {
-            return vec![];
+            return Ok(vec![]);
         }
         let mut join_set = JoinSet::new();
         for account_chunks in accounts.chunks(100) {
@@ -54,39 +55,52 @@ pub trait SnapshotInitializer {
             let account_type = account_type.clone();
             let account_chunks = account_chunks.to_vec();
             join_set.spawn(async move {
-                rpc_client
+                // 启动时为finalized，断线重连后重新加载时为processed
+                let response = rpc_client
                     .get_multiple_accounts_with_commitment(
                         account_chunks.as_slice(),
-                        CommitmentConfig::finalized(),
+                        rpc_client.commitment(),
                     )
-                    .await
-                    .unwrap()
+                    .await?;
+                let slot = response.context.slot;
+                let accounts = response
                     .value
                     .into_iter()
                     .zip(account_chunks)
                     .map(|(account, account_key)| {
-                        account.map_or(AccountDataSlice::new(account_key, None, None), |acc| {
-                            let dynamic_data = try_slice_data(
-                                dex_type.clone(),
-                                account_type.clone(),
-                                acc.data.clone(),
-                                SliceType::Subscribed,
-                            )
-                            .map_or(None, |v| Some(v));
-                            let static_data = try_slice_data(
-                                dex_type.clone(),
-                                account_type.clone(),
-                                acc.data,
-                                SliceType::Unsubscribed,
-                            )
-                            .map_or(None, |v| Some(v));
-                            AccountDataSlice::new(account_key, static_data, dynamic_data)
-                        })
+                        account.map_or(
+                            AccountDataSlice::new(account_key, slot, None, None),
+                            |acc| {
+                                let dynamic_data = try_slice_data(
+                                    dex_type.clone(),
+                                    account_type.clone(),
+                                    acc.data.clone(),
+                                    SliceType::Subscribed,
+                                )
+                                .map_or(None, |v| Some(v));
+                                let static_data = try_slice_data(
+                                    dex_type.clone(),
+                                    account_type.clone(),
+                                    acc.data,
+                                    SliceType::Unsubscribed,
+                                )
+                                .map_or(None, |v| Some(v));
+                                AccountDataSlice::new(account_key, slot, static_data, dynamic_data)
+                            },
+                        )
                     })
-                    .collect::<Vec<_>>()
+                    .collect::<Vec<_>>();
+                anyhow::Ok(accounts)
             });
         }
-        join_set.join_all().await.into_iter().flatten().collect()
+        Ok(join_set
+            .join_all()
+            .await
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect())
     }
 }
 
@@ -106,7 +120,28 @@ pub async fn init_snapshot(
     rpc_client: Arc<RpcClient>,
 ) -> anyhow::Result<GlobalCache> {
     info!("开始初始化Snapshot...");
-    let mut cache = GlobalCache::init();
+    let cache = GlobalCache::init();
+    load_snapshot(dex_data, rpc_client.clone(), &cache).await?;
+    // 加载clock
+    cache_clock(rpc_client.clone(), &cache).await?;
+    info!("初始化Snapshot结束, 数量 : {}", dex_data.len());
+    #[cfg(feature = "print_slice_data")]
+    print_slice_data(dex_data);
+    if dex_data.is_empty() {
+        Err(anyhow!("所有DexJson均加载失败"))
+    } else {
+        Ok(cache)
+    }
+}
+
+/// 加载池子的账户、alt、Mint到缓存中，移除无效的DexJson
+///
+/// 启动时写入新建的缓存，运行时新增池子直接写入全局缓存
+pub async fn load_snapshot(
+    dex_data: &mut Vec<DexJson>,
+    rpc_client: Arc<RpcClient>,
+    cache: &GlobalCache,
+) -> anyhow::Result<()> {
     for snapshot in vec![
         SnapshotType::from(MeteoraDLMMSnapshotInitializer),
         SnapshotType::from(MeteoraDAMMV2SnapshotLoader),
@@ -117,31 +152,56 @@ pub async fn init_snapshot(
         SnapshotType::from(RaydiumCPMMSnapshotLoader),
     ] {
         let accounts: Vec<AccountDataSlice> =
-            snapshot.init_snapshot(dex_data, rpc_client.clone()).await;
+            snapshot.init_snapshot(dex_data, rpc_client.clone()).await?;
         // 缓存账户
         accounts.into_iter().for_each(|account| {
+            let version = account.version();
             account
                 .static_slice_data
                 .and_then(|data| cache.upsert_static(account.account_key, data));
-            account
-                .dynamic_slice_data
-                .and_then(|data| cache.upsert_dynamic(account.account_key, data));
+            if let Some(data) = account.dynamic_slice_data {
+                cache.upsert_dynamic_with_version(account.account_key, data, version);
+            }
         })
     }
     // 加载alt
-    cache_lookup_table_accounts(dex_data.as_slice(), rpc_client.clone(), &cache).await;
-    // 加载token2022
-    cache_token_2022(dex_data.as_slice(), rpc_client.clone(), &mut cache).await;
-    // 加载clock
-    cache_clock(rpc_client.clone(), &cache).await;
-    info!("初始化Snapshot结束, 数量 : {}", dex_data.len());
-    #[cfg(feature = "print_slice_data")]
-    print_slice_data(dex_data);
-    if dex_data.is_empty() {
-        Err(anyhow!("所有DexJson均加载失败"))
-    } else {
-        Ok(cache)
+    cache_lookup_table_accounts(dex_data.as_slice(), rpc_client.clone(), cache).await;
+    // 加载Mint，移除无法支持的池子
+    cache_mint_infos(dex_data, rpc_client.clone(), cache).await
+}
+
+/// GRPC断线期间账户可能发生变化，重新加载所有池子的动态数据，返回写入的账户数量
+///
+/// rpc_client需要使用processed，比缓存旧的数据不会写入
+pub async fn resync_snapshot(
+    dex_data: &[DexJson],
+    rpc_client: Arc<RpcClient>,
+    cache: &GlobalCache,
+) -> anyhow::Result<usize> {
+    let mut dex_data = dex_data.to_vec();
+    let mut count = 0;
+    for snapshot in vec![
+        SnapshotType::from(MeteoraDLMMSnapshotInitializer),
+        SnapshotType::from(MeteoraDAMMV2SnapshotLoader),
+        SnapshotType::from(OrcaWhirlpoolsSnapshotInitializer),
+        SnapshotType::from(PumpFunAMMSnapshotInitializer),
+        SnapshotType::from(RaydiumAmmSnapshotInitializer),
+        SnapshotType::from(RaydiumCLMMSnapshotInitializer),
+        SnapshotType::from(RaydiumCPMMSnapshotLoader),
+    ] {
+        let accounts: Vec<AccountDataSlice> = snapshot
+            .init_snapshot(&mut dex_data, rpc_client.clone())
+            .await?;
+        for account in accounts {
+            let version = account.version();
+            if let Some(data) = account.dynamic_slice_data {
+                if cache.upsert_dynamic_with_version(account.account_key, data, version) {
+                    count += 1;
+                }
+            }
+        }
     }
+    Ok(count)
 }
 
 fn print_slice_data(dex_json: &[DexJson]) {
@@ -156,7 +216,9 @@ fn print_slice_data(dex_json: &[DexJson]) {
     ]
     .into_iter()
     .for_each(|snapshot| {
-        snapshot.print_snapshot(dex_json).unwrap();
+        if let Err(e) = snapshot.print_snapshot(dex_json) {
+            error!("输出slice数据失败，原因 : {}", e);
+        }
     })
 }
 
@@ -221,7 +283,12 @@ async fn cache_lookup_table_accounts(
     }
 }
 
-async fn cache_token_2022(dex_data: &[DexJson], rpc_client: Arc<RpcClient>, cache: &mut GlobalCache) {
+/// 加载池子涉及的Mint，移除Mint加载失败或扩展无法支持的DexJson
+async fn cache_mint_infos(
+    dex_data: &mut Vec<DexJson>,
+    rpc_client: Arc<RpcClient>,
+    cache: &GlobalCache,
+) -> anyhow::Result<()> {
     let all_tokens = dex_data
         .iter()
         .flat_map(|json| vec![json.mint_a, json.mint_b])
@@ -233,65 +300,113 @@ async fn cache_token_2022(dex_data: &[DexJson], rpc_client: Arc<RpcClient>, cach
         let rpc_client = rpc_client.clone();
         let account_chunks = account_chunks.to_vec();
         join_set.spawn(async move {
-            rpc_client
+            let mint_infos = rpc_client
                 .get_multiple_accounts_with_commitment(
                     account_chunks.as_slice(),
                     CommitmentConfig::finalized(),
                 )
-                .await
-                .unwrap()
+                .await?
                 .value
                 .into_iter()
                 .zip(account_chunks)
-                .map(|(account, account_key)| {
-                    account.map_or(AccountDataSlice::new(account_key, None, None), |acc| {
-                        let token_2022 = if let Ok(mint_extensions) =
-                            StateWithExtensions::<spl_token_2022::state::Mint>::unpack(
-                                acc.data.as_ref(),
-                            ) {
-                            mint_extensions
-                                .get_extension::<TransferFeeConfig>()
-                                .map_or(None, |_| Some(()))
-                        } else {
+                .filter_map(|(account, account_key)| {
+                    let account = account?;
+                    match MintInfo::unpack(&account.owner, account.data.as_slice()) {
+                        Ok(mint_info) => Some((account_key, mint_info)),
+                        Err(e) => {
+                            error!("Mint[{}]解析失败，原因 : {}", account_key, e);
                             None
-                        };
-                        if token_2022.is_some() {
-                            AccountDataSlice::new(account_key, Some(acc.data), None)
-                        } else {
-                            AccountDataSlice::new(account_key, None, None)
                         }
-                    })
+                    }
                 })
-                .collect::<Vec<_>>()
+                .collect::<Vec<_>>();
+            anyhow::Ok(mint_infos)
         });
     }
-    let token_2022_accounts = join_set
+    let mut mint_infos = join_set
         .join_all()
         .await
         .into_iter()
-        .flatten()
-        .collect::<Vec<_>>()
+        .collect::<anyhow::Result<Vec<_>>>()?
         .into_iter()
-        .filter(|account| account.static_slice_data.is_some())
+        .flatten()
         .collect::<Vec<_>>();
-    info!("Token2022加载完毕，数量 : {}", token_2022_accounts.len());
-    token_2022_accounts.into_iter().for_each(|account| {
-        cache.upsert_static(account.account_key, account.static_slice_data.unwrap());
-    })
+    cache_transfer_hook_accounts(&mut mint_infos, rpc_client).await?;
+    info!(
+        "Mint加载完毕，数量 : {}, Token2022数量 : {}",
+        mint_infos.len(),
+        mint_infos
+            .iter()
+            .filter(|(_, mint_info)| mint_info.is_token_2022())
+            .count()
+    );
+    mint_infos.into_iter().for_each(|(mint, mint_info)| {
+        cache.upsert_mint_info(mint, mint_info);
+    });
+    dex_data.retain(|json| {
+        let dex_type = match DexType::try_from(&json.owner) {
+            Ok(dex_type) => dex_type,
+            Err(_) => return false,
+        };
+        [json.mint_a, json.mint_b].iter().all(|mint| {
+            match cache
+                .get_mint_info(mint)
+                .map_or(Err(anyhow!("Mint[{}]加载失败", mint)), |mint_info| {
+                    mint_info.check_supported(&dex_type)
+                }) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!("移除池子[{}]，Mint[{}] : {}", json.pool, mint, e);
+                    false
+                }
+            }
+        })
+    });
+    Ok(())
 }
 
-async fn cache_clock(rpc_client: Arc<RpcClient>, cache: &GlobalCache) {
-    let clock_data = rpc_client
-        .clone()
-        .get_account_data(&CLOCK_ID)
-        .await
-        .unwrap();
+/// 加载transfer hook的额外账户
+async fn cache_transfer_hook_accounts(
+    mint_infos: &mut [(Pubkey, MintInfo)],
+    rpc_client: Arc<RpcClient>,
+) -> anyhow::Result<()> {
+    let mut hooks = mint_infos
+        .iter_mut()
+        .filter_map(|(mint, mint_info)| {
+            let hook = mint_info.transfer_hook.as_mut()?;
+            Some((
+                get_extra_account_metas_address(mint, &hook.program_id),
+                hook,
+            ))
+        })
+        .collect::<Vec<_>>();
+    for hook_chunks in hooks.chunks_mut(100) {
+        let keys = hook_chunks.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+        let accounts = rpc_client.get_multiple_accounts(keys.as_slice()).await?;
+        for (account, (key, hook)) in accounts.into_iter().zip(hook_chunks.iter_mut()) {
+            // 没有extra account meta list账户时不需要额外账户
+            hook.extra_account_metas = match account {
+                None => Some(vec![]),
+                Some(account) => unpack_extra_account_metas(account.data.as_slice())
+                    .inspect_err(|e| error!("extra account meta list[{}] : {}", key, e))
+                    .ok(),
+            };
+        }
+    }
+    Ok(())
+}
+
+async fn cache_clock(rpc_client: Arc<RpcClient>, cache: &GlobalCache) -> anyhow::Result<()> {
+    let clock_data = rpc_client.get_account_data(&CLOCK_ID).await?;
     cache.upsert_dynamic(CLOCK_ID, clock_data);
+    Ok(())
 }
 
 #[derive(Clone, Debug)]
 pub struct AccountDataSlice {
     pub account_key: Pubkey,
+    // 获取数据时RPC节点所在的slot
+    pub slot: u64,
     pub static_slice_data: Option<Vec<u8>>,
     pub dynamic_slice_data: Option<Vec<u8>>,
 }
@@ -299,13 +414,24 @@ pub struct AccountDataSlice {
 impl AccountDataSlice {
     pub fn new(
         account_key: Pubkey,
+        slot: u64,
         static_slice_data: Option<Vec<u8>>,
         dynamic_slice_data: Option<Vec<u8>>,
     ) -> Self {
         Self {
             account_key,
+            slot,
             static_slice_data,
             dynamic_slice_data,
         }
     }
+
+    /// RPC返回的数据没有write_version，同一个slot内GRPC推送的数据优先
+    pub fn version(&self) -> CacheVersion {
+        CacheVersion::Account {
+            slot: self.slot,
+            write_version: 0,
+            endpoint: None,
+        }
+    }
 }
@@ -0,0 +1,286 @@
+use crate::dex::{CacheVersion, GlobalCache, MintInfo, TransferHook};
+use crate::dex_data::DexJson;
+use ahash::AHashSet;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use solana_sdk::address_lookup_table::AddressLookupTableAccount;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::interest_bearing_mint::InterestBearingConfig;
+use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+use spl_token_2022::extension::ExtensionType;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// 保存到文件的全局缓存，回放时代替RPC加载snapshot
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredSnapshot {
+    // 动态数据中最大的slot
+    slot: u64,
+    // 加载成功的池子
+    pools: Vec<Pubkey>,
+    dynamic_accounts: Vec<(Pubkey, CacheVersion, Vec<u8>)>,
+    static_accounts: Vec<(Pubkey, Vec<u8>)>,
+    // 池子 -> (alt, alt中的地址)
+    alts: Vec<(Pubkey, Vec<(Pubkey, Vec<Pubkey>)>)>,
+    mint_infos: Vec<(Pubkey, StoredMintInfo)>,
+}
+
+/// Token2022扩展按Pod的字节保存
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredMintInfo {
+    token_program: Pubkey,
+    decimals: u8,
+    transfer_fee: Option<Vec<u8>>,
+    transfer_hook: Option<StoredTransferHook>,
+    interest_bearing: Option<Vec<u8>>,
+    permanent_delegate: Option<Pubkey>,
+    unsupported_extensions: Vec<u16>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredTransferHook {
+    program_id: Pubkey,
+    // 额外账户(pubkey, is_signer, is_writable)
+    extra_account_metas: Option<Vec<(Pubkey, bool, bool)>>,
+}
+
+/// 从文件加载的snapshot
+pub struct LoadedSnapshot {
+    pub cache: GlobalCache,
+    pub slot: u64,
+    pub pools: AHashSet<Pubkey>,
+}
+
+impl From<&MintInfo> for StoredMintInfo {
+    fn from(mint_info: &MintInfo) -> Self {
+        Self {
+            token_program: mint_info.token_program,
+            decimals: mint_info.decimals,
+            transfer_fee: mint_info
+                .transfer_fee
+                .map(|config| bytemuck::bytes_of(&config).to_vec()),
+            transfer_hook: mint_info
+                .transfer_hook
+                .as_ref()
+                .map(|hook| StoredTransferHook {
+                    program_id: hook.program_id,
+                    extra_account_metas: hook.extra_account_metas.as_ref().map(|metas| {
+                        metas
+                            .iter()
+                            .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+                            .collect()
+                    }),
+                }),
+            interest_bearing: mint_info
+                .interest_bearing
+                .map(|config| bytemuck::bytes_of(&config).to_vec()),
+            permanent_delegate: mint_info.permanent_delegate,
+            unsupported_extensions: mint_info
+                .unsupported_extensions
+                .iter()
+                .map(|extension| u16::from(*extension))
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<StoredMintInfo> for MintInfo {
+    type Error = anyhow::Error;
+
+    fn try_from(stored: StoredMintInfo) -> anyhow::Result<Self> {
+        Ok(Self {
+            token_program: stored.token_program,
+            decimals: stored.decimals,
+            transfer_fee: stored
+                .transfer_fee
+                .map(|bytes| bytemuck::try_pod_read_unaligned::<TransferFeeConfig>(&bytes))
+                .transpose()
+                .map_err(|e| anyhow!("TransferFeeConfig解析失败 : {}", e))?,
+            transfer_hook: stored.transfer_hook.map(|hook| TransferHook {
+                program_id: hook.program_id,
+                extra_account_metas: hook.extra_account_metas.map(|metas| {
+                    metas
+                        .into_iter()
+                        .map(|(pubkey, is_signer, is_writable)| AccountMeta {
+                            pubkey,
+                            is_signer,
+                            is_writable,
+                        })
+                        .collect()
+                }),
+            }),
+            interest_bearing: stored
+                .interest_bearing
+                .map(|bytes| bytemuck::try_pod_read_unaligned::<InterestBearingConfig>(&bytes))
+                .transpose()
+                .map_err(|e| anyhow!("InterestBearingConfig解析失败 : {}", e))?,
+            permanent_delegate: stored.permanent_delegate,
+            unsupported_extensions: stored
+                .unsupported_extensions
+                .into_iter()
+                .map(|extension| {
+                    ExtensionType::try_from(extension)
+                        .map_err(|_| anyhow!("未知的Token2022扩展 : {}", extension))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        })
+    }
+}
+
+/// 将缓存保存到dir/snapshot-{slot}.bin，dir不存在时创建，返回文件路径
+pub fn store_snapshot(
+    cache: &GlobalCache,
+    dex_data: &[DexJson],
+    dir: &Path,
+) -> anyhow::Result<PathBuf> {
+    let dynamic_accounts = cache
+        .dynamic_entries()
+        .into_iter()
+        .map(|(account_key, entry)| (account_key, entry.version, entry.data.to_vec()))
+        .collect::<Vec<_>>();
+    let snapshot = StoredSnapshot {
+        slot: dynamic_accounts
+            .iter()
+            .map(|(_, version, _)| version.slot())
+            .max()
+            .unwrap_or_default(),
+        pools: dex_data.iter().map(|json| json.pool).collect(),
+        dynamic_accounts,
+        static_accounts: cache
+            .static_entries()
+            .into_iter()
+            .map(|(account_key, data)| (account_key, data.to_vec()))
+            .collect(),
+        alts: cache
+            .alt_entries()
+            .into_iter()
+            .map(|(pool_id, alts)| {
+                (
+                    pool_id,
+                    alts.into_iter()
+                        .map(|alt| (alt.key, alt.addresses))
+                        .collect(),
+                )
+            })
+            .collect(),
+        mint_infos: cache
+            .mint_info_entries()
+            .iter()
+            .map(|(mint, mint_info)| (*mint, StoredMintInfo::from(mint_info.as_ref())))
+            .collect(),
+    };
+    std::fs::create_dir_all(dir)?;
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("snapshot-{}.bin", snapshot.slot));
+    bincode::serialize_into(BufWriter::new(File::create(&path)?), &snapshot)?;
+    info!(
+        "保存Snapshot : {}, 池子数量 : {}, 账户数量 : {}",
+        path.display(),
+        snapshot.pools.len(),
+        snapshot.dynamic_accounts.len() + snapshot.static_accounts.len()
+    );
+    Ok(path)
+}
+
+/// 从文件加载缓存，写入的版本与保存时相同
+pub fn load_stored_snapshot(path: &Path) -> anyhow::Result<LoadedSnapshot> {
+    let snapshot: StoredSnapshot = bincode::deserialize_from(BufReader::new(File::open(path)?))?;
+    let cache = GlobalCache::init();
+    for (account_key, version, data) in snapshot.dynamic_accounts {
+        cache.upsert_dynamic_with_version(account_key, data, version);
+    }
+    for (account_key, data) in snapshot.static_accounts {
+        cache.upsert_static(account_key, data);
+    }
+    for (pool_id, alts) in snapshot.alts {
+        cache.upsert_alt(
+            pool_id,
+            alts.into_iter()
+                .map(|(key, addresses)| AddressLookupTableAccount { key, addresses })
+                .collect(),
+        );
+    }
+    for (mint, mint_info) in snapshot.mint_infos {
+        cache.upsert_mint_info(mint, MintInfo::try_from(mint_info)?);
+    }
+    info!(
+        "加载Snapshot : {}, slot : {}, 池子数量 : {}",
+        path.display(),
+        snapshot.slot,
+        snapshot.pools.len()
+    );
+    Ok(LoadedSnapshot {
+        cache,
+        slot: snapshot.slot,
+        pools: snapshot.pools.into_iter().collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::snapshot_store::{load_stored_snapshot, store_snapshot};
+    use crate::dex::{CacheVersion, GlobalCache, MintInfo, TransferHook, MINT_PROGRAM_ID};
+    use solana_sdk::address_lookup_table::AddressLookupTableAccount;
+    use solana_sdk::instruction::AccountMeta;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_store_snapshot() {
+        let cache = GlobalCache::init();
+        let account = Pubkey::new_unique();
+        let version = CacheVersion::Account {
+            slot: 100,
+            write_version: 0,
+            endpoint: None,
+        };
+        cache.upsert_dynamic_with_version(account, vec![1, 2, 3], version);
+        cache.upsert_static(account, vec![4, 5]);
+        let pool_id = Pubkey::new_unique();
+        let alt = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![Pubkey::new_unique()],
+        };
+        cache.upsert_alt(pool_id, vec![alt.clone()]);
+        let mint = Pubkey::new_unique();
+        let hook_program = Pubkey::new_unique();
+        let extra = Pubkey::new_unique();
+        cache.upsert_mint_info(
+            mint,
+            MintInfo {
+                token_program: MINT_PROGRAM_ID,
+                decimals: 6,
+                transfer_fee: None,
+                transfer_hook: Some(TransferHook {
+                    program_id: hook_program,
+                    extra_account_metas: Some(vec![AccountMeta::new(extra, false)]),
+                }),
+                interest_bearing: None,
+                permanent_delegate: None,
+                unsupported_extensions: vec![],
+            },
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = store_snapshot(&cache, &[], &dir.path().join("snapshots")).unwrap();
+        assert!(path.ends_with("snapshot-100.bin"));
+        let loaded = load_stored_snapshot(&path).unwrap();
+        assert_eq!(loaded.slot, 100);
+        let entries = loaded.cache.dynamic_entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].1.version, version);
+        assert_eq!(entries[0].1.data.as_slice(), &[1, 2, 3]);
+        assert_eq!(loaded.cache.static_entries()[0].1.as_slice(), &[4, 5]);
+        assert_eq!(loaded.cache.alt_entries(), vec![(pool_id, vec![alt])]);
+        let mint_info = loaded.cache.get_mint_info(&mint).unwrap();
+        assert_eq!(mint_info.decimals, 6);
+        let hook = mint_info.transfer_hook.as_ref().unwrap();
+        assert_eq!(hook.program_id, hook_program);
+        assert_eq!(
+            hook.extra_account_metas,
+            Some(vec![AccountMeta::new(extra, false)])
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::dex::{get_cache_slot, get_stream_slot, record_quote_error, QuoteError};
+use solana_sdk::pubkey::Pubkey;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+static STALE_STATE_CONFIG: OnceCell<StaleStateConfig> = OnceCell::const_new();
+// GRPC断线后缓存不再可信，重新加载snapshot完成前暂停路由
+static CACHE_SYNCED: AtomicBool = AtomicBool::new(true);
+
+/// 触发交易写入的池子、金库在缓存中落后于交易slot时的处理
+#[derive(Debug, Clone, Copy)]
+pub struct StaleStateConfig {
+    // 允许落后的slot数量
+    pub max_slot_lag: u64,
+    // true : 只记录，仍然quote；false : 不quote
+    pub flag_only: bool,
+}
+
+pub fn init_stale_state_config(config: StaleStateConfig) -> anyhow::Result<()> {
+    Ok(STALE_STATE_CONFIG.set(config)?)
+}
+
+pub fn set_cache_synced(synced: bool) {
+    CACHE_SYNCED.store(synced, Ordering::Release);
+}
+
+#[inline]
+pub fn is_cache_synced() -> bool {
+    CACHE_SYNCED.load(Ordering::Acquire)
+}
+
+/// 触发交易在slot写入了accounts，缓存中任一账户落后超过max_slot_lag时返回StaleState
+///
+/// 账户数据有效到最后写入的slot和订阅已推送到的slot中较大的一个，长时间没有变化的账户不算落后
+///
+/// 未开启时不检查，flag_only时只记录不返回错误，没有订阅的账户不检查
+pub fn check_stale_state(
+    pool_id: &Pubkey,
+    accounts: &[Pubkey],
+    slot: u64,
+) -> Result<(), QuoteError> {
+    let config = match STALE_STATE_CONFIG.get() {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+    let stream_slot = get_stream_slot();
+    let stale = accounts.iter().find_map(|account| {
+        let cache_slot = get_cache_slot(account)?;
+        is_stale(cache_slot, stream_slot, slot, config.max_slot_lag)
+            .then_some((account, cache_slot))
+    });
+    match stale {
+        None => Ok(()),
+        Some((account, cache_slot)) => {
+            warn!(
+                "池子[{}]的账户[{}]缓存slot[{}]、订阅slot[{}]落后于交易slot[{}]",
+                pool_id, account, cache_slot, stream_slot, slot
+            );
+            record_quote_error(pool_id, QuoteError::StaleState);
+            if config.flag_only {
+                Ok(())
+            } else {
+                Err(QuoteError::StaleState)
+            }
+        }
+    }
+}
+
+/// snapshot加载的数据slot为RPC返回的slot，交易写入后还没有收到推送时视为落后
+///
+/// stream_slot : 订阅推送到的slot，在这之前没有推送的账户没有变化
+fn is_stale(cache_slot: u64, stream_slot: u64, slot: u64, max_slot_lag: u64) -> bool {
+    cache_slot.max(stream_slot).saturating_add(max_slot_lag) < slot
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::stale_state::is_stale;
+
+    #[test]
+    fn test_is_stale() {
+        assert!(!is_stale(100, 0, 100, 0));
+        assert!(!is_stale(101, 0, 100, 0));
+        assert!(is_stale(99, 0, 100, 0));
+        assert!(!is_stale(98, 0, 100, 2));
+        assert!(is_stale(97, 0, 100, 2));
+        assert!(is_stale(0, 0, 100, 2));
+        // 订阅还没有推送到交易slot
+        assert!(is_stale(90, 97, 100, 2));
+    }
+
+    #[test]
+    fn test_unchanged_account_not_stale() {
+        // 账户在很早的slot写入后没有变化，订阅已推送到交易slot
+        assert!(!is_stale(10, 100, 100, 0));
+        assert!(!is_stale(10, 98, 100, 2));
+    }
+}
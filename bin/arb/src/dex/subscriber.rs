@@ -10,14 +10,16 @@ use crate::dex_data::DexJson;
 use ahash::AHashSet;
 use anyhow::anyhow;
 use enum_dispatch::enum_dispatch;
-use futures_util::Stream;
+use futures_util::{SinkExt, Stream};
+use parking_lot::RwLock;
 use solana_sdk::pubkey::Pubkey;
 use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::OnceCell;
-use tracing::error;
+use tokio::sync::{watch, OnceCell};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
 use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient, Interceptor};
 use yellowstone_grpc_proto::geyser::subscribe_request_filter_accounts_filter::Filter;
 use yellowstone_grpc_proto::geyser::{
@@ -28,10 +30,31 @@ use yellowstone_grpc_proto::geyser::{
 };
 use yellowstone_grpc_proto::tonic::Status;
 
-static GRPC_SUBSCRIBED_ACCOUNTS: OnceCell<Arc<AHashSet<Pubkey>>> = OnceCell::const_new();
+static GRPC_SUBSCRIBED_ACCOUNTS: OnceCell<RwLock<AHashSet<Pubkey>>> = OnceCell::const_new();
 
-pub fn get_subscribed_accounts() -> Arc<AHashSet<Pubkey>> {
-    GRPC_SUBSCRIBED_ACCOUNTS.get().unwrap().clone()
+#[inline]
+pub fn is_subscribed_account(account: &Pubkey) -> bool {
+    GRPC_SUBSCRIBED_ACCOUNTS
+        .get()
+        .is_some_and(|accounts| accounts.read().contains(account))
+}
+
+/// accounts中是否有任意一个已订阅
+#[inline]
+pub fn contains_subscribed_account(accounts: &[Pubkey]) -> bool {
+    GRPC_SUBSCRIBED_ACCOUNTS.get().is_some_and(|subscribed| {
+        let subscribed = subscribed.read();
+        accounts.iter().any(|account| subscribed.contains(account))
+    })
+}
+
+pub(crate) fn replace_subscribed_accounts(accounts: AHashSet<Pubkey>) {
+    match GRPC_SUBSCRIBED_ACCOUNTS.get() {
+        Some(subscribed) => *subscribed.write() = accounts,
+        None => {
+            let _ = GRPC_SUBSCRIBED_ACCOUNTS.set(RwLock::new(accounts));
+        }
+    }
 }
 
 #[enum_dispatch]
@@ -64,10 +87,93 @@ impl Display for Subscriber {
     }
 }
 
+/// 订阅请求、订阅的账户和生成请求的DexJson，断线重连时重复使用
+#[derive(Debug, Clone)]
+pub struct SubscribePlan {
+    pub request: SubscribeRequest,
+    pub subscribed_accounts: AHashSet<Pubkey>,
+    pub dex_json: Vec<DexJson>,
+}
+
+impl SubscribePlan {
+    pub fn build(dex_json: Vec<DexJson>) -> anyhow::Result<Self> {
+        let (request, subscribed_accounts) = build_subscribe_request(dex_json.as_slice())?;
+        Ok(Self {
+            request,
+            subscribed_accounts,
+            dex_json,
+        })
+    }
+}
+
+/// 运行时池子发生变化后，接收全量的DexJson，生成新的plan发送给所有端点
+pub async fn update_subscribe_plan(
+    dex_json_receiver: flume::Receiver<Vec<DexJson>>,
+    plan_sender: watch::Sender<Arc<SubscribePlan>>,
+) {
+    while let Ok(dex_json) = dex_json_receiver.recv_async().await {
+        match SubscribePlan::build(dex_json) {
+            Ok(plan) => {
+                plan_sender.send_replace(Arc::new(plan));
+            }
+            Err(e) => error!("GRPC订阅更新失败，原因：{}", e),
+        }
+    }
+}
+
+/// 按plan发送订阅请求，返回推送的数据流和PING、更新订阅的任务，断线后需要abort该任务
+///
+/// plan_receiver : plan发生变化后重新发送订阅请求
 pub async fn grpc_subscribe(
     grpc_url: String,
-    dex_json: Vec<DexJson>,
-) -> anyhow::Result<impl Stream<Item = Result<SubscribeUpdate, Status>>> {
+    mut plan_receiver: watch::Receiver<Arc<SubscribePlan>>,
+) -> anyhow::Result<(
+    impl Stream<Item = Result<SubscribeUpdate, Status>>,
+    JoinHandle<()>,
+)> {
+    let plan = plan_receiver.borrow_and_update().clone();
+    let mut grpc_client = create_grpc_client(grpc_url).await?;
+    let (mut subscribe_sender, stream) = grpc_client
+        .subscribe_with_request(Some(plan.request.clone()))
+        .await?;
+    replace_subscribed_accounts(plan.subscribed_accounts.clone());
+    let handle = tokio::spawn(async move {
+        let mut ping = tokio::time::interval(Duration::from_secs(5));
+        ping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ping.tick().await;
+        let mut sender_closed = false;
+        loop {
+            tokio::select! {
+                _ = ping.tick() => {
+                    if let Err(e)=grpc_client.ping(1).await{
+                        error!("GRPC PING 失败，{}",e);
+                    }
+                },
+                result = plan_receiver.changed(), if !sender_closed => {
+                    match result {
+                        Ok(_) => {
+                            let plan = plan_receiver.borrow_and_update().clone();
+                            match subscribe_sender.send(plan.request.clone()).await {
+                                Ok(_) => {
+                                    replace_subscribed_accounts(plan.subscribed_accounts.clone());
+                                    info!("GRPC订阅更新成功, 池子数量 : {}", plan.dex_json.len());
+                                }
+                                Err(e) => error!("GRPC订阅更新失败，原因：{}", e),
+                            }
+                        }
+                        Err(_) => sender_closed = true,
+                    }
+                },
+            }
+        }
+    });
+    Ok((stream, handle))
+}
+
+/// 返回订阅请求和订阅的账户
+fn build_subscribe_request(
+    dex_json: &[DexJson],
+) -> anyhow::Result<(SubscribeRequest, AHashSet<Pubkey>)> {
     let mut account_subscribe_owners: AHashSet<Pubkey> =
         AHashSet::with_capacity(dex_json.len() * 3);
     let mut tx_include_owners = AHashSet::with_capacity(dex_json.len() * 3);
@@ -83,7 +189,7 @@ pub async fn grpc_subscribe(
         Subscriber::from(OrcaWhirlAccountSubscriber),
         Subscriber::from(RaydiumCPMMAccountSubscriber),
     ] {
-        match sub.get_subscription_accounts(dex_json.as_slice()) {
+        match sub.get_subscription_accounts(dex_json) {
             None => {}
             Some(accounts) => {
                 if accounts.account_subscribe_owners.is_empty()
@@ -173,26 +279,7 @@ pub async fn grpc_subscribe(
         commitment: Some(CommitmentLevel::Processed).map(|x| x as i32),
         ..Default::default()
     };
-    let mut grpc_client = create_grpc_client(grpc_url).await;
-    let (_, stream) = grpc_client
-        .subscribe_with_request(Some(subscribe_request))
-        .await?;
-    tokio::spawn(async move {
-        let mut ping = tokio::time::interval(Duration::from_secs(5));
-        ping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
-        ping.tick().await;
-        loop {
-            tokio::select! {
-                _ = ping.tick() => {
-                    if let Err(e)=grpc_client.ping(1).await{
-                        error!("GRPC PING 失败，{}",e);
-                    }
-                },
-            }
-        }
-    });
-    GRPC_SUBSCRIBED_ACCOUNTS.set(Arc::new(subscribe_accounts))?;
-    Ok(stream)
+    Ok((subscribe_request, subscribe_accounts))
 }
 
 #[derive(Debug, Default)]
@@ -204,13 +291,13 @@ pub struct SubscriptionAccounts {
     pub need_clock: bool,
 }
 
-async fn create_grpc_client(grpc_url: String) -> GeyserGrpcClient<impl Interceptor + Sized> {
+async fn create_grpc_client(
+    grpc_url: String,
+) -> anyhow::Result<GeyserGrpcClient<impl Interceptor + Sized>> {
     let use_tls = grpc_url.starts_with("https://");
-    let mut builder = GeyserGrpcClient::build_from_shared(grpc_url).unwrap();
+    let mut builder = GeyserGrpcClient::build_from_shared(grpc_url)?;
     if use_tls {
-        builder = builder
-            .tls_config(ClientTlsConfig::new().with_native_roots())
-            .unwrap();
+        builder = builder.tls_config(ClientTlsConfig::new().with_native_roots())?;
     }
     builder
         .max_decoding_message_size(100 * 1024 * 1024) // 100MB
@@ -231,5 +318,4 @@ async fn create_grpc_client(grpc_url: String) -> GeyserGrpcClient<impl Intercept
             error!("GRPC订阅: 连接GRPC服务器失败，原因: {e}");
             anyhow::anyhow!(e)
         })
-        .unwrap()
 }
@@ -5,7 +5,7 @@ use crate::dex::pump_fun::instruction::PumpFunAMMInstructionMaterialConverter;
 use crate::dex::raydium_amm::instruction::RaydiumAMMInstructionMaterialConverter;
 use crate::dex::raydium_clmm::instruction::RaydiumCLMMInstructionMaterialConverter;
 use crate::dex::raydium_cpmm::RaydiumCPMMInstructionMaterialConverter;
-use crate::dex::DexType;
+use crate::dex::{DexType, QuoteResult};
 use crate::metadata::MintAtaPair;
 use enum_dispatch::enum_dispatch;
 use solana_sdk::address_lookup_table::AddressLookupTableAccount;
@@ -14,10 +14,12 @@ use solana_sdk::pubkey::Pubkey;
 
 #[enum_dispatch]
 pub trait InstructionMaterialConverter {
+    /// quote_result为该池子的报价结果，有tick array / bin array的池子优先使用其中实际遍历的账户
     fn convert_to_instruction_material(
         &self,
         pool_id: &Pubkey,
         swap_direction: bool,
+        quote_result: Option<&QuoteResult>,
     ) -> anyhow::Result<InstructionMaterial>;
 }
 
@@ -37,6 +39,8 @@ pub struct InstructionMaterial {
     pub swap_direction: bool,
     pub account_meta: Vec<AccountMeta>,
     pub remaining_account_num: Option<u8>,
+    // token a / token b的transfer hook账户数量，位于supplemental tick array之前
+    pub transfer_hook_account_num: (Option<u8>, Option<u8>),
     pub alts: Option<Vec<AddressLookupTableAccount>>,
     pub used_atas: Vec<MintAtaPair>,
 }
@@ -55,6 +59,7 @@ impl InstructionMaterial {
             swap_direction,
             account_meta,
             remaining_account_num,
+            transfer_hook_account_num: (None, None),
             alts,
             used_atas,
         }
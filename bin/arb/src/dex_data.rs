@@ -11,7 +11,8 @@ pub fn load_dex_json(
 ) -> anyhow::Result<Vec<DexJson>> {
     info!("加载DexJson...");
     let mut dex_data: Vec<DexJson> = match File::open(dex_json_path.as_str()) {
-        Ok(file) => serde_json::from_reader(file).expect("解析【dex_data.json】失败"),
+        Ok(file) => serde_json::from_reader(file)
+            .map_err(|e| anyhow!("解析【dex_data.json】失败 : {}", e))?,
         Err(e) => {
             error!("{}", e);
             vec![]
@@ -3,7 +3,7 @@ use crate::dex::get_token_program;
 use crate::dex::{MEMO_PROGRAM, MINT_PROGRAM_ID};
 use crate::executor::Executor;
 use crate::graph::SearchResult;
-use crate::metadata::{get_arb_mint_ata, get_keypair, get_last_blockhash};
+use crate::metadata::{get_keypair, get_last_blockhash};
 use crate::HopPathSearchResult;
 use aes_gcm::aead::Aead;
 use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
@@ -3,8 +3,10 @@ use crate::HopPathSearchResult;
 use std::sync::Arc;
 
 mod jito;
+mod paper;
 
 pub use jito::*;
+pub use paper::*;
 
 #[async_trait::async_trait]
 pub trait Executor: Sync + Send {
@@ -0,0 +1,67 @@
+use crate::arb_bot::Command;
+use crate::executor::Executor;
+use crate::{HopPathSearchResult, SearchResult};
+use anyhow::anyhow;
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// 模拟执行的套利交易
+#[derive(Debug, Clone)]
+pub struct PaperTrade {
+    pub slot: u64,
+    // 触发交易签名的前4位
+    pub tx: String,
+    pub tip: u64,
+    pub result: HopPathSearchResult,
+}
+
+/// 回放时使用，记录每个找到的路径，不构建和发送交易
+pub struct PaperExecutor {
+    // 每笔bundle的tip(lamports)
+    tip: u64,
+    trades: Mutex<Vec<PaperTrade>>,
+}
+
+impl PaperExecutor {
+    pub fn new(tip: u64) -> Self {
+        Self {
+            tip,
+            trades: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn trades(&self) -> Vec<PaperTrade> {
+        self.trades.lock().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl Executor for PaperExecutor {
+    async fn initialize(_command: &Command) -> Result<Arc<dyn Executor>>
+    where
+        Self: Sized,
+    {
+        Err(anyhow!("PaperExecutor只用于回放"))
+    }
+
+    async fn execute(
+        &self,
+        hop_path_search_result: HopPathSearchResult,
+        tx: String,
+        slot: u64,
+    ) -> Result<String> {
+        let msg = format!(
+            "模拟执行, 利润 : {}, tip : {}",
+            hop_path_search_result.profit(),
+            self.tip
+        );
+        self.trades.lock().push(PaperTrade {
+            slot,
+            tx,
+            tip: self.tip,
+            result: hop_path_search_result,
+        });
+        Ok(msg)
+    }
+}
@@ -0,0 +1,217 @@
+use crate::graph::{closed_form_amount_in, EdgeIdentifier, QuotePass};
+
+/// 黄金分割比例的倒数
+const INV_PHI: f64 = 0.618_033_988_749_895;
+
+/// 最佳amount_in搜索配置，默认不搜索
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AmountSearchConfig {
+    // 搜索区间下限
+    pub min_amount_in: u64,
+    // 最大迭代次数，0 : 不搜索，只使用固定的amount_in
+    pub max_iterations: usize,
+    // 区间收敛到该宽度后停止
+    pub precision: u64,
+}
+
+/// 在 [min_amount_in, max_amount_in] 中寻找利润最大的amount_in，固定的amount_in超过max_amount_in时只搜索
+///
+/// edges : 依次经过的池子，全部为恒定乘积池子时直接求解析解，否则使用黄金分割搜索
+///
+/// pass : 本轮路由解码过的池子状态，搜索过程中的quote不再读取缓存
+///
+/// 返回 (amount_in, profit)，结果不会差于固定的amount_in
+pub(crate) fn find_best_amount_in(
+    config: &AmountSearchConfig,
+    edges: &[&EdgeIdentifier],
+    amount_in: u64,
+    max_amount_in: u64,
+    pass: &QuotePass,
+) -> Option<(u64, i64)> {
+    // 边际汇率之积小于1时任何数量都不盈利，跳过完整的quote
+    if marginal_rate(edges, pass).is_some_and(|rate| rate < 1.0) {
+        return None;
+    }
+    search_best_amount_in(
+        config,
+        amount_in,
+        max_amount_in,
+        || closed_form_amount_in(edges),
+        |amount| {
+            edges
+                .iter()
+                .try_fold(amount, |amount, edge| pass.quote(edge, amount))
+        },
+    )
+}
+
+/// 路径起点的边际汇率(各池子边际汇率之积)，任一池子无法计算时返回None
+pub(crate) fn marginal_rate(edges: &[&EdgeIdentifier], pass: &QuotePass) -> Option<f64> {
+    edges
+        .iter()
+        .try_fold(1.0, |rate, edge| Some(rate * pass.marginal_price(edge)?))
+}
+
+/// closed_form : 解析解(没有则返回None，使用黄金分割搜索)
+///
+/// quoter : amount_in -> 经过整条路径后的amount_out
+pub(crate) fn search_best_amount_in<C, Q>(
+    config: &AmountSearchConfig,
+    amount_in: u64,
+    max_amount_in: u64,
+    closed_form: C,
+    quoter: Q,
+) -> Option<(u64, i64)>
+where
+    C: FnOnce() -> Option<f64>,
+    Q: Fn(u64) -> Option<u64>,
+{
+    let profit = |amount: u64| quoter(amount).map(|amount_out| amount_out as i64 - amount as i64);
+    let fixed = (amount_in <= max_amount_in)
+        .then(|| profit(amount_in).map(|p| (amount_in, p)))
+        .flatten();
+    if config.max_iterations == 0 {
+        return fixed;
+    }
+    let min_amount_in = config.min_amount_in.max(1);
+    if min_amount_in >= max_amount_in {
+        return fixed;
+    }
+    let searched = match closed_form() {
+        // 解析解基于浮点运算，用真实的quote复核
+        Some(optimal) => {
+            let optimal = (optimal.round() as u64).clamp(min_amount_in, max_amount_in);
+            profit(optimal).map(|p| (optimal, p))
+        }
+        None => golden_section_search(
+            min_amount_in,
+            max_amount_in,
+            config.max_iterations,
+            config.precision,
+            profit,
+        ),
+    };
+    match (fixed, searched) {
+        (Some(fixed), Some(searched)) => Some(if searched.1 > fixed.1 {
+            searched
+        } else {
+            fixed
+        }),
+        (fixed, searched) => fixed.or(searched),
+    }
+}
+
+/// 黄金分割搜索，要求 profit 在 [left, right] 上单峰
+///
+/// 路径的利润函数是凹函数且 profit(0) = 0，所以区间下限处不盈利时整个区间都不会盈利，直接返回
+pub(crate) fn golden_section_search<F>(
+    left: u64,
+    right: u64,
+    max_iterations: usize,
+    precision: u64,
+    profit: F,
+) -> Option<(u64, i64)>
+where
+    F: Fn(u64) -> Option<i64>,
+{
+    if left >= right {
+        return None;
+    }
+    let evaluate = |amount: f64| {
+        let amount = amount.round() as u64;
+        (amount, profit(amount).unwrap_or(i64::MIN))
+    };
+    let lower = evaluate(left as f64);
+    if lower.1 <= 0 {
+        return None;
+    }
+    let (mut a, mut b) = (left as f64, right as f64);
+    let mut c = evaluate(b - (b - a) * INV_PHI);
+    let mut d = evaluate(a + (b - a) * INV_PHI);
+    let mut iterations = 0;
+    while b - a > precision.max(1) as f64 && iterations < max_iterations {
+        if c.1 < d.1 {
+            a = c.0 as f64;
+            c = d;
+            d = evaluate(a + (b - a) * INV_PHI);
+        } else {
+            b = d.0 as f64;
+            d = c;
+            c = evaluate(b - (b - a) * INV_PHI);
+        }
+        iterations += 1;
+    }
+    [lower, c, d]
+        .into_iter()
+        .filter(|(_, profit)| *profit > i64::MIN)
+        .max_by_key(|(_, profit)| *profit)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graph::amount_search::{
+        golden_section_search, search_best_amount_in, AmountSearchConfig,
+    };
+
+    #[test]
+    fn test_golden_section_search() {
+        // 利润峰值在 1_000_000 附近
+        let profit = |x: u64| {
+            let x = x as i64;
+            Some(2_000_000 - (x - 1_000_000).abs() * 2 + x / 1_000)
+        };
+        let (amount_in, best_profit) =
+            golden_section_search(1, 10_000_000, 100, 10, profit).unwrap();
+        assert!(amount_in.abs_diff(1_000_000) <= 10);
+        assert!(best_profit >= 2_000_980);
+    }
+
+    #[test]
+    fn test_golden_section_search_unprofitable() {
+        let profit = |x: u64| Some(-(x as i64));
+        assert!(golden_section_search(1, 10_000_000, 100, 10, profit).is_none());
+    }
+
+    #[test]
+    fn test_search_below_fixed_amount_in() {
+        let config = AmountSearchConfig {
+            min_amount_in: 1_000,
+            max_iterations: 100,
+            precision: 10,
+        };
+        // 利润峰值在 500_000 附近，固定的amount_in超过钱包余额
+        let quoter = |x: u64| Some(x + 500_000 - x.abs_diff(500_000));
+        let (amount_in, profit) =
+            search_best_amount_in(&config, 2_000_000, 1_000_000, || None, quoter).unwrap();
+        assert!(amount_in <= 1_000_000);
+        assert!(amount_in.abs_diff(500_000) <= 10);
+        assert!(profit >= 499_990);
+    }
+
+    #[test]
+    fn test_fixed_amount_in_without_search() {
+        let quoter = |x: u64| Some(x + 500_000 - x.abs_diff(500_000));
+        // 默认不搜索，只使用固定的amount_in
+        assert_eq!(
+            search_best_amount_in(
+                &AmountSearchConfig::default(),
+                100_000,
+                1_000_000,
+                || None,
+                quoter
+            ),
+            Some((100_000, 100_000))
+        );
+        // 固定的amount_in超过钱包余额
+        assert_eq!(
+            search_best_amount_in(
+                &AmountSearchConfig::default(),
+                2_000_000,
+                1_000_000,
+                || None,
+                quoter
+            ),
+            None
+        );
+    }
+}
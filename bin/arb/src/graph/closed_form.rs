@@ -0,0 +1,114 @@
+use crate::dex::{get_quoter_type, ConstantProductReserves, Quoter};
+use crate::graph::EdgeIdentifier;
+
+/// 多个恒定乘积池子串联后，amount_out = a * amount_in / (b + c * amount_in)
+#[derive(Debug, Clone, Copy)]
+struct CompositeCurve {
+    a: f64,
+    b: f64,
+    c: f64,
+}
+
+impl CompositeCurve {
+    fn identity() -> Self {
+        Self {
+            a: 1.0,
+            b: 1.0,
+            c: 0.0,
+        }
+    }
+
+    /// 在当前曲线后再串联一个池子
+    fn then(self, reserves: &ConstantProductReserves) -> Self {
+        let reserve_in = reserves.reserve_in as f64;
+        let reserve_out = reserves.reserve_out as f64;
+        Self {
+            a: reserves.fee_out * reserve_out * reserves.fee_in * self.a,
+            b: reserve_in * self.b,
+            c: reserve_in * self.c + reserves.fee_in * self.a,
+        }
+    }
+
+    /// profit(x) = a * x / (b + c * x) - x 的极大值点
+    ///
+    /// profit'(x) = a * b / (b + c * x)^2 - 1 = 0  =>  x = (√(a * b) - b) / c
+    fn optimal_amount_in(&self) -> Option<f64> {
+        // 起始边际汇率 a / b <= 1 时不存在盈利的amount_in
+        if self.a <= self.b || self.c <= 0.0 {
+            return None;
+        }
+        let amount_in = ((self.a * self.b).sqrt() - self.b) / self.c;
+        (amount_in.is_finite() && amount_in > 0.0).then_some(amount_in)
+    }
+}
+
+/// 路径上所有池子都是恒定乘积时，直接解出利润最大的amount_in
+///
+/// 只要有一个池子无法用恒定乘积描述(CLMM / DLMM / Whirlpool等)就返回None，由调用方回退到数值搜索
+pub(crate) fn closed_form_amount_in(edges: &[&EdgeIdentifier]) -> Option<f64> {
+    edges
+        .iter()
+        .try_fold(CompositeCurve::identity(), |curve, edge| {
+            let reserves = get_quoter_type(edge.dex_type)
+                .ok()?
+                .constant_product_reserves(edge.swap_direction, &edge.pool_id()?)?;
+            Some(curve.then(&reserves))
+        })?
+        .optimal_amount_in()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dex::ConstantProductReserves;
+    use crate::graph::closed_form::CompositeCurve;
+
+    fn swap(reserves: &ConstantProductReserves, amount_in: f64) -> f64 {
+        let amount_in = amount_in * reserves.fee_in;
+        reserves.fee_out * reserves.reserve_out as f64 * amount_in
+            / (reserves.reserve_in as f64 + amount_in)
+    }
+
+    #[test]
+    fn test_closed_form_amount_in() {
+        let first =
+            ConstantProductReserves::new(1_000_000_000, 2_100_000_000, 0.9975, 1.0).unwrap();
+        let second =
+            ConstantProductReserves::new(2_000_000_000, 1_000_000_000, 1.0, 0.997).unwrap();
+        let curve = CompositeCurve::identity().then(&first).then(&second);
+        let profit = |amount_in: f64| swap(&second, swap(&first, amount_in)) - amount_in;
+        let amount_in = curve.optimal_amount_in().unwrap();
+        // 串联后的曲线与逐个池子兑换的结果一致
+        let amount_out = curve.a * amount_in / (curve.b + curve.c * amount_in);
+        assert!((amount_out - swap(&second, swap(&first, amount_in))).abs() < 1e-3);
+        // 极值点两侧利润都更低
+        assert!(profit(amount_in) > 0.0);
+        assert!(profit(amount_in) >= profit(amount_in * 0.99));
+        assert!(profit(amount_in) >= profit(amount_in * 1.01));
+    }
+
+    #[test]
+    fn test_closed_form_amount_in_unprofitable() {
+        let first =
+            ConstantProductReserves::new(1_000_000_000, 2_000_000_000, 0.9975, 1.0).unwrap();
+        let second =
+            ConstantProductReserves::new(2_000_000_000, 1_000_000_000, 0.9975, 1.0).unwrap();
+        let curve = CompositeCurve::identity().then(&first).then(&second);
+        assert!(curve.optimal_amount_in().is_none());
+    }
+
+    #[test]
+    fn test_marginal_price() {
+        let first =
+            ConstantProductReserves::new(1_000_000_000, 2_100_000_000, 0.9975, 1.0).unwrap();
+        let second =
+            ConstantProductReserves::new(2_000_000_000, 1_000_000_000, 1.0, 0.997).unwrap();
+        let curve = CompositeCurve::identity().then(&first).then(&second);
+        // 串联曲线在0处的斜率即各池子边际汇率之积
+        let rate = first.marginal_price() * second.marginal_price();
+        assert!((rate - curve.a / curve.b).abs() < 1e-12);
+        // 任意数量的实际汇率都不高于边际汇率
+        for amount_in in [1.0, 1_000.0, 1_000_000.0, 1_000_000_000.0] {
+            assert!(swap(&second, swap(&first, amount_in)) / amount_in <= rate);
+        }
+    }
+}
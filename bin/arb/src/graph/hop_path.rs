@@ -2,9 +2,13 @@ use crate::dex::DexType;
 use crate::dex::InstructionMaterialConverter;
 use crate::dex::Quoter;
 use crate::dex::{get_instruction_builder, InstructionMaterial};
-use crate::dex::{get_quoter_type, QuoteResult};
+use crate::dex::{get_quoter_type, record_quote_error, PreparedQuoteType, QuoteResult};
 use crate::dex_data::DexJson;
-use crate::{TwoHopPath, TwoHopPathSearchResult};
+use crate::{
+    AmountSearchConfig, MultiHopPath, MultiHopPathSearchResult, RouteStep, ThreeHopPath,
+    ThreeHopPathSearchResult, TwoHopPath, TwoHopPathSearchResult,
+};
+use ahash::{AHashMap, AHashSet};
 use anyhow::anyhow;
 use async_trait::async_trait;
 use enum_dispatch::enum_dispatch;
@@ -15,10 +19,52 @@ use std::sync::Arc;
 use tokio::sync::OnceCell;
 use tracing::info;
 
-/// Mint 全局索引，节省内存
-pub(crate) static MINT_INDEX: OnceCell<Arc<Vec<Pubkey>>> = OnceCell::const_new();
-/// Pool 全局索引，节省内存
-pub(crate) static POOL_INDEX: OnceCell<Arc<Vec<Pubkey>>> = OnceCell::const_new();
+/// Mint 全局索引(去重)，节省内存，运行时新增池子只追加不删除
+pub(crate) static MINT_INDEX: OnceCell<RwLock<PubkeyIndex>> = OnceCell::const_new();
+/// Pool 全局索引，节省内存，运行时新增池子只追加不删除
+pub(crate) static POOL_INDEX: OnceCell<RwLock<PubkeyIndex>> = OnceCell::const_new();
+
+/// Pubkey 与紧凑的index互相转换
+#[derive(Debug, Default)]
+pub(crate) struct PubkeyIndex {
+    keys: Vec<Pubkey>,
+    positions: AHashMap<Pubkey, usize>,
+}
+
+impl PubkeyIndex {
+    /// 已存在时返回原有的index
+    pub(crate) fn intern(&mut self, key: Pubkey) -> usize {
+        *self.positions.entry(key).or_insert_with(|| {
+            self.keys.push(key);
+            self.keys.len() - 1
+        })
+    }
+
+    #[inline]
+    pub(crate) fn position(&self, key: &Pubkey) -> Option<usize> {
+        self.positions.get(key).copied()
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, index: usize) -> Option<&Pubkey> {
+        self.keys.get(index)
+    }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+impl FromIterator<Pubkey> for PubkeyIndex {
+    fn from_iter<T: IntoIterator<Item = Pubkey>>(iter: T) -> Self {
+        let mut index = PubkeyIndex::default();
+        for key in iter {
+            index.intern(key);
+        }
+        index
+    }
+}
 
 #[enum_dispatch(HopPathTypes)]
 pub(crate) trait HopPath: Send + Sync {
@@ -28,6 +74,16 @@ pub(crate) trait HopPath: Send + Sync {
         follow_mint_index: &[usize],
     ) -> anyhow::Result<()>;
 
+    /// 运行时新增池子，只更新经过新池子的路径
+    fn add_edges(
+        &mut self,
+        edge_identifiers: &[EdgeIdentifier],
+        follow_mint_index: &[usize],
+    ) -> anyhow::Result<()>;
+
+    /// 运行时移除池子，只更新经过被移除池子的路径
+    fn remove_pools(&mut self, pool_indexes: &AHashSet<usize>) -> anyhow::Result<()>;
+
     fn find_best_hop_path(
         &self,
         pool_id: Pubkey,
@@ -35,12 +91,15 @@ pub(crate) trait HopPath: Send + Sync {
         amount_in: u64,
         max_amount_in: u64,
         min_profit: u64,
+        amount_search: &AmountSearchConfig,
     ) -> Option<HopPathSearchResult>;
 }
 
 #[enum_dispatch]
 pub enum HopPathTypes {
     TwoHop(TwoHopPath),
+    ThreeHop(ThreeHopPath),
+    MultiHop(MultiHopPath),
 }
 
 #[enum_dispatch(HopPathSearchResult)]
@@ -51,6 +110,9 @@ pub trait SearchResult {
 
     fn convert_to_instruction_materials(&self) -> anyhow::Result<Vec<InstructionMaterial>>;
 
+    /// 与instruction materials一一对应的route plan
+    fn route_steps(&self) -> Vec<RouteStep>;
+
     fn information(&self) -> String;
 }
 
@@ -58,6 +120,8 @@ pub trait SearchResult {
 #[enum_dispatch]
 pub enum HopPathSearchResult {
     TwoHop(TwoHopPathSearchResult),
+    ThreeHop(ThreeHopPathSearchResult),
+    MultiHop(MultiHopPathSearchResult),
 }
 
 pub fn init_graph(
@@ -67,17 +131,29 @@ pub fn init_graph(
 ) -> anyhow::Result<()> {
     info!("初始化Graph...");
     // 初始化 pool 全局索引
-    POOL_INDEX.set(Arc::new(
-        dex_json.iter().map(|v| v.pool).collect::<Vec<_>>(),
-    ))?;
-    // 初始化 mint 全局索引
-    MINT_INDEX.set(Arc::new(
-        dex_json
-            .iter()
-            .map(|v| vec![v.mint_a, v.mint_b])
-            .flatten()
-            .collect::<Vec<_>>(),
-    ))?;
+    POOL_INDEX.set(RwLock::new(dex_json.iter().map(|v| v.pool).collect()))?;
+    // 初始化 mint 全局索引，同一个Mint只保留一个index
+    let mint_index = dex_json
+        .iter()
+        .flat_map(|v| [v.mint_a, v.mint_b])
+        .collect::<PubkeyIndex>();
+    info!(
+        "Pool数量 : {}, Mint数量 : {}",
+        dex_json.len(),
+        mint_index.len()
+    );
+    MINT_INDEX.set(RwLock::new(mint_index))?;
+    build_hop_paths(dex_json, follow_mints, hop_paths.as_slice());
+    info!("初始化Graph结束");
+    Ok(())
+}
+
+/// 按已初始化的pool、mint索引构建图，回放时不同的配置各自构建
+pub fn build_hop_paths(
+    dex_json: &[DexJson],
+    follow_mints: &[Pubkey],
+    hop_paths: &[RwLock<HopPathTypes>],
+) {
     // 关注的Mint的index
     let follow_mint_index = follow_mints
         .iter()
@@ -89,14 +165,67 @@ pub fn init_graph(
         .map(|v| EdgeIdentifier::new(v).unwrap())
         .flatten()
         .collect::<Vec<_>>();
-    // 构建图(2 hop)
+    // 构建图(2 hop / 3 hop / N hop)
     hop_paths.iter().for_each(|mut hop_path| {
         hop_path
             .write()
             .build_graph(edge_identifiers.as_slice(), follow_mint_index.as_slice())
             .expect("初始化Graph失败");
     });
-    info!("初始化Graph结束");
+}
+
+/// 运行时新增池子，追加索引并更新图
+pub fn add_pools_to_graph(
+    dex_json: &[DexJson],
+    follow_mints: &[Pubkey],
+    hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
+) -> anyhow::Result<()> {
+    {
+        let mut pool_index = POOL_INDEX
+            .get()
+            .ok_or(anyhow!("Graph未初始化"))?
+            .write();
+        let mut mint_index = MINT_INDEX
+            .get()
+            .ok_or(anyhow!("Graph未初始化"))?
+            .write();
+        for json in dex_json {
+            pool_index.intern(json.pool);
+            mint_index.intern(json.mint_a);
+            mint_index.intern(json.mint_b);
+        }
+    }
+    let follow_mint_index = follow_mints
+        .iter()
+        .filter_map(|v| find_mint_position(v))
+        .collect::<Vec<_>>();
+    let edge_identifiers = dex_json
+        .iter()
+        .filter_map(EdgeIdentifier::new)
+        .flatten()
+        .collect::<Vec<_>>();
+    for hop_path in hop_paths.iter() {
+        hop_path
+            .write()
+            .add_edges(edge_identifiers.as_slice(), follow_mint_index.as_slice())?;
+    }
+    info!("Graph新增池子数量 : {}", dex_json.len());
+    Ok(())
+}
+
+/// 运行时移除池子，索引保留(index不复用)，只从图中删除
+pub fn remove_pools_from_graph(
+    pool_ids: &[Pubkey],
+    hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
+) -> anyhow::Result<()> {
+    let pool_indexes = pool_ids
+        .iter()
+        .filter_map(find_pool_position)
+        .collect::<AHashSet<_>>();
+    for hop_path in hop_paths.iter() {
+        hop_path.write().remove_pools(&pool_indexes)?;
+    }
+    info!("Graph移除池子数量 : {}", pool_indexes.len());
     Ok(())
 }
 
@@ -112,42 +241,138 @@ pub struct EdgeIdentifier {
 }
 
 impl EdgeIdentifier {
+    /// 每个pool生成两个方向的边
     pub(crate) fn new(dex_json: &DexJson) -> Option<Vec<Self>> {
-        None
+        let dex_type = DexType::try_from(&dex_json.owner).ok()?;
+        let pool = find_pool_position(&dex_json.pool)?;
+        let mint_0 = find_mint_position(&dex_json.mint_a)?;
+        let mint_1 = find_mint_position(&dex_json.mint_b)?;
+        Some(
+            [true, false]
+                .into_iter()
+                .map(|swap_direction| Self {
+                    dex_type,
+                    pool,
+                    mint_0,
+                    mint_1,
+                    swap_direction,
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn quote_result(&self, amount_in: u64) -> Option<QuoteResult> {
+        let pool_id = self.pool_id()?;
+        let quoter = get_quoter_type(self.dex_type).ok()?;
+        quoter
+            .quote(amount_in, self.swap_direction, &pool_id)
+            .inspect_err(|e| record_quote_error(&pool_id, *e))
+            .ok()
+    }
+
+    /// 解码池子当前的状态，见 Quoter::prepare
+    pub(crate) fn prepare(&self) -> Option<PreparedQuoteType> {
+        let pool_id = self.pool_id()?;
+        let quoter = get_quoter_type(self.dex_type).ok()?;
+        quoter
+            .prepare(&pool_id)
+            .inspect_err(|e| record_quote_error(&pool_id, *e))
+            .ok()
     }
 
-    pub(crate) fn quote(&self, amount_in: u64) -> Option<u64> {
-        let pool_id = self.pool_id().unwrap();
+    /// 扣除手续费后的边际汇率，见 Quoter::marginal_price
+    pub(crate) fn marginal_price(&self) -> Option<f64> {
+        let pool_id = self.pool_id()?;
         let quoter = get_quoter_type(self.dex_type).ok()?;
-        let quote_result: QuoteResult = quoter.quote(amount_in, self.swap_direction, pool_id)?;
-        Some(quote_result.amount_out)
+        quoter.marginal_price(self.swap_direction, &pool_id)
     }
 
-    pub(crate) fn get_instruction_material(&self) -> anyhow::Result<InstructionMaterial> {
+    pub(crate) fn get_instruction_material(
+        &self,
+        quote_result: Option<&QuoteResult>,
+    ) -> anyhow::Result<InstructionMaterial> {
         let pool_id = self
             .pool_id()
             .ok_or(anyhow!("无法通过index[{}]找到PoolId", self.pool))?;
-        get_instruction_builder(&self.dex_type)?
-            .convert_to_instruction_material(pool_id, self.swap_direction)
+        get_instruction_builder(&self.dex_type)?.convert_to_instruction_material(
+            &pool_id,
+            self.swap_direction,
+            quote_result,
+        )
     }
 
+    /// 输入的Mint index
     #[inline]
-    pub(crate) fn pool_id(&self) -> Option<&Pubkey> {
-        POOL_INDEX
-            .get()?
-            .get(self.pool)
-            .map_or(None, |pool| Some(pool))
+    pub(crate) fn input_mint(&self) -> usize {
+        if self.swap_direction {
+            self.mint_0
+        } else {
+            self.mint_1
+        }
     }
+
+    /// 输出的Mint index
+    #[inline]
+    pub(crate) fn output_mint(&self) -> usize {
+        if self.swap_direction {
+            self.mint_1
+        } else {
+            self.mint_0
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pool_id(&self) -> Option<Pubkey> {
+        POOL_INDEX.get()?.read().get(self.pool).copied()
+    }
+}
+
+/// 从amount_in开始沿环路依次重放quote，用每条边的报价结果生成指令
+///
+/// 某条边quote失败时，该边及之后的边不再传入报价结果
+pub(crate) fn cycle_instruction_materials(
+    edges: &[&EdgeIdentifier],
+    amount_in: u64,
+) -> anyhow::Result<Vec<InstructionMaterial>> {
+    let mut amount = Some(amount_in);
+    edges
+        .iter()
+        .map(|edge| {
+            let quote_result = amount.and_then(|amount| edge.quote_result(amount));
+            amount = quote_result.as_ref().map(|q| q.amount_out);
+            edge.get_instruction_material(quote_result.as_ref())
+        })
+        .collect()
 }
 
 pub(crate) fn find_pool_position(pool_id: &Pubkey) -> Option<usize> {
-    POOL_INDEX.get()?.iter().position(|v| v == pool_id)
+    POOL_INDEX.get()?.read().position(pool_id)
 }
 
 pub(crate) fn find_mint_position(mint: &Pubkey) -> Option<usize> {
-    MINT_INDEX.get()?.iter().position(|v| v == mint)
+    MINT_INDEX.get()?.read().position(mint)
 }
 
 pub(crate) fn find_mint_by_index(index: usize) -> Option<Pubkey> {
-    MINT_INDEX.get()?.get(index).cloned()
+    MINT_INDEX.get()?.read().get(index).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graph::hop_path::PubkeyIndex;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_pubkey_index() {
+        let mint_a = Pubkey::new_unique();
+        let mint_b = Pubkey::new_unique();
+        let index = [mint_a, mint_b, mint_a, mint_b]
+            .into_iter()
+            .collect::<PubkeyIndex>();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.position(&mint_a), Some(0));
+        assert_eq!(index.position(&mint_b), Some(1));
+        assert_eq!(index.get(1), Some(&mint_b));
+        assert_eq!(index.position(&Pubkey::new_unique()), None);
+    }
 }
@@ -1,5 +1,17 @@
+mod amount_search;
+mod closed_form;
 mod hop_path;
+mod multi_hop;
+mod quote_pass;
+mod split_route;
+mod three_hop;
 mod two_hop;
 
+pub use amount_search::*;
+pub use closed_form::*;
 pub use hop_path::*;
+pub use multi_hop::*;
+pub(crate) use quote_pass::*;
+pub use split_route::*;
+pub use three_hop::*;
 pub use two_hop::*;
@@ -0,0 +1,353 @@
+use crate::dex::InstructionMaterial;
+use crate::graph::{
+    cycle_instruction_materials, cycle_route_steps, find_best_amount_in, find_mint_by_index,
+    find_mint_position, find_pool_position, AmountSearchConfig, EdgeIdentifier, HopPath, QuotePass,
+    RouteStep,
+};
+use crate::{HopPathSearchResult, SearchResult};
+use ahash::{AHashMap, AHashSet};
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use tracing::info;
+
+/// 不预先生成路径，将每个EdgeIdentifier视为带权边(权重 : -ln(扣除手续费后的边际汇率))，
+/// 从触发的pool出发，用有界的Bellman-Ford搜索长度不超过 max_hops 的负权环，
+/// 只对候选环做真实的quote
+pub struct MultiHopPath {
+    max_hops: usize,
+    graph: MultiHopGraph,
+}
+
+impl MultiHopPath {
+    pub fn new(max_hops: usize) -> Self {
+        Self {
+            max_hops: max_hops.max(2),
+            graph: MultiHopGraph::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct MultiHopGraph {
+    edges: Vec<Arc<EdgeIdentifier>>,
+    // 输入Mint -> 边
+    edges_by_input_mint: AHashMap<usize, Vec<usize>>,
+    // pool -> 边
+    edges_by_pool: AHashMap<usize, Vec<usize>>,
+    follow_mint_index: Vec<usize>,
+    // 关注的Mint -> 各Mint到该Mint的最少跳数，构建图时计算
+    distances: AHashMap<usize, AHashMap<usize, usize>>,
+}
+
+impl MultiHopGraph {
+    fn push_edges(&mut self, edges: impl IntoIterator<Item = Arc<EdgeIdentifier>>) {
+        for edge in edges {
+            let index = self.edges.len();
+            self.edges_by_input_mint
+                .entry(edge.input_mint())
+                .or_default()
+                .push(index);
+            self.edges_by_pool.entry(edge.pool).or_default().push(index);
+            self.edges.push(edge);
+        }
+    }
+
+    /// 边变化后重新计算到各关注的Mint的跳数
+    fn update_distances(&mut self, max_hops: usize) {
+        self.distances = self
+            .follow_mint_index
+            .iter()
+            .map(|mint| (*mint, self.hop_distances(*mint, max_hops)))
+            .collect();
+    }
+
+    /// 到 source 的最少跳数(每个pool都有双向边，等价于无向图)
+    fn hop_distances(&self, source: usize, max_hops: usize) -> AHashMap<usize, usize> {
+        let mut distances = AHashMap::with_capacity(1_000);
+        let mut queue = VecDeque::with_capacity(1_000);
+        distances.insert(source, 0);
+        queue.push_back(source);
+        while let Some(mint) = queue.pop_front() {
+            let distance = distances[&mint];
+            if distance >= max_hops {
+                continue;
+            }
+            for edge_index in self.edges_by_input_mint.get(&mint).into_iter().flatten() {
+                let next_mint = self.edges[*edge_index].output_mint();
+                if !distances.contains_key(&next_mint) {
+                    distances.insert(next_mint, distance + 1);
+                    queue.push_back(next_mint);
+                }
+            }
+        }
+        distances
+    }
+}
+
+/// 搜索过程中到达某个 (mint, 是否经过触发pool) 状态的最优路径
+#[derive(Clone)]
+struct Label {
+    // 累计权重 : Σ -ln(边际汇率)
+    weight: f64,
+    edges: Vec<usize>,
+}
+
+impl HopPath for MultiHopPath {
+    fn build_graph(
+        &mut self,
+        edge_identifiers: &[EdgeIdentifier],
+        follow_mint_index: &[usize],
+    ) -> anyhow::Result<()> {
+        self.graph = MultiHopGraph::default();
+        self.add_edges(edge_identifiers, follow_mint_index)?;
+        info!(
+            "N hop 边数量 : {}, 最大长度 : {}",
+            self.graph.edges.len(),
+            self.max_hops
+        );
+        Ok(())
+    }
+
+    fn add_edges(
+        &mut self,
+        edge_identifiers: &[EdgeIdentifier],
+        follow_mint_index: &[usize],
+    ) -> anyhow::Result<()> {
+        self.graph
+            .push_edges(edge_identifiers.iter().cloned().map(Arc::new));
+        self.graph.follow_mint_index = follow_mint_index.to_vec();
+        self.graph.update_distances(self.max_hops);
+        Ok(())
+    }
+
+    fn remove_pools(&mut self, pool_indexes: &AHashSet<usize>) -> anyhow::Result<()> {
+        // 边以下标引用，删除后整体重建索引
+        let edges = std::mem::take(&mut self.graph.edges);
+        let follow_mint_index = std::mem::take(&mut self.graph.follow_mint_index);
+        self.graph = MultiHopGraph {
+            follow_mint_index,
+            ..Default::default()
+        };
+        self.graph.push_edges(
+            edges
+                .into_iter()
+                .filter(|edge| !pool_indexes.contains(&edge.pool)),
+        );
+        self.graph.update_distances(self.max_hops);
+        Ok(())
+    }
+
+    fn find_best_hop_path(
+        &self,
+        pool_id: Pubkey,
+        arb_mint: Arc<Pubkey>,
+        amount_in: u64,
+        max_amount_in: u64,
+        min_profit: u64,
+        amount_search: &AmountSearchConfig,
+    ) -> Option<HopPathSearchResult> {
+        let graph = &self.graph;
+        let pool_index = find_pool_position(&pool_id)?;
+        let start_mint = find_mint_position(arb_mint.as_ref())?;
+        let distance_to_start = graph.distances.get(&start_mint)?;
+        // 触发pool的边 (输入Mint, 输出Mint)
+        let trigger_edges = &graph
+            .edges_by_pool
+            .get(&pool_index)?
+            .iter()
+            .filter_map(|edge_index| {
+                let edge = &graph.edges[*edge_index];
+                Some((
+                    *distance_to_start.get(&edge.input_mint())?,
+                    *distance_to_start.get(&edge.output_mint())?,
+                ))
+            })
+            .collect::<Vec<_>>();
+        // 松弛时的边际汇率和复核时的quote共用，同一池子只解码一次
+        let pass = &QuotePass::default();
+
+        let mut layer: AHashMap<(usize, bool), Label> = AHashMap::new();
+        layer.insert(
+            (start_mint, false),
+            Label {
+                weight: 0.0,
+                edges: vec![],
+            },
+        );
+        let mut candidates = Vec::new();
+        for hop in 1..=self.max_hops {
+            let remaining = self.max_hops - hop;
+            let relaxations = layer
+                .par_iter()
+                .flat_map_iter(|((mint, passed_trigger), label)| {
+                    graph
+                        .edges_by_input_mint
+                        .get(mint)
+                        .into_iter()
+                        .flatten()
+                        .filter_map(move |edge_index| {
+                            let edge = &graph.edges[*edge_index];
+                            let output_mint = edge.output_mint();
+                            // 简单环 : pool和中间Mint都不能重复
+                            if label
+                                .edges
+                                .iter()
+                                .any(|used| graph.edges[*used].pool == edge.pool)
+                                || (output_mint != start_mint
+                                    && label.edges.iter().any(|used| {
+                                        graph.edges[*used].output_mint() == output_mint
+                                    }))
+                            {
+                                return None;
+                            }
+                            let passed_trigger = *passed_trigger || edge.pool == pool_index;
+                            let distance = distance_to_start.get(&output_mint).copied();
+                            let reachable = if output_mint == start_mint {
+                                passed_trigger && hop >= 2
+                            } else if passed_trigger {
+                                distance.is_some_and(|distance| distance <= remaining)
+                            } else {
+                                // 到触发pool的跳数不小于两者到起点跳数之差
+                                distance.is_some_and(|distance| {
+                                    trigger_edges.iter().any(|(input, output)| {
+                                        distance.abs_diff(*input) + 1 + output <= remaining
+                                    })
+                                })
+                            };
+                            if !reachable {
+                                return None;
+                            }
+                            let price = pass.marginal_price(edge)?;
+                            if price <= 0.0 {
+                                return None;
+                            }
+                            let mut edges = label.edges.clone();
+                            edges.push(*edge_index);
+                            Some((
+                                (output_mint, passed_trigger),
+                                Label {
+                                    weight: label.weight - price.ln(),
+                                    edges,
+                                },
+                            ))
+                        })
+                })
+                .collect::<Vec<_>>();
+            let mut next_layer: AHashMap<(usize, bool), Label> =
+                AHashMap::with_capacity(relaxations.len());
+            for (state, label) in relaxations {
+                if state.0 == start_mint {
+                    // 负权环
+                    if label.weight < 0.0 {
+                        candidates.push(label.edges);
+                    }
+                    continue;
+                }
+                match next_layer.get(&state) {
+                    Some(previous) if previous.weight <= label.weight => {}
+                    _ => {
+                        next_layer.insert(state, label);
+                    }
+                }
+            }
+            if next_layer.is_empty() {
+                break;
+            }
+            layer = next_layer;
+        }
+
+        // 边际汇率只是上界，用真实的quote复核候选环，同时搜索最佳amount_in
+        candidates
+            .into_par_iter()
+            .filter_map(|edge_indexes| {
+                let edges = edge_indexes
+                    .into_iter()
+                    .map(|index| graph.edges[index].clone())
+                    .collect::<Vec<_>>();
+                let (best_amount_in, profit) = find_best_amount_in(
+                    amount_search,
+                    &edges.iter().map(|edge| edge.as_ref()).collect::<Vec<_>>(),
+                    amount_in,
+                    max_amount_in,
+                    pass,
+                )?;
+                (profit >= min_profit as i64).then_some((edges, best_amount_in, profit))
+            })
+            .max_by_key(|(_, _, profit)| *profit)
+            .map(|(edges, best_amount_in, profit)| {
+                HopPathSearchResult::MultiHop(MultiHopPathSearchResult::new(
+                    edges,
+                    best_amount_in,
+                    profit,
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiHopPathSearchResult {
+    pub edges: Vec<Arc<EdgeIdentifier>>,
+    pub amount_in: u64,
+    pub profit: i64,
+}
+
+impl MultiHopPathSearchResult {
+    fn new(edges: Vec<Arc<EdgeIdentifier>>, amount_in: u64, profit: i64) -> Self {
+        Self {
+            edges,
+            amount_in,
+            profit,
+        }
+    }
+}
+
+impl SearchResult for MultiHopPathSearchResult {
+    fn profit(&self) -> i64 {
+        self.profit
+    }
+
+    fn amount_in(&self) -> (u64, Pubkey) {
+        (
+            self.amount_in,
+            find_mint_by_index(self.edges.first().unwrap().input_mint()).unwrap(),
+        )
+    }
+
+    fn convert_to_instruction_materials(&self) -> anyhow::Result<Vec<InstructionMaterial>> {
+        cycle_instruction_materials(
+            &self
+                .edges
+                .iter()
+                .map(|edge| edge.as_ref())
+                .collect::<Vec<_>>(),
+            self.amount_in,
+        )
+    }
+
+    fn route_steps(&self) -> Vec<RouteStep> {
+        cycle_route_steps(self.edges.len())
+    }
+
+    fn information(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+impl Display for MultiHopPathSearchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pools = self
+            .edges
+            .iter()
+            .map(|edge| format!("[{} {}]", edge.dex_type, edge.pool_id().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        f.write_str(&format!(
+            "{}, amount_in : {}, profit : {}",
+            pools.join(" -> "),
+            self.amount_in,
+            self.profit
+        ))
+    }
+}
@@ -0,0 +1,49 @@
+use crate::dex::{record_quote_error, PreparedQuote, PreparedQuoteType};
+use crate::graph::EdgeIdentifier;
+use ahash::RandomState;
+use dashmap::DashMap;
+use std::sync::Arc;
+
+/// 一轮路由内各池子解码后的状态，数量搜索和拆单对同一池子的多次quote只读取一次缓存
+///
+/// 缓存更新后的新一轮路由需要重新创建
+#[derive(Default)]
+pub(crate) struct QuotePass {
+    // pool index -> 池子状态，None : 解码失败，失败原因见 QuoteError
+    prepared: DashMap<usize, Option<Arc<PreparedQuoteType>>, RandomState>,
+    // (pool index, swap方向) -> 边际汇率
+    marginal_prices: DashMap<(usize, bool), Option<f64>, RandomState>,
+}
+
+impl QuotePass {
+    pub(crate) fn quote(&self, edge: &EdgeIdentifier, amount_in: u64) -> Option<u64> {
+        match self.prepared(edge)?.quote(amount_in, edge.swap_direction) {
+            Ok(quote_result) => Some(quote_result.amount_out),
+            Err(e) => {
+                if let Some(pool_id) = edge.pool_id() {
+                    record_quote_error(&pool_id, e);
+                }
+                None
+            }
+        }
+    }
+
+    /// 扣除手续费后的边际汇率，同一轮路由内每个方向只计算一次
+    pub(crate) fn marginal_price(&self, edge: &EdgeIdentifier) -> Option<f64> {
+        let key = (edge.pool, edge.swap_direction);
+        if let Some(price) = self.marginal_prices.get(&key) {
+            return *price;
+        }
+        let price = edge.marginal_price();
+        *self.marginal_prices.entry(key).or_insert(price)
+    }
+
+    fn prepared(&self, edge: &EdgeIdentifier) -> Option<Arc<PreparedQuoteType>> {
+        if let Some(prepared) = self.prepared.get(&edge.pool) {
+            return prepared.clone();
+        }
+        // 解码时不持有分片锁，并发下可能重复解码，结果相同
+        let prepared = edge.prepare().map(Arc::new);
+        self.prepared.entry(edge.pool).or_insert(prepared).clone()
+    }
+}
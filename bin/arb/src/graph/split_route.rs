@@ -0,0 +1,128 @@
+use crate::dex::QuoteResult;
+use crate::graph::{EdgeIdentifier, QuotePass};
+use std::sync::Arc;
+
+/// 拆分的粒度(%)
+const SPLIT_PERCENT_STEP: u64 = 10;
+
+/// Jupiter route plan 中的一步
+///
+/// percent : 从 input_index 对应的剩余数量中取出的比例，同一个 input_index 的最后一步为100
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteStep {
+    pub percent: u8,
+    pub input_index: u8,
+    pub output_index: u8,
+}
+
+/// 环路 : 0 -> 1 -> ... -> n-1 -> 0，每一跳只经过一个池子
+pub(crate) fn cycle_route_steps(hop_count: usize) -> Vec<RouteStep> {
+    (0..hop_count)
+        .map(|index| RouteStep {
+            percent: 100,
+            input_index: index as u8,
+            output_index: if index + 1 == hop_count {
+                0
+            } else {
+                (index + 1) as u8
+            },
+        })
+        .collect()
+}
+
+/// 同一跳拆分到多个并行池子(相同的交易对和方向)
+#[derive(Debug, Clone)]
+pub struct SplitHop {
+    // (池子, 占剩余数量的百分比)
+    pub edges: Vec<(Arc<EdgeIdentifier>, u8)>,
+}
+
+impl SplitHop {
+    /// 按Jupiter的方式依次扣减剩余数量，返回所有池子输出之和
+    pub(crate) fn quote(&self, amount_in: u64, pass: &QuotePass) -> Option<u64> {
+        let mut remaining = amount_in;
+        let mut amount_out = 0_u64;
+        for (edge, percent) in self.edges.iter() {
+            let amount = (remaining as u128 * *percent as u128 / 100) as u64;
+            remaining -= amount;
+            if amount > 0 {
+                amount_out = amount_out.checked_add(pass.quote(edge, amount)?)?;
+            }
+        }
+        Some(amount_out)
+    }
+
+    /// 同quote，返回每个池子的报价结果，分配数量为0或quote失败时为None
+    pub(crate) fn quote_results(&self, amount_in: u64) -> Vec<Option<QuoteResult>> {
+        let mut remaining = amount_in;
+        self.edges
+            .iter()
+            .map(|(edge, percent)| {
+                let amount = (remaining as u128 * *percent as u128 / 100) as u64;
+                remaining -= amount;
+                (amount > 0).then(|| edge.quote_result(amount)).flatten()
+            })
+            .collect()
+    }
+}
+
+/// 按边际产出将amount_in以 SPLIT_PERCENT_STEP 为粒度贪心分配到多个并行池子
+///
+/// 只用到一个池子时返回该池子100%，与不拆分的quote相同
+pub(crate) fn split_hop(
+    edges: &[Arc<EdgeIdentifier>],
+    amount_in: u64,
+    pass: &QuotePass,
+) -> Option<SplitHop> {
+    if edges.is_empty() || amount_in == 0 {
+        return None;
+    }
+    let chunk_count = 100 / SPLIT_PERCENT_STEP;
+    let chunk_amount =
+        |chunks: u64| (amount_in as u128 * chunks as u128 / chunk_count as u128) as u64;
+    let mut chunks = vec![0_u64; edges.len()];
+    let mut amount_outs = vec![0_u64; edges.len()];
+    for _ in 0..chunk_count {
+        let (index, amount_out) = edges
+            .iter()
+            .enumerate()
+            .filter_map(|(index, edge)| {
+                pass.quote(edge, chunk_amount(chunks[index] + 1))
+                    .map(|amount_out| (index, amount_out))
+            })
+            .max_by_key(|(index, amount_out)| amount_out.saturating_sub(amount_outs[*index]))?;
+        chunks[index] += 1;
+        amount_outs[index] = amount_out;
+    }
+    let used = (0..edges.len())
+        .filter(|index| chunks[*index] > 0)
+        .collect::<Vec<_>>();
+    // 分配的份数 -> 占剩余数量的百分比
+    let mut remaining_chunks = chunk_count;
+    let split_edges = used
+        .iter()
+        .map(|index| {
+            let percent = (chunks[*index] * 100 / remaining_chunks) as u8;
+            remaining_chunks -= chunks[*index];
+            (edges[*index].clone(), percent)
+        })
+        .collect();
+    Some(SplitHop { edges: split_edges })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::graph::split_route::cycle_route_steps;
+
+    #[test]
+    fn test_cycle_route_steps() {
+        let steps = cycle_route_steps(3);
+        assert_eq!(
+            steps
+                .iter()
+                .map(|step| (step.percent, step.input_index, step.output_index))
+                .collect::<Vec<_>>(),
+            vec![(100, 0, 1), (100, 1, 2), (100, 2, 0)]
+        );
+    }
+}
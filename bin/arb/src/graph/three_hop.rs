@@ -0,0 +1,277 @@
+use crate::dex::InstructionMaterial;
+use crate::graph::{
+    cycle_instruction_materials, cycle_route_steps, find_best_amount_in, find_mint_by_index,
+    find_mint_position, find_pool_position, AmountSearchConfig, EdgeIdentifier, HopPath, QuotePass,
+    RouteStep,
+};
+use crate::{HopPathSearchResult, SearchResult};
+use ahash::{AHashMap, AHashSet};
+use rayon::iter::IntoParallelRefIterator;
+use rayon::iter::ParallelIterator;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use tracing::info;
+
+/// 三角套利 : 关注的Mint -> X -> Y -> 关注的Mint
+#[derive(Default)]
+pub struct ThreeHopPath {
+    // 输入Mint -> 边
+    edges_by_input_mint: AHashMap<usize, Vec<Arc<EdgeIdentifier>>>,
+    // key : pool index，value : 经过该pool的所有环路
+    graph: AHashMap<usize, Arc<Vec<Arc<TrianglePath>>>>,
+}
+
+impl ThreeHopPath {
+    fn get_graph_with_pool_index(&self, pool_index: usize) -> Option<Arc<Vec<Arc<TrianglePath>>>> {
+        self.graph.get(&pool_index).cloned()
+    }
+}
+
+impl HopPath for ThreeHopPath {
+    fn build_graph(
+        &mut self,
+        edge_identifiers: &[EdgeIdentifier],
+        follow_mint_index: &[usize],
+    ) -> anyhow::Result<()> {
+        *self = Self::default();
+        self.add_edges(edge_identifiers, follow_mint_index)?;
+        info!(
+            "3 hop 环路数量 : {}",
+            self.graph.values().map(|paths| paths.len()).sum::<usize>() / 3
+        );
+        Ok(())
+    }
+
+    fn add_edges(
+        &mut self,
+        edge_identifiers: &[EdgeIdentifier],
+        follow_mint_index: &[usize],
+    ) -> anyhow::Result<()> {
+        let new_pools = edge_identifiers
+            .iter()
+            .map(|edge| edge.pool)
+            .collect::<AHashSet<_>>();
+        // 按输入的Mint分组
+        for edge in edge_identifiers.iter().cloned().map(Arc::new) {
+            self.edges_by_input_mint
+                .entry(edge.input_mint())
+                .or_default()
+                .push(edge);
+        }
+        // 只生成至少经过一个新pool的环路，已有的环路保持不变
+        let mut new_paths: AHashMap<usize, Vec<Arc<TrianglePath>>> = AHashMap::new();
+        for start_mint in follow_mint_index.iter().collect::<AHashSet<_>>() {
+            let Some(first_edges) = self.edges_by_input_mint.get(start_mint) else {
+                continue;
+            };
+            for first in first_edges {
+                let first_out_mint = first.output_mint();
+                let Some(second_edges) = self.edges_by_input_mint.get(&first_out_mint) else {
+                    continue;
+                };
+                for second in second_edges {
+                    let second_out_mint = second.output_mint();
+                    if second.pool == first.pool
+                        || second_out_mint == *start_mint
+                        || second_out_mint == first_out_mint
+                    {
+                        continue;
+                    }
+                    let Some(third_edges) = self.edges_by_input_mint.get(&second_out_mint) else {
+                        continue;
+                    };
+                    for third in third_edges {
+                        if third.output_mint() != *start_mint
+                            || third.pool == first.pool
+                            || third.pool == second.pool
+                        {
+                            continue;
+                        }
+                        let pools = [first.pool, second.pool, third.pool];
+                        if !pools.iter().any(|pool| new_pools.contains(pool)) {
+                            continue;
+                        }
+                        let path = Arc::new(TrianglePath {
+                            first: first.clone(),
+                            second: second.clone(),
+                            third: third.clone(),
+                        });
+                        for pool in pools {
+                            new_paths.entry(pool).or_default().push(path.clone());
+                        }
+                    }
+                }
+            }
+        }
+        for (pool, paths) in new_paths {
+            let merged = self
+                .graph
+                .get(&pool)
+                .map_or(vec![], |previous| previous.as_ref().clone())
+                .into_iter()
+                .chain(paths)
+                .collect::<Vec<_>>();
+            self.graph.insert(pool, Arc::new(merged));
+        }
+        Ok(())
+    }
+
+    fn remove_pools(&mut self, pool_indexes: &AHashSet<usize>) -> anyhow::Result<()> {
+        for edges in self.edges_by_input_mint.values_mut() {
+            edges.retain(|edge| !pool_indexes.contains(&edge.pool));
+        }
+        self.edges_by_input_mint
+            .retain(|_, edges| !edges.is_empty());
+        // 与被移除的pool组成过环路的pool
+        let affected_pools = pool_indexes
+            .iter()
+            .filter_map(|pool| self.graph.remove(pool))
+            .flat_map(|paths| {
+                paths
+                    .iter()
+                    .flat_map(|path| path.edges().map(|edge| edge.pool))
+                    .collect::<Vec<_>>()
+            })
+            .filter(|pool| !pool_indexes.contains(pool))
+            .collect::<AHashSet<_>>();
+        for pool in affected_pools {
+            if let Some(paths) = self.graph.get_mut(&pool) {
+                *paths = Arc::new(
+                    paths
+                        .iter()
+                        .filter(|path| {
+                            !path
+                                .edges()
+                                .iter()
+                                .any(|edge| pool_indexes.contains(&edge.pool))
+                        })
+                        .cloned()
+                        .collect(),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn find_best_hop_path(
+        &self,
+        pool_id: Pubkey,
+        arb_mint: Arc<Pubkey>,
+        amount_in: u64,
+        max_amount_in: u64,
+        min_profit: u64,
+        amount_search: &AmountSearchConfig,
+    ) -> Option<HopPathSearchResult> {
+        let pool_index = find_pool_position(&pool_id)?;
+        let amount_in_mint_index = find_mint_position(arb_mint.as_ref())?;
+        let hop_paths = self.get_graph_with_pool_index(pool_index)?;
+        let pass = QuotePass::default();
+        hop_paths
+            .par_iter()
+            .filter(|hop_path| hop_path.swaped_mint_index() == amount_in_mint_index)
+            .filter_map(|hop_path| {
+                find_best_amount_in(
+                    amount_search,
+                    &hop_path.edges(),
+                    amount_in,
+                    max_amount_in,
+                    &pass,
+                )
+                .and_then(|(best_amount_in, profit)| {
+                    (profit >= min_profit as i64).then_some((hop_path, best_amount_in, profit))
+                })
+            })
+            .max_by_key(|(_, _, profit)| *profit)
+            .map(|(hop_path, best_amount_in, profit)| {
+                HopPathSearchResult::ThreeHop(ThreeHopPathSearchResult::new(
+                    hop_path.clone(),
+                    best_amount_in,
+                    profit,
+                ))
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrianglePath {
+    pub first: Arc<EdgeIdentifier>,
+    pub second: Arc<EdgeIdentifier>,
+    pub third: Arc<EdgeIdentifier>,
+}
+
+impl TrianglePath {
+    #[inline]
+    pub fn swaped_mint_index(&self) -> usize {
+        self.first.input_mint()
+    }
+
+    #[inline]
+    pub fn swaped_mint(&self) -> Option<Pubkey> {
+        find_mint_by_index(self.swaped_mint_index())
+    }
+
+    #[inline]
+    fn edges(&self) -> [&EdgeIdentifier; 3] {
+        [&self.first, &self.second, &self.third]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ThreeHopPathSearchResult {
+    pub hop_path: Arc<TrianglePath>,
+    pub amount_in: u64,
+    pub profit: i64,
+}
+
+impl ThreeHopPathSearchResult {
+    fn new(hop_path: Arc<TrianglePath>, amount_in: u64, profit: i64) -> Self {
+        Self {
+            hop_path,
+            amount_in,
+            profit,
+        }
+    }
+}
+
+impl SearchResult for ThreeHopPathSearchResult {
+    fn profit(&self) -> i64 {
+        self.profit
+    }
+
+    fn amount_in(&self) -> (u64, Pubkey) {
+        (self.amount_in, self.hop_path.swaped_mint().unwrap())
+    }
+
+    fn convert_to_instruction_materials(&self) -> anyhow::Result<Vec<InstructionMaterial>> {
+        cycle_instruction_materials(&self.hop_path.edges(), self.amount_in)
+    }
+
+    fn route_steps(&self) -> Vec<RouteStep> {
+        cycle_route_steps(3)
+    }
+
+    fn information(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+impl Display for ThreeHopPathSearchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let edges = [
+            &self.hop_path.first,
+            &self.hop_path.second,
+            &self.hop_path.third,
+        ];
+        let pools = edges
+            .iter()
+            .map(|edge| format!("[{} {}]", edge.dex_type, edge.pool_id().unwrap_or_default()))
+            .collect::<Vec<_>>();
+        f.write_str(&format!(
+            "{}, amount_in : {}, profit : {}",
+            pools.join(" -> "),
+            self.amount_in,
+            self.profit
+        ))
+    }
+}
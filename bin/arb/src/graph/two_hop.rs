@@ -1,38 +1,35 @@
 use crate::dex::get_token_program;
-use crate::dex::DexType;
 use crate::dex::InstructionMaterial;
 use crate::graph::{
-    find_mint_by_index, find_mint_position, find_pool_position, EdgeIdentifier, HopPath,
+    find_best_amount_in, find_mint_by_index, find_mint_position, find_pool_position, split_hop,
+    AmountSearchConfig, EdgeIdentifier, HopPath, QuotePass, RouteStep, SplitHop,
 };
 use crate::metadata::MintAtaPair;
 use crate::HopPathSearchResult::TwoHop;
 use crate::{HopPathSearchResult, SearchResult};
-use ahash::AHashMap;
-use anyhow::anyhow;
+use ahash::{AHashMap, AHashSet};
 use rayon::iter::IntoParallelIterator;
 use rayon::iter::ParallelIterator;
 use solana_sdk::pubkey::Pubkey;
 use spl_associated_token_account::get_associated_token_address_with_program_id;
-use std::collections::hash_map::Entry;
 use std::fmt::{Display, Formatter};
-use std::panic;
-use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use tokio::sync::OnceCell;
-use tracing::error;
-
-/// 后续针对多hop可以改成枚举，针对不同的枚举实现不同的Trigger和Quoter
-static GRAPH: OnceCell<Arc<AHashMap<usize, Arc<Vec<Arc<Path>>>>>> = OnceCell::const_new();
-
-pub fn get_graph_with_pool_index(pool_index: usize) -> Option<Arc<Vec<Arc<Path>>>> {
-    GRAPH
-        .get()
-        .unwrap()
-        .get(&pool_index)
-        .map_or(None, |v| Some(v.clone()))
+use tracing::info;
+
+/// 2 hop 环路 : 关注的Mint -> X -> 关注的Mint
+#[derive(Default)]
+pub struct TwoHopPath {
+    // (输入Mint, 输出Mint) -> 边
+    edges_by_pair: AHashMap<(usize, usize), Vec<Arc<EdgeIdentifier>>>,
+    // key : pool index，value : 经过该pool的所有路径
+    graph: AHashMap<usize, Arc<Vec<Arc<Path>>>>,
 }
 
-pub struct TwoHopPath;
+impl TwoHopPath {
+    fn get_graph_with_pool_index(&self, pool_index: usize) -> Option<Arc<Vec<Arc<Path>>>> {
+        self.graph.get(&pool_index).cloned()
+    }
+}
 
 impl HopPath for TwoHopPath {
     fn build_graph(
@@ -40,7 +37,107 @@ impl HopPath for TwoHopPath {
         edge_identifiers: &[EdgeIdentifier],
         follow_mint_index: &[usize],
     ) -> anyhow::Result<()> {
-        Err(anyhow!("build failed"))
+        *self = Self::default();
+        self.add_edges(edge_identifiers, follow_mint_index)?;
+        info!(
+            "2 hop 路径数量 : {}",
+            self.graph.values().map(|paths| paths.len()).sum::<usize>() / 2
+        );
+        Ok(())
+    }
+
+    fn add_edges(
+        &mut self,
+        edge_identifiers: &[EdgeIdentifier],
+        follow_mint_index: &[usize],
+    ) -> anyhow::Result<()> {
+        let follow_mint_index = follow_mint_index.iter().collect::<AHashSet<_>>();
+        let new_edges = edge_identifiers
+            .iter()
+            .cloned()
+            .map(Arc::new)
+            .collect::<Vec<_>>();
+        let new_pools = new_edges
+            .iter()
+            .map(|edge| edge.pool)
+            .collect::<AHashSet<_>>();
+        for edge in new_edges.iter() {
+            self.edges_by_pair
+                .entry((edge.input_mint(), edge.output_mint()))
+                .or_default()
+                .push(edge.clone());
+        }
+        // 新的路径必然经过新的边
+        let mut new_paths: AHashMap<usize, Vec<Arc<Path>>> = AHashMap::new();
+        for edge in new_edges.iter() {
+            let Some(reverse_edges) = self
+                .edges_by_pair
+                .get(&(edge.output_mint(), edge.input_mint()))
+            else {
+                continue;
+            };
+            for reverse_edge in reverse_edges {
+                // 两条边都是新的时，只在pool index较小的一侧生成
+                if new_pools.contains(&reverse_edge.pool) && reverse_edge.pool < edge.pool {
+                    continue;
+                }
+                for (first, second) in [(edge, reverse_edge), (reverse_edge, edge)] {
+                    if !follow_mint_index.contains(&first.input_mint()) {
+                        continue;
+                    }
+                    if let Some(path) = Path::new(first, second) {
+                        let path = Arc::new(path);
+                        new_paths.entry(first.pool).or_default().push(path.clone());
+                        new_paths.entry(second.pool).or_default().push(path);
+                    }
+                }
+            }
+        }
+        for (pool, paths) in new_paths {
+            let merged = self
+                .graph
+                .get(&pool)
+                .map_or(vec![], |previous| previous.as_ref().clone())
+                .into_iter()
+                .chain(paths)
+                .collect::<Vec<_>>();
+            self.graph.insert(pool, Arc::new(merged));
+        }
+        Ok(())
+    }
+
+    fn remove_pools(&mut self, pool_indexes: &AHashSet<usize>) -> anyhow::Result<()> {
+        for edges in self.edges_by_pair.values_mut() {
+            edges.retain(|edge| !pool_indexes.contains(&edge.pool));
+        }
+        self.edges_by_pair.retain(|_, edges| !edges.is_empty());
+        // 与被移除的pool组成过路径的pool
+        let affected_pools = pool_indexes
+            .iter()
+            .filter_map(|pool| self.graph.remove(pool))
+            .flat_map(|paths| {
+                paths
+                    .iter()
+                    .flat_map(|path| [path.first.pool, path.second.pool])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|pool| !pool_indexes.contains(pool))
+            .collect::<AHashSet<_>>();
+        for pool in affected_pools {
+            if let Some(paths) = self.graph.get_mut(&pool) {
+                *paths = Arc::new(
+                    paths
+                        .iter()
+                        .filter(|path| {
+                            !pool_indexes.contains(&path.first.pool)
+                                && !pool_indexes.contains(&path.second.pool)
+                        })
+                        .cloned()
+                        .collect(),
+                );
+            }
+        }
+        Ok(())
     }
 
     fn find_best_hop_path(
@@ -50,69 +147,26 @@ impl HopPath for TwoHopPath {
         amount_in: u64,
         max_amount_in: u64,
         min_profit: u64,
+        amount_search: &AmountSearchConfig,
     ) -> Option<HopPathSearchResult> {
         let pool_index = find_pool_position(&pool_id)?;
-        let hop_paths = get_graph_with_pool_index(pool_index)?;
-        let mut use_ternary_search_hop_path = None;
-        let mut normal_hop_path = None;
-        if max_amount_in >= amount_in {
-            let (temp_use_ternary_search_hop_path, temp_normal_hop_path): (Vec<_>, Vec<_>) =
-                hop_paths
-                    .iter()
-                    .cloned()
-                    .partition(|hop| hop.use_ternary_search(pool_index));
-            use_ternary_search_hop_path = (!temp_use_ternary_search_hop_path.is_empty())
-                .then_some(temp_use_ternary_search_hop_path);
-            normal_hop_path = (!temp_normal_hop_path.is_empty()).then_some(temp_normal_hop_path);
-        }
-
-        if use_ternary_search_hop_path.is_none() && normal_hop_path.is_none() {
+        let hop_paths = self.get_graph_with_pool_index(pool_index)?;
+        if hop_paths.is_empty() {
             return None;
         }
-
         let amount_in_mint_index = find_mint_position(arb_mint.as_ref())?;
+        let pass = QuotePass::default();
 
-        match normal_quote(
-            normal_hop_path.unwrap(),
-            pool_index,
+        sized_quote(
+            hop_paths.as_slice(),
             amount_in_mint_index,
             amount_in,
+            max_amount_in,
             min_profit,
-        ) {
-            None => None,
-            Some(res) => Some(HopPathSearchResult::from(TwoHop(res))),
-        }
-
-        // let (res1, res2) = rayon::join(
-        //     || {
-        //         if let Some(path) = normal_hop_path {
-        //             normal_quote(
-        //                 path,
-        //                 pool_index,
-        //                 amount_in_mint_index,
-        //                 amount_in,
-        //                 min_profit,
-        //             )
-        //         } else {
-        //             None
-        //         }
-        //     },
-        //     || {
-        //         if let Some(path) = use_ternary_search_hop_path {
-        //             ternary_search_quote(path, max_amount_in, min_profit)
-        //         } else {
-        //             None
-        //         }
-        //     },
-        // );
-        // // 并行执行两种报价逻辑
-        // let result = panic::catch_unwind(AssertUnwindSafe(|| {
-        //     [res1, res2]
-        //         .into_iter()
-        //         .flatten()
-        //         .map(|a| HopPathSearchResult::from(TwoHop(a)))
-        //         .max_by_key(|a| a.profit())
-        // }));
+            amount_search,
+            &pass,
+        )
+        .map(|res| TwoHop(split_quote(hop_paths.as_slice(), pool_index, res, &pass)))
     }
 }
 
@@ -168,16 +222,6 @@ impl Path {
         })
     }
 
-    #[inline]
-    pub fn use_ternary_search(&self, pool_index: usize) -> bool {
-        use DexType::{PumpFunAMM, RaydiumAMM};
-
-        // (self.first.pool == pool_index || self.second.pool == pool_index)
-        //     && matches!(self.first.dex_type, PumpFunAMM | RaydiumAMM)
-        //     && matches!(self.second.dex_type, PumpFunAMM | RaydiumAMM)
-        false
-    }
-
     #[inline]
     pub fn is_positive(&self, pool_index: &usize) -> bool {
         &self.first.pool == pool_index
@@ -212,6 +256,8 @@ pub struct TwoHopPathSearchResult {
     pub hop_path: Arc<Path>,
     pub amount_in: u64,
     pub profit: i64,
+    // (被拆分的hop, 拆分到的并行池子)，None : 不拆分
+    pub split: Option<(usize, SplitHop)>,
 }
 
 impl TwoHopPathSearchResult {
@@ -220,8 +266,16 @@ impl TwoHopPathSearchResult {
             hop_path,
             amount_in,
             profit,
+            split: None,
         }
     }
+
+    #[inline]
+    fn split_hop(&self, hop: usize) -> Option<&SplitHop> {
+        self.split
+            .as_ref()
+            .and_then(|(split_index, split_hop)| (*split_index == hop).then_some(split_hop))
+    }
 }
 
 impl SearchResult for TwoHopPathSearchResult {
@@ -234,10 +288,57 @@ impl SearchResult for TwoHopPathSearchResult {
     }
 
     fn convert_to_instruction_materials(&self) -> anyhow::Result<Vec<InstructionMaterial>> {
-        Ok(vec![
-            self.hop_path.first.get_instruction_material()?,
-            self.hop_path.second.get_instruction_material()?,
-        ])
+        let mut materials = Vec::with_capacity(4);
+        // 沿路径重放quote，quote失败后不再传入报价结果
+        let mut amount = Some(self.amount_in);
+        for (hop, edge) in [&self.hop_path.first, &self.hop_path.second]
+            .into_iter()
+            .enumerate()
+        {
+            match self.split_hop(hop) {
+                Some(split_hop) => {
+                    let quote_results = amount.map_or_else(
+                        || vec![None; split_hop.edges.len()],
+                        |amount| split_hop.quote_results(amount),
+                    );
+                    amount = quote_results
+                        .iter()
+                        .map(|quote_result| quote_result.as_ref().map(|q| q.amount_out))
+                        .sum();
+                    for ((edge, _), quote_result) in split_hop.edges.iter().zip(&quote_results) {
+                        materials.push(edge.get_instruction_material(quote_result.as_ref())?);
+                    }
+                }
+                None => {
+                    let quote_result = amount.and_then(|amount| edge.quote_result(amount));
+                    amount = quote_result.as_ref().map(|q| q.amount_out);
+                    materials.push(edge.get_instruction_material(quote_result.as_ref())?);
+                }
+            }
+        }
+        Ok(materials)
+    }
+
+    fn route_steps(&self) -> Vec<RouteStep> {
+        // token index : 0 关注的Mint，1 中间的Mint
+        let mut steps = Vec::with_capacity(4);
+        for (hop, (input_index, output_index)) in [(0, 1), (1, 0)].into_iter().enumerate() {
+            match self.split_hop(hop) {
+                Some(split_hop) => {
+                    steps.extend(split_hop.edges.iter().map(|(_, percent)| RouteStep {
+                        percent: *percent,
+                        input_index,
+                        output_index,
+                    }))
+                }
+                None => steps.push(RouteStep {
+                    percent: 100,
+                    input_index,
+                    output_index,
+                }),
+            }
+        }
+        steps
     }
 
     fn information(&self) -> String {
@@ -245,186 +346,126 @@ impl SearchResult for TwoHopPathSearchResult {
     }
 }
 
-fn normal_quote(
-    hop_paths: Vec<Arc<Path>>,
-    pool_index: usize,
+/// 对每条路径搜索利润最大的amount_in，取利润最大的路径
+fn sized_quote(
+    hop_paths: &[Arc<Path>],
     amount_in_mint_index: usize,
     amount_in: u64,
-    min_profit: u64,
-) -> Option<TwoHopPathSearchResult> {
-    let (positive_hop_path, reverse_hop_path): (Vec<_>, Vec<_>) = hop_paths
-        .iter()
-        .filter(|hop| hop.swaped_mint_index() == &amount_in_mint_index)
-        .cloned()
-        .partition(|hop| hop.is_positive(&pool_index));
-    if positive_hop_path.is_empty() && reverse_hop_path.is_empty() {
-        return None;
-    }
-    // 正向quote，当前pool只计算一次quote，避免重复计算
-    let positive_best_hop_path = positive_hop_path
-        .first()
-        .cloned()
-        .map_or(None, |first_hop| {
-            first_hop
-                .first
-                .quote(amount_in)
-                .and_then(|first_amount_out| {
-                    positive_hop_path
-                        .into_par_iter()
-                        .filter_map(|hop_path| {
-                            hop_path
-                                .second
-                                .quote(first_amount_out)
-                                .and_then(|second_amount_out| {
-                                    calculate_profit(amount_in, second_amount_out, min_profit)
-                                        .and_then(|profit| Some((hop_path, profit as i64)))
-                                })
-                        })
-                        .max_by_key(|x| x.1)
-                })
-        });
-    // 反向quote
-    let reverse_best_hop_path = reverse_hop_path
-        .into_par_iter()
-        .filter_map(|hop_path| {
-            hop_path
-                .first
-                .quote(amount_in)
-                .and_then(|first_amount_out| {
-                    hop_path
-                        .second
-                        .quote(first_amount_out)
-                        .and_then(|second_amount_out| {
-                            calculate_profit(amount_in, second_amount_out, min_profit)
-                                .and_then(|profit| Some((hop_path, profit as i64)))
-                        })
-                })
-        })
-        .max_by_key(|x| x.1);
-    match (positive_best_hop_path, reverse_best_hop_path) {
-        (Some((p_path, p_profit)), Some((r_path, r_profit))) => Some(if p_profit >= r_profit {
-            TwoHopPathSearchResult::new(p_path, amount_in, p_profit)
-        } else {
-            TwoHopPathSearchResult::new(r_path, amount_in, r_profit)
-        }),
-        (Some((p_path, p_profit)), None) => {
-            Some(TwoHopPathSearchResult::new(p_path, amount_in, p_profit))
-        }
-        (None, Some((r_path, r_profit))) => {
-            Some(TwoHopPathSearchResult::new(r_path, amount_in, r_profit))
-        }
-        _ => None,
-    }
-}
-
-fn ternary_search_quote(
-    hop_paths: Vec<Arc<Path>>,
     max_amount_in: u64,
     min_profit: u64,
+    amount_search: &AmountSearchConfig,
+    pass: &QuotePass,
 ) -> Option<TwoHopPathSearchResult> {
     hop_paths
         .into_par_iter()
+        .filter(|hop| hop.swaped_mint_index() == &amount_in_mint_index)
         .filter_map(|hop_path| {
-            find_maximize_quote_with_ternary_search(max_amount_in, |amount_in| {
-                hop_path
-                    .first
-                    .quote(amount_in)
-                    .and_then(|first_amount_out| {
-                        hop_path
-                            .second
-                            .quote(first_amount_out)
-                            .and_then(|second_amount_out| {
-                                Some(second_amount_out as i64 - amount_in as i64)
-                            })
-                    })
-            })
+            find_best_amount_in(
+                amount_search,
+                &[hop_path.first.as_ref(), hop_path.second.as_ref()],
+                amount_in,
+                max_amount_in,
+                pass,
+            )
             .and_then(|(best_amount_in, profit)| {
-                if (profit as u64) < min_profit {
-                    None
-                } else {
-                    Some((hop_path, best_amount_in, profit))
-                }
+                (profit >= min_profit as i64).then_some((hop_path, best_amount_in, profit))
             })
         })
         .max_by_key(|(_, _, profit)| *profit)
         .map(|(path, best_amount_in, profit)| {
-            TwoHopPathSearchResult::new(path, best_amount_in, profit)
+            TwoHopPathSearchResult::new(path.clone(), best_amount_in, profit)
         })
 }
 
-#[inline]
-fn calculate_profit(amount_in: u64, amount_out: u64, min_profit: u64) -> Option<u64> {
-    (amount_out >= amount_in + min_profit).then(|| amount_out - amount_in)
-}
-
-/// 2hop 三元搜索最佳size
-fn find_maximize_quote_with_ternary_search<Q>(max_amount_in: u64, quoter: Q) -> Option<(u64, i64)>
-where
-    Q: Fn(u64) -> Option<i64>,
-{
-    let mut left = 100_000_000u64; // 0.1 SOL
-    let mut right = max_amount_in;
-
-    let mut iterations = 0;
-    let max_iterations = 50;
-    let precision_threshold = 100_000_000u64; // 最小精度  0.1 SOL
-
-    // println!("🔍 三分搜索开始：区间 = {} ~ {}", left, right);
-
-    while right - left > precision_threshold && iterations < max_iterations {
-        let mid1 = left + (right - left) / 3;
-        let mid2 = right - (right - left) / 3;
-
-        let profit1 = quoter(mid1).unwrap_or(i64::MIN);
-
-        let profit2 = quoter(mid2).unwrap_or(i64::MIN);
-
-        // println!("🔁 Iter {}: left={}, mid1={}, mid2={}, right={}, profit1={}, profit2={}", iterations, left, mid1, mid2, right, profit1, profit2);
-
-        if profit1 < profit2 {
-            left = mid1;
-        } else {
-            right = mid2;
-        }
-
-        iterations += 1;
-    }
-
-    // if iterations >= max_iterations {
-    //     println!("⚠️ 达到最大迭代次数，可能未收敛");
-    // } else {
-    //     println!("✅ 收敛完成，共迭代 {} 次，最终区间：{} ~ {}", iterations, left, right);
-    // }
-
-    let mut best_input = 0u64;
-    let mut best_profit = i64::MIN;
-
-    // 枚举精搜 0.01 步长
-    for dx in (left..=right).step_by(10_000_000) {
-        let profit = quoter(dx).unwrap_or(i64::MIN);
-        if profit > best_profit {
-            best_profit = profit;
-            best_input = dx;
-        }
+/// 将非触发的一跳拆分到同一交易对的多个并行池子，产出更多时替换单池子的结果
+///
+/// 只在单池子搜索得到的amount_in处分配一次
+fn split_quote(
+    hop_paths: &[Arc<Path>],
+    pool_index: usize,
+    result: TwoHopPathSearchResult,
+    pass: &QuotePass,
+) -> TwoHopPathSearchResult {
+    let trigger_first = result.hop_path.is_positive(&pool_index);
+    let (trigger, split_index) = if trigger_first {
+        (result.hop_path.first.clone(), 1)
+    } else {
+        (result.hop_path.second.clone(), 0)
+    };
+    let parallel_edges = hop_paths
+        .iter()
+        .filter(|hop_path| {
+            hop_path.swaped_mint_index() == result.hop_path.swaped_mint_index()
+                && hop_path.is_positive(&pool_index) == trigger_first
+        })
+        .map(|hop_path| {
+            if trigger_first {
+                hop_path.second.clone()
+            } else {
+                hop_path.first.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    if parallel_edges.len() < 2 {
+        return result;
     }
-
-    if best_profit > 0 {
-        Some((best_input, best_profit))
+    let amount_in = result.amount_in;
+    let split = if trigger_first {
+        pass.quote(&trigger, amount_in).and_then(|middle_amount| {
+            let split_hop = split_hop(parallel_edges.as_slice(), middle_amount, pass)?;
+            Some((split_hop.quote(middle_amount, pass)?, split_hop))
+        })
     } else {
-        None
+        split_hop(parallel_edges.as_slice(), amount_in, pass).and_then(|split_hop| {
+            let middle_amount = split_hop.quote(amount_in, pass)?;
+            Some((pass.quote(&trigger, middle_amount)?, split_hop))
+        })
+    };
+    match split {
+        // 只用到一个池子时不拆分
+        Some((amount_out, split_hop)) if split_hop.edges.len() >= 2 => {
+            let profit = amount_out as i64 - amount_in as i64;
+            if profit > result.profit {
+                TwoHopPathSearchResult {
+                    profit,
+                    split: Some((split_index, split_hop)),
+                    ..result
+                }
+            } else {
+                result
+            }
+        }
+        _ => result,
     }
 }
 
 impl Display for TwoHopPathSearchResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let binding = Pubkey::default();
-        let first_pool = self.hop_path.first.pool_id().unwrap_or(&binding);
-        let second_pool = self.hop_path.second.pool_id().unwrap_or(&binding);
+        let first_pool = self.hop_path.first.pool_id().unwrap_or_default();
+        let second_pool = self.hop_path.second.pool_id().unwrap_or_default();
         let f_dex_type = &self.hop_path.first.dex_type;
         let s_dex_type = &self.hop_path.second.dex_type;
+        let split = self
+            .split
+            .as_ref()
+            .map_or(String::new(), |(hop, split_hop)| {
+                let pools = split_hop
+                    .edges
+                    .iter()
+                    .map(|(edge, percent)| {
+                        format!(
+                            "[{} {} {}%]",
+                            edge.dex_type,
+                            edge.pool_id().unwrap_or_default(),
+                            percent
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                format!(", split hop {} : {}", hop, pools.join(" "))
+            });
         f.write_str(&format!(
-            "[{} {}] -> [{} {}], amount_in : {}, profit : {}",
-            f_dex_type, first_pool, s_dex_type, second_pool, self.amount_in, self.profit
+            "[{} {}] -> [{} {}], amount_in : {}, profit : {}{}",
+            f_dex_type, first_pool, s_dex_type, second_pool, self.amount_in, self.profit, split
         ))
     }
 }
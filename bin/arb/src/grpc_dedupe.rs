@@ -0,0 +1,180 @@
+use ahash::RandomState;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 去重记录保留的slot数量
+const KEEP_SLOTS: u64 = 150;
+/// slot前进超过该数量时清理过期的去重记录
+const PRUNE_INTERVAL_SLOTS: u64 = 50;
+
+/// 多个GRPC端点订阅相同的数据，只保留最先到达的一条，并统计各端点的到达延迟
+///
+/// Account : (pubkey, slot, 数据hash)，各端点的write_version不可比较；Transaction : signature
+#[derive(Debug)]
+pub struct GrpcDeduper {
+    endpoints: Vec<String>,
+    // 最先到达的时间，key为(pubkey, slot, 数据hash)
+    accounts: DashMap<(Pubkey, u64, u64), Instant, RandomState>,
+    // slot和最先到达的时间
+    transactions: DashMap<Vec<u8>, (u64, Instant), RandomState>,
+    // (端点, 程序)的到达统计
+    latency_stats: DashMap<(usize, Pubkey), EndpointLatency, RandomState>,
+    pruned_slot: AtomicU64,
+    // 计算Account数据的hash
+    hasher: RandomState,
+}
+
+/// 端点推送某个程序数据的到达统计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EndpointLatency {
+    pub arrivals: u64,
+    // 最先到达的次数
+    pub first_arrivals: u64,
+    // 非最先到达时落后于最先到达的时间之和
+    pub total_lag: Duration,
+}
+
+impl EndpointLatency {
+    /// 非最先到达时平均落后的时间
+    pub fn average_lag(&self) -> Duration {
+        match self.arrivals - self.first_arrivals {
+            0 => Duration::ZERO,
+            late_arrivals => self.total_lag / late_arrivals as u32,
+        }
+    }
+}
+
+impl GrpcDeduper {
+    pub fn new(endpoints: Vec<String>) -> Self {
+        Self {
+            endpoints,
+            accounts: DashMap::with_capacity_and_hasher(100_000, RandomState::default()),
+            transactions: DashMap::with_capacity_and_hasher(100_000, RandomState::default()),
+            latency_stats: DashMap::with_hasher(RandomState::default()),
+            pruned_slot: AtomicU64::new(0),
+            hasher: RandomState::default(),
+        }
+    }
+
+    /// endpoint : 端点下标，owner : 账户的owner
+    pub fn is_first_account(
+        &self,
+        endpoint: usize,
+        owner: Pubkey,
+        account_key: Pubkey,
+        slot: u64,
+        data: &[u8],
+    ) -> bool {
+        self.prune(slot);
+        let data_hash = self.hasher.hash_one(data);
+        let lag = match self.accounts.entry((account_key, slot, data_hash)) {
+            Entry::Vacant(entry) => {
+                entry.insert(Instant::now());
+                None
+            }
+            Entry::Occupied(entry) => Some(entry.get().elapsed()),
+        };
+        self.record(endpoint, owner, lag);
+        lag.is_none()
+    }
+
+    /// program : 交易涉及的Dex程序
+    pub fn is_first_transaction(
+        &self,
+        endpoint: usize,
+        program: Pubkey,
+        signature: &[u8],
+        slot: u64,
+    ) -> bool {
+        self.prune(slot);
+        let lag = match self.transactions.entry(signature.to_vec()) {
+            Entry::Vacant(entry) => {
+                entry.insert((slot, Instant::now()));
+                None
+            }
+            Entry::Occupied(entry) => Some(entry.get().1.elapsed()),
+        };
+        self.record(endpoint, program, lag);
+        lag.is_none()
+    }
+
+    /// lag : 非最先到达时落后的时间
+    fn record(&self, endpoint: usize, program: Pubkey, lag: Option<Duration>) {
+        let mut stats = self.latency_stats.entry((endpoint, program)).or_default();
+        stats.arrivals += 1;
+        match lag {
+            None => stats.first_arrivals += 1,
+            Some(lag) => stats.total_lag += lag,
+        }
+    }
+
+    fn prune(&self, slot: u64) {
+        let pruned_slot = self.pruned_slot.load(Ordering::Relaxed);
+        if slot < pruned_slot + PRUNE_INTERVAL_SLOTS
+            || self
+                .pruned_slot
+                .compare_exchange(pruned_slot, slot, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        let min_slot = slot.saturating_sub(KEEP_SLOTS);
+        self.accounts
+            .retain(|(_, account_slot, _), _| *account_slot >= min_slot);
+        self.transactions
+            .retain(|_, (transaction_slot, _)| *transaction_slot >= min_slot);
+    }
+
+    /// 按程序、端点排序的到达统计
+    pub fn latency_stats(&self) -> Vec<(String, Pubkey, EndpointLatency)> {
+        let mut stats = self
+            .latency_stats
+            .iter()
+            .map(|entry| {
+                let (endpoint, program) = *entry.key();
+                (program, endpoint, *entry.value())
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by_key(|(program, endpoint, _)| (*program, *endpoint));
+        stats
+            .into_iter()
+            .map(|(program, endpoint, latency)| {
+                (self.endpoints[endpoint].clone(), program, latency)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grpc_dedupe::GrpcDeduper;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_grpc_deduper() {
+        let deduper = GrpcDeduper::new(vec!["a".to_string(), "b".to_string()]);
+        let owner = Pubkey::new_unique();
+        let account = Pubkey::new_unique();
+        assert!(deduper.is_first_account(0, owner, account, 10, &[1]));
+        // 其他端点推送的相同数据
+        assert!(!deduper.is_first_account(1, owner, account, 10, &[1]));
+        // 同一个slot内数据变化
+        assert!(deduper.is_first_account(1, owner, account, 10, &[2]));
+        assert!(deduper.is_first_transaction(1, owner, &[1; 64], 10));
+        assert!(!deduper.is_first_transaction(0, owner, &[1; 64], 10));
+
+        let stats = deduper.latency_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].0, "a");
+        assert_eq!((stats[0].2.arrivals, stats[0].2.first_arrivals), (2, 1));
+        assert_eq!((stats[1].2.arrivals, stats[1].2.first_arrivals), (3, 2));
+
+        // slot前进后清理过期的记录
+        assert!(deduper.is_first_account(0, owner, account, 10 + 200, &[1]));
+        assert_eq!(deduper.accounts.len(), 1);
+        assert!(deduper.transactions.is_empty());
+    }
+}
@@ -4,9 +4,10 @@ use crate::dex::oracle::Oracle;
 use crate::dex::tick_array::TickArray;
 use crate::dex::whirlpool::Whirlpool;
 use crate::dex::{
-    get_account_data, get_dex_type_and_account_type, get_subscribed_accounts, is_follow_vault,
-    raydium_cpmm, read_from, update_cache, AccountType, AmmInfo, BinArray, BinArrayBitmapExtension,
-    LbPair, MintVault, PoolState, TickArrayBitmapExtension, TickArrayState, CLOCK_ID,
+    confirm_stream_slot, contains_subscribed_account, get_account_data,
+    get_dex_type_and_account_type, is_follow_vault, raydium_cpmm, read_from, update_cache,
+    AccountType, AmmInfo, BinArray, BinArrayBitmapExtension, CacheVersion, LbPair, MintVault,
+    PoolState, TickArrayBitmapExtension, TickArrayState, CLOCK_ID,
 };
 use crate::dex::{slice_data_auto_get_dex_type, SliceType};
 use crate::dex::{DexType, FromCache};
@@ -27,7 +28,7 @@ use spl_token::solana_program::program_pack::Pack;
 use spl_token::state::Account;
 use std::collections::HashSet;
 use std::fmt::{Debug, Formatter};
-use std::ops::{BitAnd, Sub};
+use std::ops::Sub;
 use std::ptr;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -64,21 +65,9 @@ impl MessageProcessor {
                 loop {
                     match grpc_message_receiver.recv_async().await {
                         Ok(grpc_message) => {
-                            match grpc_message {
-                                GrpcMessage::Account(account_msg) => {
-                                    match Self::update_cache(
-                                        account_msg.owner_key,
-                                        account_msg.account_key,
-                                        account_msg.data,
-                                    ) {
-                                        Ok(_) => {
-                                        }
-                                        Err(e) => {
-                                            error!("更新缓存失败，{}", e);
-                                        }
-                                    }
-                                }
-                                GrpcMessage::Transaction(transaction_msg) => {
+                            match Self::process(grpc_message) {
+                                None => {}
+                                Some(transaction_msg) => {
                                     match cached_message_sender.try_send(transaction_msg) {
                                         Err(TrySendError::Full(msg)) => {
                                             cached_msg_drop_receiver.try_recv().ok();
@@ -117,14 +106,50 @@ impl MessageProcessor {
         }
     }
 
-    fn update_cache(owner: Vec<u8>, account_key: Vec<u8>, data: Vec<u8>) -> anyhow::Result<()> {
+    /// Account写入缓存，交易返回给调用方触发路由
+    pub fn process(grpc_message: GrpcMessage) -> Option<GrpcTransactionMsg> {
+        match grpc_message {
+            GrpcMessage::Account(account_msg) => {
+                confirm_stream_slot(account_msg.slot);
+                if let Err(e) = Self::update_cache(
+                    account_msg.owner_key,
+                    account_msg.account_key,
+                    account_msg.data,
+                    account_msg.slot,
+                    account_msg.write_version,
+                    account_msg.endpoint,
+                ) {
+                    error!("更新缓存失败，{}", e);
+                }
+                None
+            }
+            GrpcMessage::Transaction(transaction_msg) => Some(transaction_msg),
+        }
+    }
+
+    /// 按slot和write_version写入，多个Processor乱序处理时旧数据不会覆盖新数据
+    ///
+    /// endpoint : 推送的端点下标，write_version只在同一个端点内可比较
+    fn update_cache(
+        owner: Vec<u8>,
+        account_key: Vec<u8>,
+        data: Vec<u8>,
+        slot: u64,
+        write_version: u64,
+        endpoint: usize,
+    ) -> anyhow::Result<()> {
         let account_key = Pubkey::try_from(account_key)
             .map_or(Err(anyhow!("转换account_key失败")), |a| Ok(a))?;
         let owner = Pubkey::try_from(owner).map_or(Err(anyhow!("转换owner失败")), |a| Ok(a))?;
         update_cache(
             account_key,
             slice_data_auto_get_dex_type(&account_key, &owner, data, SliceType::Subscribed)?,
-        )?;
+            CacheVersion::Account {
+                slot,
+                write_version,
+                endpoint: Some(endpoint),
+            },
+        );
         // match get_dex_type_and_account_type(&owner, &account_key) {
         //     None => {}
         //     Some((dex_type, account_type)) => match dex_type {
@@ -317,6 +342,26 @@ impl BalanceChangeInfo {
         }
     }
 
+    /// 恒定乘积池子的金库余额只由金库决定，直接将交易的post balance写入缓存
+    ///
+    /// index : 交易在slot中的位置，返回是否写入
+    pub fn apply_post_balance(&self, slot: u64, index: u64) -> bool {
+        if !matches!(
+            self.dex_type,
+            DexType::RaydiumAMM | DexType::RaydiumCPMM | DexType::PumpFunAMM
+        ) {
+            return false;
+        }
+        match self.post_account.parse::<u64>() {
+            Ok(amount) => update_cache(
+                self.vault_account,
+                amount.to_le_bytes().to_vec(),
+                CacheVersion::Transaction { slot, index },
+            ),
+            Err(_) => false,
+        }
+    }
+
     pub fn collect_balance_change_infos(
         tx: &[u8],
         message: Option<Message>,
@@ -330,12 +375,7 @@ impl BalanceChangeInfo {
             .chain(meta.loaded_readonly_addresses)
             .map(|v| Pubkey::try_from(v).unwrap())
             .collect::<Vec<_>>();
-        if get_subscribed_accounts()
-            .bitand(&account_keys.to_vec().into_iter().collect::<AHashSet<_>>())
-            .iter()
-            .next()
-            .is_none()
-        {
+        if !contains_subscribed_account(account_keys.as_slice()) {
             return None;
         }
         let pre_token_balances = meta.pre_token_balances;
@@ -1,11 +1,18 @@
-use crate::dex::{get_subscribed_accounts, grpc_subscribe};
+use crate::data_source::{ConnectionState, DataSource, ReconnectBackoff};
+use crate::dex::{
+    grpc_subscribe, is_subscribed_account, update_subscribe_plan, DexType, SubscribePlan,
+    MINT_PROGRAM_ID,
+};
 use crate::dex_data::DexJson;
+use crate::grpc_dedupe::GrpcDeduper;
 use crate::grpc_subscribe;
 use ahash::AHashSet;
 use anyhow::anyhow;
+use async_trait::async_trait;
 use base58::ToBase58;
 use chrono::{DateTime, Local, NaiveDateTime, Utc};
 use flume::Sender;
+use futures_util::future::join_all;
 use solana_sdk::clock::Clock;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::sysvar::SysvarId;
@@ -13,7 +20,9 @@ use spl_token::solana_program::program_pack::Pack;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::sync::watch;
 use tokio_stream::{Stream, StreamExt};
 use tracing::{error, info, warn};
 use yellowstone_grpc_client::GeyserGrpcClient;
@@ -33,36 +42,169 @@ use yellowstone_grpc_proto::tonic::service::Interceptor;
 use yellowstone_grpc_proto::tonic::transport::ClientTlsConfig;
 use yellowstone_grpc_proto::tonic::Status;
 
-pub struct GrpcSubscribe;
+/// 多个端点同时订阅，推送的数据去重后只转发最先到达的
+pub struct GrpcSubscribe {
+    grpc_urls: Vec<String>,
+    backoff: ReconnectBackoff,
+    // 只有一个端点时为None
+    deduper: Option<Arc<GrpcDeduper>>,
+    connection: Arc<ConnectionState>,
+}
 
 pub const POOL_TICK_ARRAY_BITMAP_SEED: &str = "pool_tick_array_bitmap_extension";
 
+#[async_trait]
+impl DataSource for GrpcSubscribe {
+    /// 每个端点独立订阅，断线后按backoff重连并发送相同的订阅请求
+    ///
+    /// 所有端点都断开时推送丢失，重连后重新加载snapshot，加载完成前暂停路由
+    async fn subscribe(
+        &self,
+        dex_data: Vec<DexJson>,
+        dex_data_receiver: flume::Receiver<Vec<DexJson>>,
+        message_sender: Sender<GrpcMessage>,
+    ) {
+        let plan = match SubscribePlan::build(dex_data) {
+            Ok(plan) => plan,
+            Err(e) => {
+                error!("构建GRPC订阅请求失败，原因：{}", e);
+                return;
+            }
+        };
+        let (plan_sender, plan_receiver) = watch::channel(Arc::new(plan));
+        tokio::spawn(update_subscribe_plan(dex_data_receiver, plan_sender));
+        join_all(
+            self.grpc_urls
+                .iter()
+                .enumerate()
+                .map(|(endpoint, grpc_url)| {
+                    self.subscribe_endpoint(
+                        endpoint,
+                        grpc_url.clone(),
+                        plan_receiver.clone(),
+                        message_sender.clone(),
+                    )
+                }),
+        )
+        .await;
+    }
+}
+
 impl GrpcSubscribe {
-    pub async fn subscribe(
+    pub fn new(grpc_urls: Vec<String>, rpc_url: String, backoff: ReconnectBackoff) -> Self {
+        let deduper = (grpc_urls.len() > 1).then(|| Arc::new(GrpcDeduper::new(grpc_urls.clone())));
+        Self {
+            grpc_urls,
+            backoff,
+            deduper,
+            connection: ConnectionState::new(rpc_url, backoff),
+        }
+    }
+
+    pub fn deduper(&self) -> Option<Arc<GrpcDeduper>> {
+        self.deduper.clone()
+    }
+
+    /// endpoint : 端点下标
+    async fn subscribe_endpoint(
         &self,
+        endpoint: usize,
         grpc_url: String,
-        dex_data: Vec<DexJson>,
+        plan_receiver: watch::Receiver<Arc<SubscribePlan>>,
         message_sender: Sender<GrpcMessage>,
     ) {
-        let mut stream = grpc_subscribe(grpc_url, dex_data).await.unwrap();
-        let subscribed_accounts = get_subscribed_accounts();
-        info!("GRPC订阅成功, 等待GRPC推送数据");
+        let mut delay = self.backoff.min_delay;
+        loop {
+            match grpc_subscribe(grpc_url.clone(), plan_receiver.clone()).await {
+                Ok((stream, ping_handle)) => {
+                    info!("GRPC[{}]订阅成功, 等待GRPC推送数据", grpc_url);
+                    self.connection
+                        .on_connected(|| plan_receiver.borrow().dex_json.clone());
+                    // 收到过推送才视为连接恢复，避免连接后立即断开时不断重连
+                    if self.forward(endpoint, stream, &message_sender).await {
+                        delay = self.backoff.min_delay;
+                    }
+                    ping_handle.abort();
+                    self.connection.on_disconnected();
+                }
+                Err(e) => {
+                    error!("GRPC[{}]订阅失败，原因：{}", grpc_url, e);
+                }
+            }
+            warn!("GRPC[{}]连接断开，{:?}后重连", grpc_url, delay);
+            tokio::time::sleep(delay).await;
+            delay = self.backoff.next_delay(delay);
+        }
+    }
+
+    /// 没有其他端点先推送过该Account
+    fn is_first_account(
+        &self,
+        endpoint: usize,
+        account: &SubscribeUpdateAccountInfo,
+        account_key: Pubkey,
+        slot: u64,
+    ) -> bool {
+        self.deduper.as_ref().map_or(true, |deduper| {
+            let owner = Pubkey::try_from(account.owner.as_slice()).unwrap_or_default();
+            deduper.is_first_account(endpoint, owner, account_key, slot, &account.data)
+        })
+    }
+
+    /// 没有其他端点先推送过该交易，按交易涉及的第一个Dex程序统计
+    fn is_first_transaction(
+        &self,
+        endpoint: usize,
+        transaction: &SubscribeUpdateTransactionInfo,
+        slot: u64,
+    ) -> bool {
+        self.deduper.as_ref().map_or(true, |deduper| {
+            let program = transaction
+                .transaction
+                .as_ref()
+                .and_then(|tx| tx.message.as_ref())
+                .and_then(|message| {
+                    message
+                        .account_keys
+                        .iter()
+                        .filter_map(|key| Pubkey::try_from(key.as_slice()).ok())
+                        .find(|key| key != &MINT_PROGRAM_ID && DexType::try_from(key).is_ok())
+                })
+                .unwrap_or_default();
+            deduper.is_first_transaction(endpoint, program, transaction.signature.as_slice(), slot)
+        })
+    }
+
+    /// 将推送的数据转发给Processor，断开时返回是否收到过推送
+    async fn forward(
+        &self,
+        endpoint: usize,
+        mut stream: impl Stream<Item = Result<SubscribeUpdate, Status>> + Unpin,
+        message_sender: &Sender<GrpcMessage>,
+    ) -> bool {
+        let mut received = false;
         while let Some(message) = stream.next().await {
             match message {
                 Ok(data) => {
+                    received = true;
                     let created_at = data.created_at;
                     if let Some(UpdateOneof::Account(account)) = data.update_oneof {
+                        let slot = account.slot;
                         match account.account {
                             Some(acc) => {
                                 let pubkey = Pubkey::try_from(acc.pubkey.as_slice()).unwrap();
-                                if subscribed_accounts.contains(&pubkey) {
+                                if is_subscribed_account(&pubkey)
+                                    && self.is_first_account(endpoint, &acc, pubkey, slot)
+                                {
                                     // info!(
                                     //     "tx {:?}, account : {:?}",
                                     //     acc.txn_signature.as_ref().map_or("11".to_string(),|t|t.as_slice().to_base58()),
                                     //     pubkey
                                     // );
                                     match message_sender
-                                        .send_async(GrpcMessage::Account(GrpcAccountMsg::from(acc)))
+                                        .send_async(GrpcMessage::Account(GrpcAccountMsg::from((
+                                            acc, slot, endpoint,
+                                        ))))
                                         .await
                                     {
                                         Ok(_) => {}
@@ -77,8 +219,7 @@ impl GrpcSubscribe {
                     } else if let Some(UpdateOneof::Transaction(transaction)) = data.update_oneof {
                         let slot = transaction.slot;
                         match transaction.transaction {
-                            None => {}
-                            Some(tx) => {
+                            Some(tx) if self.is_first_transaction(endpoint, &tx, slot) => {
                                 // info!(
                                 //     "tx {:?}",
                                 //     tx.signature.as_slice().to_base58(),
@@ -95,6 +236,7 @@ impl GrpcSubscribe {
                                     }
                                 }
                             }
+                            _ => {}
                         }
                     }
                 }
@@ -104,6 +246,7 @@ impl GrpcSubscribe {
                 }
             }
         }
+        received
     }
 }
 
@@ -120,11 +263,14 @@ pub struct GrpcAccountMsg {
     pub owner_key: Vec<u8>,
     pub data: Vec<u8>,
     pub write_version: u64,
+    pub slot: u64,
+    // 推送的端点下标
+    pub endpoint: usize,
     pub received_timestamp: DateTime<Local>,
 }
 
-impl From<SubscribeUpdateAccountInfo> for GrpcAccountMsg {
-    fn from(account: SubscribeUpdateAccountInfo) -> Self {
+impl From<(SubscribeUpdateAccountInfo, u64, usize)> for GrpcAccountMsg {
+    fn from((account, slot, endpoint): (SubscribeUpdateAccountInfo, u64, usize)) -> Self {
         let time = Local::now();
         let tx = account.txn_signature.unwrap_or([0; 64].try_into().unwrap());
         Self {
@@ -133,6 +279,8 @@ impl From<SubscribeUpdateAccountInfo> for GrpcAccountMsg {
             owner_key: account.owner,
             data: account.data,
             write_version: account.write_version,
+            slot,
+            endpoint,
             received_timestamp: time,
         }
     }
@@ -143,7 +291,7 @@ pub struct GrpcTransactionMsg {
     pub signature: Vec<u8>,
     pub transaction: Option<Transaction>,
     pub meta: Option<TransactionStatusMeta>,
-    pub _index: u64,
+    pub index: u64,
     pub received_timestamp: DateTime<Local>,
     pub slot: u64,
     pub instant: Instant,
@@ -157,7 +305,7 @@ impl From<(SubscribeUpdateTransactionInfo, u64, Timestamp)> for GrpcTransactionM
             signature: transaction.0.signature,
             transaction: transaction.0.transaction,
             meta: transaction.0.meta,
-            _index: transaction.0.index,
+            index: transaction.0.index,
             received_timestamp: time,
             slot: transaction.1,
             instant: Instant::now(),
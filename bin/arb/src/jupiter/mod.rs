@@ -1,5 +1,5 @@
 use crate::dex::InstructionMaterial;
-use crate::dex::{DexType, MINT_PROGRAM_ID};
+use crate::dex::{get_token_program, DexType};
 use crate::graph::SearchResult;
 use crate::jupiter::accounts_type::AccountsType;
 use crate::jupiter::jupiter_route::RouteBuilder;
@@ -10,7 +10,7 @@ use crate::jupiter::swap::Swap;
 use crate::metadata::{get_arb_mint_ata, get_keypair, remove_already_ata, MintAtaPair};
 use crate::HopPathSearchResult;
 use ahash::AHashSet;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use solana_sdk::address_lookup_table::AddressLookupTableAccount;
 use solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_sdk::pubkey;
@@ -28,6 +28,7 @@ pub mod swap;
 /// `jupiter` program ID.
 const JUPITER_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
 const JUPITER_EVENT_AUTHORITY: Pubkey = pubkey!("D8cy77BBepLMngZx6ZukaTff5hCt1HrWyKk3Hnd9oitf");
+const WSOL_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
 
 pub fn build_jupiter_swap_ix(
     hop_path_search_result: HopPathSearchResult,
@@ -38,12 +39,21 @@ pub fn build_jupiter_swap_ix(
     Vec<AddressLookupTableAccount>,
 )> {
     let mut remaining_accounts = Vec::with_capacity(100);
-    let mut route_plan = Vec::with_capacity(2);
-    let mut alts = Vec::with_capacity(2);
     let instruction_materials: Vec<InstructionMaterial> =
         hop_path_search_result.convert_to_instruction_materials()?;
-    let mut used_atas = AHashSet::with_capacity(instruction_materials.len() * 2);
-    for (index, mut material) in instruction_materials.into_iter().enumerate() {
+    let route_steps = hop_path_search_result.route_steps();
+    if route_steps.len() != instruction_materials.len() {
+        return Err(anyhow!(
+            "route plan数量[{}]与swap数量[{}]不一致",
+            route_steps.len(),
+            instruction_materials.len()
+        ));
+    }
+    let hop_count = instruction_materials.len();
+    let mut route_plan = Vec::with_capacity(hop_count);
+    let mut alts = Vec::with_capacity(hop_count);
+    let mut used_atas = AHashSet::with_capacity(hop_count * 2);
+    for (mut material, route_step) in instruction_materials.into_iter().zip(route_steps) {
         let (swap, append_jup_program) = get_jupiter_swap_type(&mut material)?;
         remaining_accounts.push(AccountMeta::new_readonly(
             material.dex_type.get_ref_program_id().clone(),
@@ -56,15 +66,22 @@ pub fn build_jupiter_swap_ix(
         alts.extend(material.alts.unwrap_or(vec![]));
         route_plan.push(RoutePlanStep {
             swap,
-            percent: 100,
-            input_index: if index == 0 { 0 } else { 1 },
-            output_index: if index == 0 { 1 } else { 0 },
+            percent: route_step.percent,
+            input_index: route_step.input_index,
+            output_index: route_step.output_index,
         });
         used_atas.extend(material.used_atas);
     }
     remove_already_ata(&mut used_atas);
-    let arb_mint_ata = get_arb_mint_ata();
     let (amount_in, amount_in_mint): (u64, Pubkey) = hop_path_search_result.amount_in();
+    let arb_mint_ata = get_arb_mint_ata(&amount_in_mint)
+        .ok_or(anyhow!("未配置的arb mint : {}", amount_in_mint))?;
+    // tip以lamports计，只有arb mint为WSOL时才能计入最低输出
+    let quoted_out_amount = if amount_in_mint == WSOL_MINT {
+        amount_in + tip + 5_000
+    } else {
+        amount_in
+    };
     let instruction = RouteBuilder::new()
         .user_transfer_authority(get_keypair().pubkey())
         .user_source_token_account(arb_mint_ata)
@@ -73,10 +90,10 @@ pub fn build_jupiter_swap_ix(
         .destination_token_account(Some(JUPITER_ID))
         .platform_fee_account(Some(JUPITER_ID))
         .program(JUPITER_ID)
-        .token_program(MINT_PROGRAM_ID)
+        .token_program(get_token_program(&amount_in_mint))
         .event_authority(JUPITER_EVENT_AUTHORITY)
         .in_amount(amount_in)
-        .quoted_out_amount(amount_in + tip + 5_000)
+        .quoted_out_amount(quoted_out_amount)
         .slippage_bps(0)
         .platform_fee_bps(0)
         .route_plan(route_plan)
@@ -103,17 +120,26 @@ fn get_jupiter_swap_type(instruction_material: &mut InstructionMaterial) -> Resu
             Swap::WhirlpoolSwapV2 {
                 a_to_b: instruction_material.swap_direction,
                 // 设置remaining account的数量&类型
+                // 顺序与InstructionMaterial中账户的顺序一致
                 remaining_accounts_info: {
-                    instruction_material
-                        .remaining_account_num
-                        .map_or(None, |num| {
-                            Some(RemainingAccountsInfo {
-                                slices: vec![RemainingAccountsSlice {
-                                    accounts_type: AccountsType::SupplementalTickArrays,
-                                    length: num,
-                                }],
-                            })
+                    let (hook_a_num, hook_b_num) = instruction_material.transfer_hook_account_num;
+                    let slices = [
+                        (AccountsType::TransferHookA, hook_a_num),
+                        (AccountsType::TransferHookB, hook_b_num),
+                        (
+                            AccountsType::SupplementalTickArrays,
+                            instruction_material.remaining_account_num,
+                        ),
+                    ]
+                    .into_iter()
+                    .filter_map(|(accounts_type, num)| {
+                        num.map(|length| RemainingAccountsSlice {
+                            accounts_type,
+                            length,
                         })
+                    })
+                    .collect::<Vec<_>>();
+                    (!slices.is_empty()).then_some(RemainingAccountsInfo { slices })
                 },
             },
             false,
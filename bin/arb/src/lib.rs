@@ -1,13 +1,17 @@
 pub mod arb;
 pub mod arb_bot;
+pub mod backtest;
+pub mod data_source;
 pub mod dex;
 pub mod dex_data;
 mod executor;
 mod graph;
+pub mod grpc_dedupe;
 pub mod grpc_processor;
 pub mod grpc_subscribe;
 mod keypair;
 mod metadata;
+mod pool_manager;
 mod jupiter;
 
 pub use graph::*;
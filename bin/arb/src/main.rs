@@ -1,5 +1,6 @@
-use arb::arb_bot;
+use arb::{arb_bot, backtest};
 use chrono::Local;
+use clap::{Parser, Subcommand};
 use mimalloc::MiMalloc;
 use rayon::ThreadPoolBuilder;
 use tracing_appender::non_blocking;
@@ -17,11 +18,26 @@ impl FormatTime for MicrosecondFormatter {
     }
 }
 
+#[derive(Parser)]
+struct Cli {
+    #[command(subcommand)]
+    mode: Mode,
+}
+
+#[derive(Subcommand)]
+enum Mode {
+    /// 实盘套利
+    Run(arb_bot::Command),
+    /// 回放录制文件回测
+    Replay(backtest::ReplayCommand),
+}
+
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
     ThreadPoolBuilder::new()
         .num_threads(num_cpus::get() / 4)
         .build_global()?;
@@ -50,6 +66,9 @@ async fn main() -> anyhow::Result<()> {
         )
         .with(EnvFilter::new("info"))
         .init();
-    arb_bot::start_with_custom().await?;
+    match cli.mode {
+        Mode::Run(command) => arb_bot::start_with_custom(command).await?,
+        Mode::Replay(command) => backtest::start_replay(command).await?,
+    }
     Ok(())
 }
@@ -1,5 +1,5 @@
 use crate::dex::get_token_program;
-use crate::dex::{ATA_PROGRAM_ID, MINT_PROGRAM_ID};
+use crate::dex::ATA_PROGRAM_ID;
 use crate::dex_data::DexJson;
 use crate::keypair::KeypairVault;
 use ahash::{AHashMap, AHashSet};
@@ -23,20 +23,31 @@ use tracing::{error, info};
 
 pub static KEYPAIR: OnceCell<Arc<Keypair>> = OnceCell::const_new();
 static WALLET_OF_ATA_AMOUNT: OnceCell<Arc<RwLock<AHashMap<Pubkey, u64>>>> = OnceCell::const_new();
-static ARB_MINT_ATA_ACCOUNT: OnceCell<Pubkey> = OnceCell::const_new();
+/// arb mint -> 钱包的ATA账户
+static ARB_MINT_ATA_ACCOUNT: OnceCell<AHashMap<Pubkey, Pubkey>> = OnceCell::const_new();
 static LAST_BLOCK_HASH: OnceCell<Arc<RwLock<Hash>>> = OnceCell::const_new();
 
 pub(crate) async fn init_metadata(
     keypair: Keypair,
-    arb_mint: &Pubkey,
+    arb_mints: &[Pubkey],
     dex_data: &[DexJson],
     rpc_client: Arc<RpcClient>,
 ) -> anyhow::Result<()> {
     KEYPAIR.set(Arc::new(keypair))?;
     let wallet = KEYPAIR.get().unwrap().pubkey();
-    let arb_mint_ata =
-        get_associated_token_address_with_program_id(&wallet, arb_mint, &MINT_PROGRAM_ID);
-    ARB_MINT_ATA_ACCOUNT.set(arb_mint_ata)?;
+    let arb_mint_atas = arb_mints
+        .iter()
+        .map(|mint| {
+            (
+                *mint,
+                get_associated_token_address_with_program_id(
+                    &wallet,
+                    mint,
+                    &get_token_program(mint),
+                ),
+            )
+        })
+        .collect::<AHashMap<_, _>>();
     let mint_atas = dex_data
         .iter()
         .map(|json| vec![json.mint_a.clone(), json.mint_b.clone()])
@@ -52,9 +63,11 @@ pub(crate) async fn init_metadata(
             )
             .0
         })
+        .chain(arb_mint_atas.values().cloned())
         .collect::<AHashSet<_>>()
         .into_iter()
         .collect::<Vec<_>>();
+    ARB_MINT_ATA_ACCOUNT.set(arb_mint_atas)?;
     // ata账户更新
     let wallet_all_ata_amount = Arc::new(RwLock::new(
         init_wallet_ata_account(rpc_client.clone(), mint_atas.as_slice()).await,
@@ -90,6 +103,35 @@ pub(crate) async fn init_metadata(
     Ok(())
 }
 
+/// 回放时使用随机钱包和固定的arb mint余额，不请求RPC
+pub(crate) fn init_paper_metadata(arb_mint_balances: &[(Pubkey, u64)]) -> anyhow::Result<()> {
+    let keypair = Keypair::new();
+    let wallet = keypair.pubkey();
+    KEYPAIR.set(Arc::new(keypair))?;
+    let arb_mint_atas = arb_mint_balances
+        .iter()
+        .map(|(mint, _)| {
+            (
+                *mint,
+                get_associated_token_address_with_program_id(
+                    &wallet,
+                    mint,
+                    &get_token_program(mint),
+                ),
+            )
+        })
+        .collect::<AHashMap<_, _>>();
+    WALLET_OF_ATA_AMOUNT.set(Arc::new(RwLock::new(
+        arb_mint_balances
+            .iter()
+            .map(|(mint, amount)| (arb_mint_atas[mint], *amount))
+            .collect(),
+    )))?;
+    ARB_MINT_ATA_ACCOUNT.set(arb_mint_atas)?;
+    LAST_BLOCK_HASH.set(Arc::new(RwLock::new(Hash::default())))?;
+    Ok(())
+}
+
 async fn blockhash_refresher(
     rpc_client: Arc<RpcClient>,
     cached_blockhash: Arc<RwLock<Hash>>,
@@ -123,7 +165,11 @@ async fn init_wallet_ata_account(
     ata_accounts
         .into_iter()
         .filter_map(|info| info)
-        .map(|(key, account)| (key, account.lamports))
+        // token account 的 amount(offset : 64)，Token-2022 账户的前165字节布局相同
+        .filter_map(|(key, account)| {
+            let amount = account.data.get(64..72)?.try_into().ok()?;
+            Some((key, u64::from_le_bytes(amount)))
+        })
         .collect::<AHashMap<_, _>>()
 }
 
@@ -157,16 +203,15 @@ pub fn is_initialized_ata(key: &Pubkey) -> bool {
     WALLET_OF_ATA_AMOUNT.get().unwrap().read().contains_key(key)
 }
 
-pub fn get_arb_mint_ata() -> Pubkey {
-    ARB_MINT_ATA_ACCOUNT.get().unwrap().clone()
+pub fn get_arb_mint_ata(arb_mint: &Pubkey) -> Option<Pubkey> {
+    ARB_MINT_ATA_ACCOUNT.get()?.get(arb_mint).cloned()
 }
 
-pub fn get_arb_mint_ata_amount() -> Option<u64> {
+pub fn get_arb_mint_ata_amount(arb_mint: &Pubkey) -> Option<u64> {
     WALLET_OF_ATA_AMOUNT
-        .get()
-        .unwrap()
+        .get()?
         .read()
-        .get(&get_arb_mint_ata())
+        .get(&get_arb_mint_ata(arb_mint)?)
         .cloned()
 }
 
@@ -0,0 +1,110 @@
+use crate::dex::{
+    extend_account_relations, get_global_cache, load_snapshot, remove_account_relations,
+};
+use crate::dex_data::{load_dex_json, DexJson};
+use crate::graph::{add_pools_to_graph, remove_pools_from_graph, HopPathTypes};
+use ahash::AHashSet;
+use parking_lot::RwLock;
+use solana_rpc_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+/// 定时重新加载DexJson，运行时增删池子
+///
+/// 新增 : 加载snapshot -> account关系 -> 图
+///
+/// 移除 : 图 -> account关系，全局缓存中的数据不删除
+///
+/// 增删后将完整的DexJson发送给GRPC订阅，重新订阅
+pub struct PoolManager {
+    dex_json_path: String,
+    follow_mints: Vec<Pubkey>,
+    hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
+    rpc_client: Arc<RpcClient>,
+    dex_data_sender: flume::Sender<Vec<DexJson>>,
+    // 当前生效的DexJson
+    dex_data: Vec<DexJson>,
+}
+
+impl PoolManager {
+    pub fn new(
+        dex_json_path: String,
+        follow_mints: Vec<Pubkey>,
+        hop_paths: Arc<Vec<RwLock<HopPathTypes>>>,
+        rpc_client: Arc<RpcClient>,
+        dex_data_sender: flume::Sender<Vec<DexJson>>,
+        dex_data: Vec<DexJson>,
+    ) -> Self {
+        Self {
+            dex_json_path,
+            follow_mints,
+            hop_paths,
+            rpc_client,
+            dex_data_sender,
+            dex_data,
+        }
+    }
+
+    pub async fn start(mut self, join_set: &mut JoinSet<()>, reload_interval: Duration) {
+        join_set.spawn(async move {
+            loop {
+                tokio::time::sleep(reload_interval).await;
+                if let Err(e) = self.reload().await {
+                    error!("重新加载DexJson失败，原因 : {}", e);
+                }
+            }
+        });
+    }
+
+    async fn reload(&mut self) -> anyhow::Result<()> {
+        let latest = load_dex_json(self.dex_json_path.clone(), self.follow_mints.as_slice())?;
+        let current_pools = self
+            .dex_data
+            .iter()
+            .map(|json| json.pool)
+            .collect::<AHashSet<_>>();
+        let latest_pools = latest.iter().map(|json| json.pool).collect::<AHashSet<_>>();
+        let removed_pools = current_pools
+            .difference(&latest_pools)
+            .cloned()
+            .collect::<AHashSet<_>>();
+        let mut added_pools = AHashSet::new();
+        let mut added = latest
+            .into_iter()
+            .filter(|json| !current_pools.contains(&json.pool) && added_pools.insert(json.pool))
+            .collect::<Vec<_>>();
+        if added.is_empty() && removed_pools.is_empty() {
+            return Ok(());
+        }
+        if !added.is_empty() {
+            // 移除无效的DexJson
+            load_snapshot(&mut added, self.rpc_client.clone(), get_global_cache()).await?;
+            extend_account_relations(added.as_slice())?;
+            add_pools_to_graph(
+                added.as_slice(),
+                self.follow_mints.as_slice(),
+                self.hop_paths.clone(),
+            )?;
+        }
+        if !removed_pools.is_empty() {
+            remove_pools_from_graph(
+                removed_pools.iter().cloned().collect::<Vec<_>>().as_slice(),
+                self.hop_paths.clone(),
+            )?;
+            remove_account_relations(&removed_pools)?;
+        }
+        info!(
+            "重新加载DexJson, 新增池子 : {}, 移除池子 : {}",
+            added.len(),
+            removed_pools.len()
+        );
+        self.dex_data
+            .retain(|json| !removed_pools.contains(&json.pool));
+        self.dex_data.extend(added);
+        self.dex_data_sender.send(self.dex_data.clone())?;
+        Ok(())
+    }
+}